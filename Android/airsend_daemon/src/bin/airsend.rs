@@ -0,0 +1,621 @@
+//! `airsend`：守护进程 IPC 的命令行客户端，可在 `adb shell` 或 Linux 主机上脚本化调用。
+//!
+//! `send --direct` / `send-text --direct` / `receive --once` 不经过守护进程，
+//! 临时起一个 localsend 节点完成一次传输后退出，适合 CI 与 shell 脚本。
+//!
+//! 退出码：0 成功，1 传输失败，2 其他错误，3 找不到对端或超时，4 对端拒绝
+
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::time::Duration;
+
+use anyhow::{bail, Context, Result};
+use clap::{Args, Parser, Subcommand};
+use localsend::error::{BuildError, LocalSendError};
+use localsend::models::device::DeviceInfo;
+use localsend::net::{TlsConfig, DEFAULT_PORT};
+use localsend::oneshot::{Payload, SendReport, Target};
+use localsend::platform::{Linux, Platform};
+use localsend::transfer::hooks::HookPipeline;
+use localsend::transfer::routing::RoutingTable;
+use localsend::Client;
+use serde_json::Value;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::unix::OwnedReadHalf;
+use tokio::net::UnixStream;
+
+#[path = "../ipc.rs"]
+mod ipc;
+#[path = "../socket.rs"]
+mod socket;
+
+#[derive(Parser)]
+#[command(name = "airsend", version, about = "Control the AirSend daemon")]
+struct Cli {
+    /// Print raw JSON instead of human-readable output
+    #[arg(long, global = true)]
+    json: bool,
+
+    /// Daemon IPC socket; defaults to `$AIRSEND_SOCKET`, then the platform default
+    #[arg(long, global = true)]
+    socket: Option<PathBuf>,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    #[command(flatten)]
+    Daemon(DaemonCommand),
+    /// Accept one incoming transfer without the daemon, then exit
+    Receive {
+        /// Exit after the first session (the only mode supported)
+        #[arg(long, required = true)]
+        once: bool,
+        /// Where received files are written
+        #[arg(long, default_value = ".")]
+        dir: PathBuf,
+        /// Give up after this many seconds; waits forever by default
+        #[arg(long)]
+        timeout: Option<u64>,
+        #[arg(long, default_value_t = DEFAULT_PORT)]
+        port: u16,
+        /// Name shown to the sender
+        #[arg(long, default_value = "airsend")]
+        alias: String,
+        /// Also print received text to stdout
+        #[arg(long)]
+        print_text: bool,
+    },
+}
+
+/// 经守护进程执行的命令；`send`/`send-text` 加 `--direct` 时例外，见 [`run`]
+#[derive(Subcommand)]
+enum DaemonCommand {
+    /// List known peers
+    Peers {
+        /// Run a subnet scan first (for networks that filter multicast)
+        #[arg(long)]
+        scan: bool,
+    },
+    /// Send one or more files
+    Send {
+        #[arg(required = true)]
+        paths: Vec<PathBuf>,
+        /// Peer fingerprint or alias; defaults to the first peer online
+        #[arg(long)]
+        to: Option<String>,
+        #[command(flatten)]
+        direct: Direct,
+    },
+    /// Send text (`-` reads it from stdin)
+    SendText {
+        text: String,
+        #[arg(long)]
+        to: Option<String>,
+        #[command(flatten)]
+        direct: Direct,
+    },
+    /// Show recent transfers, newest first
+    History {
+        #[arg(long, default_value_t = 20)]
+        limit: usize,
+    },
+    /// Show daemon status
+    Status,
+    /// Show or change the daemon's log level until it restarts
+    LogLevel {
+        /// `RUST_LOG` syntax, e.g. `debug` or `info,localsend=trace`
+        level: Option<String>,
+    },
+    /// Manage folders whose new files are sent automatically
+    Watch {
+        #[command(subcommand)]
+        action: WatchAction,
+    },
+    /// Stream daemon events until interrupted
+    TailEvents,
+}
+
+/// 不经守护进程直接发送的选项
+#[derive(Args)]
+struct Direct {
+    /// Send without the daemon: discover the peer, transfer, exit
+    #[arg(long)]
+    direct: bool,
+    /// Connect to `host[:port]` instead of discovering (implies --direct)
+    #[arg(long)]
+    address: Option<String>,
+    /// Seconds to wait for the peer to show up
+    #[arg(long, default_value_t = 10)]
+    timeout: u64,
+    /// Local port for the temporary node
+    #[arg(long, default_value_t = DEFAULT_PORT)]
+    port: u16,
+    /// Name shown to the receiver
+    #[arg(long, default_value = "airsend")]
+    alias: String,
+}
+
+impl Direct {
+    fn enabled(&self) -> bool {
+        self.direct || self.address.is_some()
+    }
+
+    fn target(&self, to: Option<String>) -> Target {
+        match (&self.address, to) {
+            (Some(address), _) => Target::Address(address.clone()),
+            (None, Some(peer)) => Target::Peer(peer),
+            (None, None) => Target::Any,
+        }
+    }
+}
+
+#[derive(Subcommand)]
+enum WatchAction {
+    List,
+    Add {
+        path: PathBuf,
+        #[arg(long)]
+        to: Option<String>,
+    },
+    Remove {
+        path: PathBuf,
+    },
+}
+
+struct Connection {
+    reader: BufReader<OwnedReadHalf>,
+    writer: tokio::net::unix::OwnedWriteHalf,
+}
+
+impl Connection {
+    async fn open(path: &Path) -> Result<Self> {
+        let stream = UnixStream::connect(path).await.with_context(|| {
+            format!("Failed to connect to the AirSend daemon at {} (is it running?)", socket::describe(path))
+        })?;
+        let (reader, writer) = stream.into_split();
+        Ok(Self { reader: BufReader::new(reader), writer })
+    }
+
+    async fn send(&mut self, command: &str) -> Result<()> {
+        self.writer.write_all(format!("{}\n", command).as_bytes()).await?;
+        Ok(())
+    }
+
+    async fn read(&mut self) -> Result<Option<Value>> {
+        let mut line = String::new();
+        if self.reader.read_line(&mut line).await? == 0 {
+            return Ok(None);
+        }
+        Ok(Some(serde_json::from_str(line.trim()).context("Malformed reply from daemon")?))
+    }
+
+    async fn request(&mut self, command: &str) -> Result<Value> {
+        self.send(command).await?;
+        self.read().await?.context("Daemon closed the connection")
+    }
+}
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> ExitCode {
+    let cli = Cli::parse();
+    match run(cli).await {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::FAILURE,
+        Err(e) => {
+            eprintln!("airsend: {:#}", e);
+            ExitCode::from(exit_code(&e))
+        }
+    }
+}
+
+fn exit_code(error: &anyhow::Error) -> u8 {
+    match error.downcast_ref::<LocalSendError>() {
+        Some(LocalSendError::PeerNotFound | LocalSendError::Timeout) => 3,
+        Some(LocalSendError::Rejected) => 4,
+        _ => 2,
+    }
+}
+
+/// 临时节点：不跑接收后处理流水线 (进程随即退出)，收到的文件全部落在 `dir`
+async fn oneshot_client(alias: &str, port: u16, dir: &std::path::Path) -> Result<Client> {
+    let device = DeviceInfo { alias: alias.to_string(), ..Default::default() };
+    let client = Client::builder()
+        .device(device)
+        .port(port)
+        .download_dir(dir)
+        .routes(RoutingTable { rules: Vec::new() })
+        .hooks(HookPipeline::new())
+        .tls(TlsConfig::self_signed())
+        .build()
+        .await;
+    match client {
+        Err(e @ BuildError::Bind { .. }) => {
+            Err(e).with_context(|| format!("Failed to bind port {} (is the daemon running? try --port)", port))
+        }
+        result => Ok(result?),
+    }
+}
+
+async fn send_direct(direct: &Direct, to: Option<String>, payload: Payload, json: bool) -> Result<bool> {
+    let client = oneshot_client(&direct.alias, direct.port, &std::env::temp_dir()).await?;
+    let report = client
+        .send_once(&direct.target(to), payload, Duration::from_secs(direct.timeout))
+        .await?;
+    print_report(&report, json);
+    if report.failed.iter().any(|(_, e)| matches!(e, LocalSendError::Rejected)) {
+        return Err(LocalSendError::Rejected.into());
+    }
+    Ok(report.is_success())
+}
+
+fn print_report(report: &SendReport, json: bool) {
+    if json {
+        let failed: Vec<_> = report
+            .failed
+            .iter()
+            .map(|(name, e)| serde_json::json!({ "name": name, "error": e.to_string() }))
+            .collect();
+        println!(
+            "{}",
+            serde_json::json!({
+                "ok": report.is_success(),
+                "peer": report.peer.alias,
+                "sent": report.sent,
+                "failed": failed,
+            })
+        );
+        return;
+    }
+    for name in &report.sent {
+        println!("✓ {} → {}", name, report.peer.alias);
+    }
+    for (name, e) in &report.failed {
+        eprintln!("✗ {}: {}", name, e);
+    }
+}
+
+fn read_text(text: String) -> Result<String> {
+    if text != "-" {
+        return Ok(text);
+    }
+    let mut buf = String::new();
+    std::io::stdin().read_to_string(&mut buf)?;
+    Ok(buf)
+}
+
+/// 返回 false 表示命令已执行但结果为失败 (退出码 1)
+async fn run(cli: Cli) -> Result<bool> {
+    let json = cli.json;
+    match cli.command {
+        Command::Daemon(DaemonCommand::Send { paths, to, direct }) if direct.enabled() => {
+            send_direct(&direct, to, Payload::Files(paths), json).await
+        }
+        Command::Daemon(DaemonCommand::SendText { text, to, direct }) if direct.enabled() => {
+            send_direct(&direct, to, Payload::Text(read_text(text)?), json).await
+        }
+        Command::Receive { once: _, dir, timeout, port, alias, print_text } => {
+            std::fs::create_dir_all(&dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+            let dir = std::path::absolute(&dir)?;
+            let client = oneshot_client(&alias, port, &dir).await?;
+            let report = client.receive_once(timeout.map(Duration::from_secs)).await?;
+            if json {
+                let files: Vec<_> = report.files.iter().map(|p| p.display().to_string()).collect();
+                println!(
+                    "{}",
+                    serde_json::json!({ "ok": true, "sender": report.sender.alias, "files": files, "texts": report.texts })
+                );
+            } else {
+                for path in &report.files {
+                    println!("✓ {} ← {}", path.display(), report.sender.alias);
+                }
+                for text in &report.texts {
+                    if print_text {
+                        println!("{}", text);
+                    } else {
+                        println!("✓ text ({} bytes) ← {}", text.len(), report.sender.alias);
+                    }
+                }
+            }
+            Ok(true)
+        }
+        Command::Daemon(command) => run_daemon(command, json, &ipc_socket(cli.socket)).await,
+    }
+}
+
+/// 带参命令整行编码为 `<NAME>:<json>`，见 `ipc.rs`
+fn request_line(name: &str, args: &impl serde::Serialize) -> Result<String> {
+    Ok(format!("{}:{}", name, serde_json::to_string(args)?))
+}
+
+/// 守护进程的工作目录与这里不同，必须传绝对路径；非 UTF-8 路径无法放进 JSON
+fn absolute_utf8(path: &Path) -> Result<String> {
+    let path = std::path::absolute(path)?;
+    match path.to_str() {
+        Some(path) => Ok(path.to_string()),
+        None => bail!("Paths that are not valid UTF-8 are not supported: {:?}", path),
+    }
+}
+
+/// 与守护进程的缺省位置一致 (见 `socket.rs`)
+fn ipc_socket(flag: Option<PathBuf>) -> PathBuf {
+    flag.or_else(|| std::env::var_os(socket::SOCKET_ENV).map(PathBuf::from)).unwrap_or_else(|| {
+        let runtime_dir = std::env::var("XDG_RUNTIME_DIR").ok();
+        socket::default_path(cfg!(target_os = "android"), runtime_dir.as_deref(), || Linux::detect().state_dir())
+    })
+}
+
+async fn run_daemon(command: DaemonCommand, json: bool, socket: &Path) -> Result<bool> {
+    let mut conn = Connection::open(socket).await?;
+    match command {
+        DaemonCommand::Peers { scan } => {
+            let peers = conn.request(if scan { "SCAN_PEERS" } else { "GET_PEERS" }).await?;
+            print_peers(&peers, json);
+            Ok(true)
+        }
+        DaemonCommand::Send { paths, to, .. } => {
+            let mut all_ok = true;
+            for path in paths {
+                let path = absolute_utf8(&path)?;
+                let reply = conn
+                    .request(&request_line("SEND_FILE_WAIT", &ipc::SendFile { peer: to.clone(), path: path.clone() })?)
+                    .await?;
+                all_ok &= print_result(&reply, &path, json);
+            }
+            Ok(all_ok)
+        }
+        DaemonCommand::SendText { text, to, .. } => {
+            let text = read_text(text)?;
+            let reply = conn.request(&request_line("SEND_TEXT_WAIT", &ipc::SendText { peer: to, text })?).await?;
+            Ok(print_result(&reply, "text", json))
+        }
+        DaemonCommand::History { limit } => {
+            let history = conn.request(&format!("GET_HISTORY:{}", limit)).await?;
+            if json {
+                println!("{}", history);
+            } else {
+                for entry in history.as_array().into_iter().flatten() {
+                    println!("{}", format_transfer(entry));
+                }
+            }
+            Ok(true)
+        }
+        DaemonCommand::Status => {
+            let status = conn.request("STATUS").await?;
+            if json {
+                println!("{}", status);
+            } else if let Some(fields) = status.as_object() {
+                for (key, value) in fields {
+                    println!("{:<14} {}", format!("{}:", key), plain(value));
+                }
+            }
+            Ok(true)
+        }
+        DaemonCommand::LogLevel { level } => match level {
+            None => {
+                let reply = conn.request("GET_LOG_LEVEL").await?;
+                if json {
+                    println!("{}", reply);
+                } else {
+                    println!("{}", plain(&reply["level"]));
+                }
+                Ok(true)
+            }
+            Some(level) => {
+                let reply = conn.request(&format!("SET_LOG_LEVEL:{}", level)).await?;
+                Ok(print_result(&reply, &format!("log level {}", level), json))
+            }
+        },
+        DaemonCommand::Watch { action } => match action {
+            WatchAction::List => {
+                let folders = conn.request("WATCH_LIST").await?;
+                if json {
+                    println!("{}", folders);
+                } else {
+                    for folder in folders.as_array().into_iter().flatten() {
+                        let peer = folder["peer"].as_str().unwrap_or("(first peer)");
+                        println!("{}  -> {}", plain(&folder["path"]), peer);
+                    }
+                }
+                Ok(true)
+            }
+            WatchAction::Add { path, to } => {
+                let path = absolute_utf8(&path)?;
+                let reply = conn.request(&request_line("WATCH_ADD", &ipc::WatchAdd { peer: to, path: path.clone() })?).await?;
+                Ok(print_result(&reply, &path, json))
+            }
+            WatchAction::Remove { path } => {
+                let path = std::path::absolute(&path)?;
+                let reply = conn.request(&format!("WATCH_REMOVE:{}", path.display())).await?;
+                Ok(print_result(&reply, &path.display().to_string(), json))
+            }
+        },
+        DaemonCommand::TailEvents => {
+            conn.send("TAIL_EVENTS").await?;
+            while let Some(event) = conn.read().await? {
+                if json {
+                    println!("{}", event);
+                } else {
+                    println!("{}", format_event(&event));
+                }
+            }
+            Ok(true)
+        }
+    }
+}
+
+fn plain(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+fn print_peers(peers: &Value, json: bool) {
+    if json {
+        println!("{}", peers);
+        return;
+    }
+    let peers = peers.as_array().map(Vec::as_slice).unwrap_or_default();
+    if peers.is_empty() {
+        println!("No peers found");
+    }
+    for peer in peers {
+        let manual = if peer["manual"].as_bool() == Some(true) { "  (manual)" } else { "" };
+        println!(
+            "{:<24} {:<38} {}{}",
+            plain(&peer["alias"]),
+            plain(&peer["id"]),
+            plain(&peer["device_model"]),
+            manual
+        );
+    }
+}
+
+/// `{ok, error?}` 回复：人类可读模式下打印一行结论，返回是否成功
+fn print_result(reply: &Value, subject: &str, json: bool) -> bool {
+    let ok = reply["ok"].as_bool() == Some(true);
+    if json {
+        println!("{}", reply);
+    } else if ok {
+        println!("✓ {}", subject);
+    } else {
+        let error = reply["error"].as_str().unwrap_or("failed");
+        eprintln!("✗ {}: {}", subject, error);
+    }
+    ok
+}
+
+fn format_transfer(entry: &Value) -> String {
+    let arrow = if entry["direction"] == "sent" { "→" } else { "←" };
+    let peer = entry["peer"].as_str().filter(|p| !p.is_empty()).unwrap_or("?");
+    let outcome = match entry["error"].as_str() {
+        Some(error) => format!("  ✗ {}", error),
+        None => String::new(),
+    };
+    format!(
+        "{}  {} {:<16} {} ({} bytes){}",
+        plain(&entry["time"]),
+        arrow,
+        peer,
+        plain(&entry["name"]),
+        entry["size"],
+        outcome
+    )
+}
+
+fn format_event(event: &Value) -> String {
+    match event["event"].as_str() {
+        Some("transfer") => format_transfer(event),
+        Some("watch_added") => format!("{}  + watching {}", plain(&event["time"]), plain(&event["path"])),
+        Some("watch_removed") => format!("{}  - stopped watching {}", plain(&event["time"]), plain(&event["path"])),
+        _ => event.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+
+    fn parse(args: &[&str]) -> Result<Cli, clap::Error> {
+        Cli::try_parse_from(std::iter::once("airsend").chain(args.iter().copied()))
+    }
+
+    #[test]
+    fn cli_definition_is_consistent() {
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn parses_send_and_direct_targets() {
+        let cli = parse(&["send", "a.txt", "b.txt", "--to", "Mac:mini", "--json"]).unwrap();
+        assert!(cli.json);
+        let Command::Daemon(DaemonCommand::Send { paths, to, direct }) = cli.command else { panic!("expected send") };
+        assert_eq!(paths, [PathBuf::from("a.txt"), PathBuf::from("b.txt")]);
+        assert!(!direct.enabled());
+        assert!(matches!(direct.target(to), Target::Peer(peer) if peer == "Mac:mini"));
+
+        let cli = parse(&["send-text", "hi", "--address", "10.0.0.2:53317"]).unwrap();
+        let Command::Daemon(DaemonCommand::SendText { direct, .. }) = cli.command else { panic!("expected send-text") };
+        assert!(direct.enabled());
+        assert!(matches!(direct.target(Some("ignored".to_string())), Target::Address(a) if a == "10.0.0.2:53317"));
+
+        let cli = parse(&["send", "a.txt", "--direct"]).unwrap();
+        let Command::Daemon(DaemonCommand::Send { to, direct, .. }) = cli.command else { panic!("expected send") };
+        assert!(matches!(direct.target(to), Target::Any));
+    }
+
+    #[test]
+    fn receive_is_handled_without_the_daemon() {
+        let cli = parse(&["receive", "--once", "--dir", "/tmp/in", "--timeout", "30"]).unwrap();
+        let Command::Receive { dir, timeout, port, .. } = cli.command else { panic!("expected receive") };
+        assert_eq!(dir, PathBuf::from("/tmp/in"));
+        assert_eq!(timeout, Some(30));
+        assert_eq!(port, DEFAULT_PORT);
+        assert!(matches!(parse(&["status"]).unwrap().command, Command::Daemon(DaemonCommand::Status)));
+    }
+
+    #[test]
+    fn rejects_incomplete_commands() {
+        assert!(parse(&["send"]).is_err());
+        assert!(parse(&["receive"]).is_err());
+        assert!(parse(&["watch"]).is_err());
+        assert!(parse(&["bogus"]).is_err());
+    }
+
+    #[test]
+    fn socket_flag_is_global() {
+        let cli = parse(&["status", "--socket", "/tmp/a.sock"]).unwrap();
+        assert_eq!(ipc_socket(cli.socket), PathBuf::from("/tmp/a.sock"));
+    }
+
+    #[test]
+    fn request_lines_survive_colons_and_newlines() {
+        let line = request_line("SEND_TEXT_WAIT", &ipc::SendText { peer: Some("Mac:mini".to_string()), text: "a\nb".to_string() })
+            .unwrap();
+        assert_eq!(line, r#"SEND_TEXT_WAIT:{"peer":"Mac:mini","text":"a\nb"}"#);
+        let line = request_line("WATCH_ADD", &ipc::WatchAdd { peer: None, path: "/sdcard/a:b".to_string() }).unwrap();
+        assert_eq!(line, r#"WATCH_ADD:{"path":"/sdcard/a:b"}"#);
+    }
+
+    #[test]
+    fn paths_are_made_absolute() {
+        let path = absolute_utf8(Path::new("a.txt")).unwrap();
+        assert!(Path::new(&path).is_absolute());
+        assert!(path.ends_with("a.txt"));
+
+        use std::os::unix::ffi::OsStrExt;
+        let invalid = Path::new(std::ffi::OsStr::from_bytes(b"/tmp/\xff"));
+        assert!(absolute_utf8(invalid).is_err());
+    }
+
+    #[test]
+    fn exit_codes_follow_the_documented_table() {
+        assert_eq!(exit_code(&anyhow::Error::from(LocalSendError::PeerNotFound)), 3);
+        assert_eq!(exit_code(&anyhow::Error::from(LocalSendError::Timeout)), 3);
+        assert_eq!(exit_code(&anyhow::Error::from(LocalSendError::Rejected)), 4);
+        assert_eq!(exit_code(&anyhow::Error::from(LocalSendError::Rejected).context("sending")), 4);
+        assert_eq!(exit_code(&anyhow::anyhow!("no daemon")), 2);
+    }
+
+    #[test]
+    fn formats_history_and_events() {
+        let entry = serde_json::json!({
+            "time": "2026-01-01T00:00:00+08:00", "direction": "received", "kind": "text",
+            "peer": "", "name": "clipboard", "size": 5, "ok": true,
+        });
+        assert_eq!(format_transfer(&entry), "2026-01-01T00:00:00+08:00  ← ?                clipboard (5 bytes)");
+
+        let event = serde_json::json!({
+            "event": "transfer", "time": "t", "direction": "sent", "peer": "Mac",
+            "name": "/tmp/a", "size": 1, "ok": false, "error": "refused",
+        });
+        assert_eq!(format_event(&event), "t  → Mac              /tmp/a (1 bytes)  ✗ refused");
+        let event = serde_json::json!({ "event": "watch_added", "time": "t", "path": "/tmp/w" });
+        assert_eq!(format_event(&event), "t  + watching /tmp/w");
+    }
+}
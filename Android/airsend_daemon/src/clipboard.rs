@@ -0,0 +1,229 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::VecDeque;
+use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+/// 去重窗口：同一段文本在该时间内重复出现即视为回声/重复
+pub const DEDUP_WINDOW: Duration = Duration::from_secs(30);
+/// 历史条目上限，防止高频复制时无限增长
+const MAX_ENTRIES: usize = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Origin {
+    /// 本机剪贴板产生、由 Daemon 发往对端
+    Local,
+    /// 从对端 (Mac) 收到、推送进本机剪贴板
+    Remote,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verdict {
+    Accept,
+    /// 内容刚从另一侧同步过来，属于回声
+    Echo,
+    /// 内容刚以同一方向处理过，属于重复
+    Duplicate,
+}
+
+struct Entry {
+    hash: u64,
+    origin: Origin,
+    at: Instant,
+}
+
+#[derive(Default, Serialize)]
+pub struct ClipboardStats {
+    pub sent: u64,
+    pub received: u64,
+    pub suppressed_echo: u64,
+    pub suppressed_duplicate: u64,
+    pub tracked: usize,
+}
+
+/// 剪贴板双向同步的防回环闸门
+///
+/// 只记录内容哈希 + 来源 + 时间戳，不保存明文。
+pub struct ClipboardGuard {
+    entries: Mutex<VecDeque<Entry>>,
+    window: Duration,
+    sent: AtomicU64,
+    received: AtomicU64,
+    suppressed_echo: AtomicU64,
+    suppressed_duplicate: AtomicU64,
+}
+
+impl ClipboardGuard {
+    pub fn new(window: Duration) -> Self {
+        Self {
+            entries: Mutex::new(VecDeque::new()),
+            window,
+            sent: AtomicU64::new(0),
+            received: AtomicU64::new(0),
+            suppressed_echo: AtomicU64::new(0),
+            suppressed_duplicate: AtomicU64::new(0),
+        }
+    }
+
+    /// 本机准备发出一段剪贴板文本 (SEND_TEXT)
+    pub fn check_outgoing(&self, text: &str) -> Verdict {
+        self.check(text, Origin::Local)
+    }
+
+    /// 对端推来一段文本，决定是否写入本机剪贴板
+    pub fn check_incoming(&self, text: &str) -> Verdict {
+        self.check(text, Origin::Remote)
+    }
+
+    /// 仅记录，不做拦截（用于用户显式指定目标的分享）
+    pub fn record(&self, text: &str, origin: Origin) {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        self.accept(&mut entries, hash_text(text), origin, Instant::now());
+    }
+
+    pub fn stats(&self) -> ClipboardStats {
+        let tracked = self.entries.lock().map(|e| e.len()).unwrap_or(0);
+        ClipboardStats {
+            sent: self.sent.load(Ordering::Relaxed),
+            received: self.received.load(Ordering::Relaxed),
+            suppressed_echo: self.suppressed_echo.load(Ordering::Relaxed),
+            suppressed_duplicate: self.suppressed_duplicate.load(Ordering::Relaxed),
+            tracked,
+        }
+    }
+
+    fn check(&self, text: &str, origin: Origin) -> Verdict {
+        let hash = hash_text(text);
+        let now = Instant::now();
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+
+        while let Some(front) = entries.front() {
+            if now.duration_since(front.at) > self.window {
+                entries.pop_front();
+            } else {
+                break;
+            }
+        }
+
+        // 只和最近一条记录比较：回声总是紧跟在对侧同步之后出现。
+        // 中间夹了别的内容，再次出现的同一段文本就是用户有意重新复制
+        let verdict = match entries.back() {
+            Some(last) if last.hash == hash && last.origin != origin => Verdict::Echo,
+            Some(last) if last.hash == hash => Verdict::Duplicate,
+            _ => Verdict::Accept,
+        };
+
+        match verdict {
+            Verdict::Accept => self.accept(&mut entries, hash, origin, now),
+            Verdict::Echo => {
+                self.suppressed_echo.fetch_add(1, Ordering::Relaxed);
+            }
+            Verdict::Duplicate => {
+                self.suppressed_duplicate.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        verdict
+    }
+
+    fn accept(&self, entries: &mut VecDeque<Entry>, hash: u64, origin: Origin, at: Instant) {
+        entries.push_back(Entry { hash, origin, at });
+        while entries.len() > MAX_ENTRIES {
+            entries.pop_front();
+        }
+        let counter = match origin {
+            Origin::Local => &self.sent,
+            Origin::Remote => &self.received,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+fn hash_text(text: &str) -> u64 {
+    // 剪贴板在两端往返时可能被追加/去掉末尾换行，统一裁剪后再比较
+    let mut hasher = DefaultHasher::new();
+    text.trim_end_matches(['\r', '\n']).hash(&mut hasher);
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn new_guard() -> ClipboardGuard {
+        ClipboardGuard::new(DEDUP_WINDOW)
+    }
+
+    #[test]
+    fn echo_of_remote_text_is_dropped() {
+        let guard = new_guard();
+        assert_eq!(guard.check_incoming("hello"), Verdict::Accept);
+        assert_eq!(guard.check_outgoing("hello"), Verdict::Echo);
+
+        let guard = new_guard();
+        assert_eq!(guard.check_outgoing("hello"), Verdict::Accept);
+        assert_eq!(guard.check_incoming("hello\n"), Verdict::Echo);
+    }
+
+    #[test]
+    fn back_to_back_repeat_is_a_duplicate() {
+        let guard = new_guard();
+        assert_eq!(guard.check_outgoing("a"), Verdict::Accept);
+        assert_eq!(guard.check_outgoing("a"), Verdict::Duplicate);
+        assert_eq!(guard.check_outgoing("b"), Verdict::Accept);
+        assert_eq!(guard.check_outgoing("a"), Verdict::Accept);
+    }
+
+    #[test]
+    fn deliberate_recopy_after_other_traffic_is_sent() {
+        let guard = new_guard();
+        assert_eq!(guard.check_outgoing("A"), Verdict::Accept);
+        assert_eq!(guard.check_incoming("B"), Verdict::Accept);
+        assert_eq!(guard.check_outgoing("A"), Verdict::Accept);
+
+        let guard = new_guard();
+        assert_eq!(guard.check_incoming("A"), Verdict::Accept);
+        assert_eq!(guard.check_outgoing("B"), Verdict::Accept);
+        assert_eq!(guard.check_outgoing("A"), Verdict::Accept);
+    }
+
+    #[test]
+    fn recorded_share_suppresses_its_echo() {
+        let guard = new_guard();
+        guard.record("shared", Origin::Local);
+        assert_eq!(guard.check_incoming("shared"), Verdict::Echo);
+    }
+
+    #[test]
+    fn entries_expire_after_the_window() {
+        let guard = ClipboardGuard::new(Duration::from_millis(20));
+        assert_eq!(guard.check_incoming("late"), Verdict::Accept);
+        std::thread::sleep(Duration::from_millis(40));
+        assert_eq!(guard.check_outgoing("late"), Verdict::Accept);
+    }
+
+    #[test]
+    fn stats_count_each_verdict() {
+        let guard = new_guard();
+        guard.check_incoming("x");
+        guard.check_outgoing("x");
+        guard.check_outgoing("y");
+        guard.check_outgoing("y");
+        let stats = guard.stats();
+        assert_eq!((stats.sent, stats.received), (1, 1));
+        assert_eq!((stats.suppressed_echo, stats.suppressed_duplicate), (1, 1));
+        assert_eq!(stats.tracked, 2);
+    }
+
+    #[test]
+    fn history_is_bounded() {
+        let guard = new_guard();
+        for i in 0..MAX_ENTRIES + 10 {
+            guard.check_outgoing(&i.to_string());
+        }
+        assert_eq!(guard.stats().tracked, MAX_ENTRIES);
+    }
+}
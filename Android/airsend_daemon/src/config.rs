@@ -0,0 +1,168 @@
+use std::net::{IpAddr, Ipv4Addr};
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use localsend::net::DEFAULT_PORT;
+use localsend::transfer::conflict::ConflictPolicy;
+use localsend::transfer::hooks::{self, HookStep};
+use localsend::transfer::routing::RoutingTable;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DaemonConfig {
+    /// 手动添加的收藏设备（组播被过滤的网络下唯一的发现途径）
+    pub favourites: Vec<FavouritePeer>,
+    /// 对外广播的设备名，缺省沿用协议栈默认值
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub alias: Option<String>,
+    /// 存储根目录：设置后缺省目录改为 `<root>/Download/AirSend` 与
+    /// `<root>/Pictures/AirSend`，便于在沙箱或非标准分区上运行
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub storage_root: Option<String>,
+    /// 接收目录，缺省取平台下载目录 (Android: /sdcard/Download/AirSend)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub download_dir: Option<String>,
+    /// 接收文件的分流规则，按顺序匹配，第一条命中者生效；
+    /// 缺省为图片/视频进平台相册目录，其余进下载目录
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub routes: Option<RoutingTable>,
+    /// 同名文件冲突策略：rename (默认) / overwrite / skip_identical / keep_newest
+    pub conflict_policy: ConflictPolicy,
+    /// 是否把发送方的修改/访问时间写回接收到的文件
+    pub preserve_timestamps: bool,
+    /// 接收后处理流水线，按顺序执行；缺省仅对图片/视频触发媒体扫描
+    pub hooks: Vec<HookStep>,
+    /// 是否监听系统截图目录并自动发送；缺省仅 Android 开启
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub watch_screenshots: Option<bool>,
+    /// 监听目录：新文件落盘后自动发送
+    pub watch: Vec<WatchFolder>,
+    /// 端口与组播参数，缺省与官方 LocalSend 一致
+    pub network: NetworkConfig,
+    /// 日志级别与轮转；设备常年运行，日志文件必须有上限
+    pub log: LogConfig,
+    /// 本机状态页端口 (只监听 127.0.0.1)，提供 /status 与 Prometheus /metrics；缺省关闭
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status_port: Option<u16>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NetworkConfig {
+    /// HTTP 端口，同时向对端广播
+    pub port: u16,
+    /// HTTP 监听地址；0.0.0.0 表示双栈监听全部网卡
+    pub bind: IpAddr,
+    /// 关闭后不加入组播、不广播，只靠收藏设备与网段扫描发现对端
+    pub multicast: bool,
+    pub multicast_group: Ipv4Addr,
+    /// 组播端口，缺省与 HTTP 端口相同
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub multicast_port: Option<u16>,
+}
+
+impl Default for NetworkConfig {
+    fn default() -> Self {
+        Self {
+            port: DEFAULT_PORT,
+            bind: IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            multicast: true,
+            multicast_group: Ipv4Addr::new(224, 0, 0, 167),
+            multicast_port: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LogConfig {
+    /// `RUST_LOG` 语法，环境变量 `RUST_LOG` 优先；运行时可用 IPC `SET_LOG_LEVEL` 临时调整
+    pub level: String,
+    /// 单个日志文件的上限，超过即轮转
+    pub max_size_mb: u64,
+    /// 保留的历史文件数 (`.1` 最新)，0 表示轮转时直接清空
+    pub max_files: usize,
+    /// 按时间轮转的间隔，缺省每天一次；为空则只按大小轮转
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rotate_hours: Option<u64>,
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        Self {
+            level: "info".to_string(),
+            max_size_mb: 5,
+            max_files: 3,
+            rotate_hours: Some(24),
+        }
+    }
+}
+
+impl Default for DaemonConfig {
+    fn default() -> Self {
+        Self {
+            favourites: Vec::new(),
+            alias: None,
+            storage_root: None,
+            download_dir: None,
+            routes: None,
+            conflict_policy: ConflictPolicy::default(),
+            preserve_timestamps: true,
+            hooks: hooks::default_steps(),
+            watch_screenshots: None,
+            watch: Vec::new(),
+            network: NetworkConfig::default(),
+            log: LogConfig::default(),
+            status_port: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FavouritePeer {
+    /// `host:port`，host 可以是 IP 或主机名
+    pub address: String,
+    /// 最近一次探测到的指纹，用于在 GET_PEERS 中标记 manual
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fingerprint: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub alias: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WatchFolder {
+    pub path: String,
+    /// 目标设备的指纹或别名；缺省发给第一个在线设备
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub peer: Option<String>,
+    #[serde(default)]
+    pub recursive: bool,
+}
+
+impl DaemonConfig {
+    /// 文件不存在视为空配置；解析失败则报错，避免静默覆盖用户手写的配置
+    pub fn load(path: &Path) -> Result<Self> {
+        match std::fs::read_to_string(path) {
+            Ok(raw) => serde_json::from_str(&raw)
+                .with_context(|| format!("Failed to parse config {}", path.display())),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e).with_context(|| format!("Failed to read config {}", path.display())),
+        }
+    }
+
+    /// 先写临时文件再 rename，断电也不会留下半截 JSON
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)
+                .with_context(|| format!("Failed to create config dir {}", dir.display()))?;
+        }
+        let tmp = path.with_extension("json.tmp");
+        std::fs::write(&tmp, json)
+            .with_context(|| format!("Failed to write config {}", tmp.display()))?;
+        std::fs::rename(&tmp, path)
+            .with_context(|| format!("Failed to replace config {}", path.display()))?;
+        Ok(())
+    }
+}
@@ -0,0 +1,101 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use localsend::models::device::DeviceInfo;
+use localsend::net::resolve_addr;
+
+use crate::config::FavouritePeer;
+use crate::AppState;
+
+const PROBE_TIMEOUT: Duration = Duration::from_secs(3);
+/// 收藏设备的保活探测间隔
+const PROBE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// 直连探测一个收藏设备，成功则与发现的设备一样记入 peers 表
+pub async fn probe(state: &AppState, favourite: &FavouritePeer) -> Result<DeviceInfo> {
+    let addr = resolve_addr(&favourite.address).await
+        .with_context(|| format!("Failed to resolve {}", favourite.address))?;
+    let device = state.client.fetch_info(addr, PROBE_TIMEOUT).await
+        .with_context(|| format!("Peer {} did not answer /info", favourite.address))?;
+    if device.fingerprint == state.client.device().fingerprint {
+        anyhow::bail!("{} is this device", favourite.address);
+    }
+    state.client.remember_peer(addr, device.clone()).await;
+    Ok(device)
+}
+
+/// ADD_PEER：探测成功后才落盘，避免把打错的地址存成收藏
+pub async fn add(state: &AppState, address: &str) -> Result<DeviceInfo> {
+    state.ensure_config_writable()?;
+    let mut favourite = FavouritePeer { address: address.trim().to_string(), fingerprint: None, alias: None };
+    let device = probe(state, &favourite).await?;
+    favourite.fingerprint = Some(device.fingerprint.clone());
+    favourite.alias = Some(device.alias.clone());
+
+    let mut config = state.config.lock().await;
+    config.favourites.retain(|f| f.address != favourite.address);
+    config.favourites.push(favourite);
+    config.save(&state.config_path)?;
+    Ok(device)
+}
+
+/// REMOVE_PEER：按地址或指纹删除收藏，同时从 peers 表摘除
+pub async fn remove(state: &AppState, key: &str) -> Result<bool> {
+    state.ensure_config_writable()?;
+    let key = key.trim();
+    let mut config = state.config.lock().await;
+    let (removed, kept): (Vec<_>, Vec<_>) = config.favourites.drain(..)
+        .partition(|f| f.address == key || f.fingerprint.as_deref() == Some(key));
+    config.favourites = kept;
+    if removed.is_empty() {
+        return Ok(false);
+    }
+    config.save(&state.config_path)?;
+    drop(config);
+
+    let mut peers = state.client.peers().lock().await;
+    for fingerprint in removed.iter().filter_map(|f| f.fingerprint.as_ref()) {
+        peers.remove(fingerprint);
+    }
+    Ok(true)
+}
+
+pub async fn is_manual(state: &AppState, fingerprint: &str) -> bool {
+    state.config.lock().await.favourites.iter()
+        .any(|f| f.fingerprint.as_deref() == Some(fingerprint))
+}
+
+/// 周期性重探所有收藏设备：重启后恢复、IP 变更后更新指纹/别名
+pub fn spawn_prober(state: Arc<AppState>) {
+    tokio::spawn(async move {
+        loop {
+            let favourites = state.config.lock().await.favourites.clone();
+            let mut dirty = false;
+            for favourite in &favourites {
+                match probe(&state, favourite).await {
+                    Ok(device) => {
+                        let changed = favourite.fingerprint.as_deref() != Some(device.fingerprint.as_str())
+                            || favourite.alias.as_deref() != Some(device.alias.as_str());
+                        if changed {
+                            let mut config = state.config.lock().await;
+                            if let Some(f) = config.favourites.iter_mut().find(|f| f.address == favourite.address) {
+                                f.fingerprint = Some(device.fingerprint);
+                                f.alias = Some(device.alias);
+                                dirty = true;
+                            }
+                        }
+                    }
+                    Err(e) => tracing::debug!("收藏设备 {} 暂不可达: {:#}", favourite.address, e),
+                }
+            }
+            // 配置加载失败时只更新内存，不回写
+            if dirty && state.config_writable {
+                if let Err(e) = state.config.lock().await.save(&state.config_path) {
+                    tracing::warn!("保存收藏设备失败: {:#}", e);
+                }
+            }
+            state.client.clock().sleep(PROBE_INTERVAL).await;
+        }
+    });
+}
@@ -0,0 +1,308 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+use chrono::{Local, SecondsFormat};
+use localsend::events::Event as ClientEvent;
+use localsend::transfer::hooks::{PostReceiveHook, ReceivedFile};
+use serde::Serialize;
+use tokio::sync::broadcast;
+
+/// 传输历史保留条数，只在内存里，重启即清空
+const MAX_ENTRIES: usize = 200;
+/// TAIL_EVENTS 订阅者跟不上时最多积压的事件数
+const EVENT_BACKLOG: usize = 256;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    Sent,
+    Received,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Kind {
+    File,
+    Text,
+}
+
+/// 一次传输的记录。文本只记长度，不保存明文 (与剪贴板闸门一致)
+#[derive(Debug, Clone, Serialize)]
+pub struct Transfer {
+    pub time: String,
+    pub direction: Direction,
+    pub kind: Kind,
+    /// 对端别名 (发送时为目标，接收时为来源)
+    pub peer: String,
+    /// 文件路径；文本为 `clipboard`
+    pub name: String,
+    pub size: u64,
+    pub ok: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// 推送给 `TAIL_EVENTS` 订阅者的事件，一行一个 JSON
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    Transfer(Transfer),
+    WatchAdded { time: String, path: String },
+    WatchRemoved { time: String, path: String },
+}
+
+/// 启动以来的累计数据，历史条目被挤出后仍然有效；供 STATUS 与 /metrics 使用
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct Totals {
+    pub files_sent: u64,
+    pub texts_sent: u64,
+    pub bytes_sent: u64,
+    pub files_received: u64,
+    pub texts_received: u64,
+    pub bytes_received: u64,
+    pub failed_sends: u64,
+}
+
+impl Totals {
+    fn add(&mut self, transfer: &Transfer) {
+        match (transfer.direction, transfer.ok) {
+            (Direction::Sent, false) => self.failed_sends += 1,
+            (Direction::Sent, true) => {
+                match transfer.kind {
+                    Kind::File => self.files_sent += 1,
+                    Kind::Text => self.texts_sent += 1,
+                }
+                self.bytes_sent += transfer.size;
+            }
+            (Direction::Received, _) => {
+                match transfer.kind {
+                    Kind::File => self.files_received += 1,
+                    Kind::Text => self.texts_received += 1,
+                }
+                self.bytes_received += transfer.size;
+            }
+        }
+    }
+}
+
+pub struct History {
+    entries: Mutex<VecDeque<Transfer>>,
+    totals: Mutex<Totals>,
+    events: broadcast::Sender<Event>,
+}
+
+pub fn now() -> String {
+    Local::now().to_rfc3339_opts(SecondsFormat::Secs, false)
+}
+
+impl History {
+    pub fn new() -> Self {
+        let (events, _) = broadcast::channel(EVENT_BACKLOG);
+        Self { entries: Mutex::new(VecDeque::new()), totals: Mutex::new(Totals::default()), events }
+    }
+
+    pub fn record(&self, transfer: Transfer) {
+        self.totals.lock().unwrap_or_else(|e| e.into_inner()).add(&transfer);
+        {
+            let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+            if entries.len() == MAX_ENTRIES {
+                entries.pop_front();
+            }
+            entries.push_back(transfer.clone());
+        }
+        self.emit(Event::Transfer(transfer));
+    }
+
+    /// 没有订阅者时 send 会返回错误，属正常情况
+    pub fn emit(&self, event: Event) {
+        let _ = self.events.send(event);
+    }
+
+    /// 最近 `limit` 条，新的在前
+    pub fn recent(&self, limit: usize) -> Vec<Transfer> {
+        let entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        entries.iter().rev().take(limit).cloned().collect()
+    }
+
+    pub fn totals(&self) -> Totals {
+        *self.totals.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<Event> {
+        self.events.subscribe()
+    }
+
+    /// 协议栈事件：放行的文本在这里记录 (文件由 [`RecordReceived`] 记录)
+    pub fn observe(&self, event: &ClientEvent) {
+        if let ClientEvent::TextReceived { sender, text, .. } = event {
+            self.record(Transfer {
+                time: now(),
+                direction: Direction::Received,
+                kind: Kind::Text,
+                peer: sender.alias.clone(),
+                name: "clipboard".to_string(),
+                size: text.len() as u64,
+                ok: true,
+                error: None,
+            });
+        }
+    }
+}
+
+/// 挂在接收后处理流水线末尾，把落盘结果记进历史
+pub struct RecordReceived(pub Arc<History>);
+
+impl PostReceiveHook for RecordReceived {
+    fn name(&self) -> &str {
+        "history"
+    }
+
+    fn run(&self, received: &mut ReceivedFile) -> std::io::Result<()> {
+        self.0.record(Transfer {
+            time: now(),
+            direction: Direction::Received,
+            kind: Kind::File,
+            peer: received.sender.alias.clone(),
+            name: received.path.to_string_lossy().to_string(),
+            size: received.file.size,
+            ok: true,
+            error: None,
+        });
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use localsend::models::device::DeviceInfo;
+    use localsend::models::file::FileMetadata;
+
+    fn sent(name: &str, size: u64) -> Transfer {
+        Transfer {
+            time: now(),
+            direction: Direction::Sent,
+            kind: Kind::File,
+            peer: "Mac".to_string(),
+            name: name.to_string(),
+            size,
+            ok: true,
+            error: None,
+        }
+    }
+
+    fn device(alias: &str) -> DeviceInfo {
+        DeviceInfo { alias: alias.to_string(), ..DeviceInfo::default() }
+    }
+
+    fn transfer(direction: Direction, kind: Kind, size: u64, ok: bool) -> Transfer {
+        Transfer { direction, kind, size, ok, error: (!ok).then(|| "refused".to_string()), ..sent("x", 0) }
+    }
+
+    #[test]
+    fn totals_count_by_direction_and_kind() {
+        let mut totals = Totals::default();
+        totals.add(&transfer(Direction::Sent, Kind::File, 100, true));
+        totals.add(&transfer(Direction::Sent, Kind::Text, 5, true));
+        totals.add(&transfer(Direction::Received, Kind::File, 1000, true));
+        totals.add(&transfer(Direction::Received, Kind::Text, 7, true));
+        totals.add(&transfer(Direction::Received, Kind::Text, 3, true));
+
+        assert_eq!((totals.files_sent, totals.texts_sent, totals.bytes_sent), (1, 1, 105));
+        assert_eq!((totals.files_received, totals.texts_received, totals.bytes_received), (1, 2, 1010));
+        assert_eq!(totals.failed_sends, 0);
+    }
+
+    #[test]
+    fn failed_sends_count_no_bytes() {
+        let mut totals = Totals::default();
+        totals.add(&transfer(Direction::Sent, Kind::File, 100, false));
+        totals.add(&transfer(Direction::Sent, Kind::Text, 5, false));
+
+        assert_eq!(totals.failed_sends, 2);
+        assert_eq!((totals.files_sent, totals.texts_sent, totals.bytes_sent), (0, 0, 0));
+    }
+
+    #[test]
+    fn recent_is_newest_first_and_limited() {
+        let history = History::new();
+        for name in ["a", "b", "c"] {
+            history.record(sent(name, 1));
+        }
+        let names: Vec<_> = history.recent(2).into_iter().map(|t| t.name).collect();
+        assert_eq!(names, ["c", "b"]);
+        assert_eq!(history.recent(10).len(), 3);
+    }
+
+    #[test]
+    fn old_entries_fall_out_but_totals_keep_counting() {
+        let history = History::new();
+        for i in 0..MAX_ENTRIES + 5 {
+            history.record(sent(&i.to_string(), 2));
+        }
+        let recent = history.recent(usize::MAX);
+        assert_eq!(recent.len(), MAX_ENTRIES);
+        assert_eq!(recent.last().unwrap().name, "5");
+        assert_eq!(history.totals().files_sent, (MAX_ENTRIES + 5) as u64);
+        assert_eq!(history.totals().bytes_sent, 2 * (MAX_ENTRIES + 5) as u64);
+    }
+
+    #[test]
+    fn received_text_records_the_sender() {
+        let history = History::new();
+        history.observe(&ClientEvent::TextReceived {
+            session_id: "s".to_string(),
+            sender: device("MacBook"),
+            text: "hello".to_string(),
+        });
+        history.observe(&ClientEvent::TransferRejected { sender: device("Other") });
+
+        let recent = history.recent(10);
+        assert_eq!(recent.len(), 1);
+        assert_eq!(recent[0].peer, "MacBook");
+        assert_eq!(recent[0].kind, Kind::Text);
+        assert_eq!(recent[0].direction, Direction::Received);
+        assert_eq!(recent[0].size, 5);
+        assert_eq!(history.totals().texts_received, 1);
+    }
+
+    #[test]
+    fn received_files_record_the_final_path_and_sender() {
+        let history = Arc::new(History::new());
+        let mut received = ReceivedFile {
+            path: "/sdcard/Download/AirSend/a.jpg".into(),
+            file: FileMetadata {
+                id: "1".to_string(),
+                file_name: "a.jpg".to_string(),
+                size: 42,
+                file_type: "image/jpeg".to_string(),
+                sha256: None,
+                preview: None,
+                metadata: None,
+            },
+            sender: device("MacBook"),
+        };
+        RecordReceived(history.clone()).run(&mut received).unwrap();
+
+        let entry = &history.recent(1)[0];
+        assert_eq!(entry.peer, "MacBook");
+        assert_eq!(entry.name, "/sdcard/Download/AirSend/a.jpg");
+        assert_eq!(entry.kind, Kind::File);
+        assert_eq!(history.totals().bytes_received, 42);
+    }
+
+    #[test]
+    fn subscribers_see_transfers_and_watch_events() {
+        let history = History::new();
+        let mut events = history.subscribe();
+        history.record(sent("a", 1));
+        history.emit(Event::WatchAdded { time: now(), path: "/tmp/w".to_string() });
+
+        let json: Vec<_> = (0..2).map(|_| serde_json::to_value(events.try_recv().unwrap()).unwrap()).collect();
+        assert_eq!(json[0]["event"], "transfer");
+        assert_eq!(json[0]["name"], "a");
+        assert!(json[0].get("error").is_none());
+        assert_eq!(json[1]["event"], "watch_added");
+        assert_eq!(json[1]["path"], "/tmp/w");
+    }
+}
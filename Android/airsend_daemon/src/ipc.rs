@@ -0,0 +1,58 @@
+//! `airsend` CLI 使用的带参命令，守护进程与 CLI 共用。
+//!
+//! 参数整体编码为一个 JSON 对象，例如 `SEND_FILE_WAIT:{"peer":"Mac:mini","path":"/tmp/a"}`：
+//! 别名里的 `:`、路径与文本里的换行都不会破坏按行分帧的协议。
+//! App 与 Xposed 使用的 `SEND_FILE_TO:` 等旧命令保持原样。
+
+use serde::{Deserialize, Serialize};
+
+/// `SEND_FILE_WAIT`：发送并等待结果；`peer` 为空时发给第一个在线设备
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SendFile {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub peer: Option<String>,
+    pub path: String,
+}
+
+/// `SEND_TEXT_WAIT`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SendText {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub peer: Option<String>,
+    pub text: String,
+}
+
+/// `WATCH_ADD`：目录里的新文件自动发给 `peer`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WatchAdd {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub peer: Option<String>,
+    pub path: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_peers_with_colons_and_text_with_newlines() {
+        let args = SendText { peer: Some("Mac:mini".to_string()), text: "a:b\nc".to_string() };
+        let json = serde_json::to_string(&args).unwrap();
+        assert!(!json.contains('\n'));
+        assert_eq!(serde_json::from_str::<SendText>(&json).unwrap(), args);
+    }
+
+    #[test]
+    fn peer_is_optional() {
+        let json = serde_json::to_string(&SendFile { peer: None, path: "/tmp/a:b".to_string() }).unwrap();
+        assert_eq!(json, r#"{"path":"/tmp/a:b"}"#);
+        let args: WatchAdd = serde_json::from_str(r#"{"path":"/sdcard/DCIM"}"#).unwrap();
+        assert_eq!(args, WatchAdd { peer: None, path: "/sdcard/DCIM".to_string() });
+    }
+
+    #[test]
+    fn rejects_the_old_colon_format() {
+        assert!(serde_json::from_str::<SendFile>("Mac:/tmp/a").is_err());
+        assert!(serde_json::from_str::<SendText>(r#"{"peer":"Mac"}"#).is_err());
+    }
+}
@@ -0,0 +1,270 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use anyhow::{Context, Result};
+use tracing_appender::non_blocking::WorkerGuard;
+use tracing_subscriber::{layer::SubscriberExt, reload, util::SubscriberInitExt, EnvFilter, Registry};
+
+use crate::config::LogConfig;
+use crate::profile::{self, Profile};
+
+/// 运行时调整日志级别 (IPC `SET_LOG_LEVEL`)；只影响本次运行，持久化请改配置 `log.level`
+#[derive(Clone)]
+pub struct LogControl {
+    handle: reload::Handle<EnvFilter, Registry>,
+}
+
+impl LogControl {
+    pub fn level(&self) -> String {
+        self.handle.with_current(|filter| filter.to_string()).unwrap_or_default()
+    }
+
+    /// 接受 `RUST_LOG` 语法，如 `debug` 或 `info,localsend=trace`
+    pub fn set_level(&self, directives: &str) -> Result<String> {
+        let filter = EnvFilter::try_new(directives).with_context(|| format!("无效的日志级别: {}", directives))?;
+        self.handle.reload(filter).context("日志系统已关闭")?;
+        Ok(self.level())
+    }
+}
+
+/// 初始化日志：前台模式写 stderr，后台模式由本进程独占写入轮转日志文件。
+/// `RUST_LOG` 优先于配置里的级别。
+pub fn init(profile: &Profile, config: &LogConfig) -> Result<(WorkerGuard, LogControl)> {
+    let env_filter = EnvFilter::try_from_default_env()
+        .or_else(|_| EnvFilter::try_new(&config.level))
+        .unwrap_or_else(|_| EnvFilter::new("info"));
+    let (filter, handle) = reload::Layer::new(env_filter);
+
+    let (non_blocking, guard) = if profile.foreground {
+        // 🖥️ 前台模式：日志写 stderr，由 systemd/journald 负责收集与轮转
+        tracing_appender::non_blocking(io::stderr())
+    } else {
+        std::fs::create_dir_all(&profile.log_dir)
+            .with_context(|| format!("Failed to create log dir {}", profile.log_dir.display()))?;
+        let file = RotatingFile::open(profile.log_dir.join(profile::LOG_NAME), config)?;
+        tracing_appender::non_blocking(file)
+    };
+
+    tracing_subscriber::registry()
+        .with(filter)
+        // 输出无颜色；🔋 后台模式下 service.sh 不再写这个文件，避免两个写入方交错
+        .with(tracing_subscriber::fmt::layer().with_writer(non_blocking).with_ansi(false))
+        .init();
+
+    log_panics();
+    Ok((guard, LogControl { handle }))
+}
+
+/// panic 信息默认只进 stderr；同时记入日志，否则后台进程崩溃后无迹可查
+fn log_panics() {
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        tracing::error!("💥 守护进程 panic: {}", info);
+        default_hook(info);
+    }));
+}
+
+/// 按大小与时间轮转的日志文件：`airsend_daemon.log` 写满 `max_size_mb` 或
+/// 超过 `rotate_hours` 后改名为 `.1`，旧的依次后移，只保留 `max_files` 份。
+pub struct RotatingFile {
+    path: PathBuf,
+    file: File,
+    written: u64,
+    opened: SystemTime,
+    max_bytes: u64,
+    max_files: usize,
+    max_age: Option<Duration>,
+    /// 轮转失败过一次就不再尝试，否则每次写入都会把编号文件再挪一遍
+    rotation_failed: bool,
+}
+
+impl RotatingFile {
+    pub fn open(path: PathBuf, config: &LogConfig) -> Result<Self> {
+        let file = append(&path).with_context(|| format!("Failed to open log {}", path.display()))?;
+        let metadata = file.metadata()?;
+        Ok(Self {
+            written: metadata.len(),
+            // 重启后沿用已有文件的创建时间，按时间轮转才不会因频繁重启而失效
+            opened: metadata.created().unwrap_or_else(|_| SystemTime::now()),
+            file,
+            path,
+            max_bytes: config.max_size_mb.max(1) * 1024 * 1024,
+            max_files: config.max_files,
+            max_age: config.rotate_hours.filter(|h| *h > 0).map(|h| Duration::from_secs(h * 3600)),
+            rotation_failed: false,
+        })
+    }
+
+    fn due(&self, incoming: usize) -> bool {
+        if self.written == 0 || self.rotation_failed {
+            return false;
+        }
+        let full = self.written + incoming as u64 > self.max_bytes;
+        let expired = self
+            .max_age
+            .is_some_and(|age| self.opened.elapsed().unwrap_or_default() >= age);
+        full || expired
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+        if self.max_files == 0 {
+            // 不保留历史：原地截断
+            self.file.set_len(0)?;
+        } else {
+            let _ = fs::remove_file(numbered(&self.path, self.max_files));
+            for n in (1..self.max_files).rev() {
+                let from = numbered(&self.path, n);
+                if from.exists() {
+                    fs::rename(&from, numbered(&self.path, n + 1))?;
+                }
+            }
+            fs::rename(&self.path, numbered(&self.path, 1))?;
+            // 重新打开成功后才切换；失败时继续写已改名为 `.1` 的旧句柄
+            self.file = append(&self.path)?;
+        }
+        self.written = 0;
+        self.opened = SystemTime::now();
+        Ok(())
+    }
+}
+
+impl Write for RotatingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.due(buf.len()) {
+            // 轮转失败 (如磁盘满) 时继续写原文件，总比丢日志好
+            if let Err(e) = self.rotate() {
+                eprintln!("日志轮转失败，本次运行不再轮转: {}", e);
+                self.rotation_failed = true;
+            }
+        }
+        let n = self.file.write(buf)?;
+        self.written += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+fn append(path: &Path) -> io::Result<File> {
+    OpenOptions::new().create(true).append(true).open(path)
+}
+
+fn numbered(path: &Path, n: usize) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".{}", n));
+    PathBuf::from(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(max_files: usize, rotate_hours: Option<u64>) -> LogConfig {
+        LogConfig { max_files, rotate_hours, ..LogConfig::default() }
+    }
+
+    /// 上限按 MiB 配置，测试里直接改成字节数
+    fn open(dir: &Path, max_files: usize, max_bytes: u64) -> RotatingFile {
+        let mut file = RotatingFile::open(dir.join("d.log"), &config(max_files, None)).unwrap();
+        file.max_bytes = max_bytes;
+        file
+    }
+
+    fn read(dir: &Path, name: &str) -> String {
+        fs::read_to_string(dir.join(name)).unwrap()
+    }
+
+    fn names(dir: &Path) -> Vec<String> {
+        let mut names: Vec<String> =
+            fs::read_dir(dir).unwrap().map(|e| e.unwrap().file_name().to_string_lossy().to_string()).collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn rolls_over_when_full() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut log = open(dir.path(), 3, 10);
+        log.write_all(b"aaaaaaaa\n").unwrap();
+        log.write_all(b"bbbb\n").unwrap();
+        log.flush().unwrap();
+        assert_eq!(read(dir.path(), "d.log"), "bbbb\n");
+        assert_eq!(read(dir.path(), "d.log.1"), "aaaaaaaa\n");
+        assert_eq!(log.written, 5);
+    }
+
+    #[test]
+    fn oversized_first_write_is_kept_whole() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut log = open(dir.path(), 3, 4);
+        log.write_all(b"longer than the limit\n").unwrap();
+        assert_eq!(names(dir.path()), ["d.log"]);
+    }
+
+    #[test]
+    fn shifts_and_keeps_max_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut log = open(dir.path(), 2, 2);
+        for line in ["1\n", "2\n", "3\n", "4\n"] {
+            log.write_all(line.as_bytes()).unwrap();
+        }
+        assert_eq!(names(dir.path()), ["d.log", "d.log.1", "d.log.2"]);
+        assert_eq!(read(dir.path(), "d.log"), "4\n");
+        assert_eq!(read(dir.path(), "d.log.1"), "3\n");
+        assert_eq!(read(dir.path(), "d.log.2"), "2\n");
+    }
+
+    #[test]
+    fn zero_max_files_truncates_in_place() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut log = open(dir.path(), 0, 4);
+        log.write_all(b"old\n").unwrap();
+        log.write_all(b"new\n").unwrap();
+        assert_eq!(names(dir.path()), ["d.log"]);
+        assert_eq!(read(dir.path(), "d.log"), "new\n");
+    }
+
+    #[test]
+    fn rotates_when_expired() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut log = RotatingFile::open(dir.path().join("d.log"), &config(3, Some(1))).unwrap();
+        log.write_all(b"yesterday\n").unwrap();
+        log.write_all(b"still today\n").unwrap();
+        assert_eq!(names(dir.path()), ["d.log"]);
+
+        log.opened = SystemTime::now() - Duration::from_secs(2 * 3600);
+        log.write_all(b"today\n").unwrap();
+        assert_eq!(read(dir.path(), "d.log"), "today\n");
+        assert_eq!(read(dir.path(), "d.log.1"), "yesterday\nstill today\n");
+    }
+
+    #[test]
+    fn reopening_continues_the_existing_file() {
+        let dir = tempfile::tempdir().unwrap();
+        open(dir.path(), 3, 100).write_all(b"before restart\n").unwrap();
+        let log = open(dir.path(), 3, 100);
+        assert_eq!(log.written, "before restart\n".len() as u64);
+    }
+
+    #[test]
+    fn failed_rotation_is_not_retried() {
+        let dir = tempfile::tempdir().unwrap();
+        // `.1` 是非空目录，把日志改名过去必然失败
+        fs::create_dir(dir.path().join("d.log.1")).unwrap();
+        fs::write(dir.path().join("d.log.1/keep"), "").unwrap();
+        let mut log = open(dir.path(), 1, 4);
+        log.write_all(b"one\n").unwrap();
+        log.write_all(b"two\n").unwrap();
+        assert!(log.rotation_failed);
+        log.write_all(b"three\n").unwrap();
+
+        assert_eq!(read(dir.path(), "d.log"), "one\ntwo\nthree\n");
+        assert!(dir.path().join("d.log.1/keep").exists());
+        assert_eq!(log.written, 14);
+    }
+}
@@ -1,26 +1,41 @@
 use tokio::net::{UnixListener, UnixStream};
 use tokio::io::{AsyncBufReadExt, BufReader, AsyncWriteExt};
-use tracing::{info, error, warn, Level};
-use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
-use notify::{Watcher, RecursiveMode, EventKind, event::ModifyKind, event::RenameMode, event::AccessKind, event::AccessMode};
+use tracing::{info, error};
 
-use tracing_subscriber::fmt::format::FmtSpan;
 use anyhow::{Result, Context};
-use std::path::{Path, PathBuf};
+use std::path::PathBuf;
+use localsend::builder::ClientBuilder;
+use localsend::error::BuildError;
+use localsend::models::device::DeviceInfo;
+use localsend::net::TlsConfig;
 use localsend::Client;
-use std::collections::HashMap;
+use localsend::discovery::scan::ScanOptions;
 use std::sync::Arc;
 use tokio::sync::Mutex;
-use localsend::models::file::FileMetadata;
-use bytes::Bytes;
 use std::time::Duration;
-use std::process::Command;
-use tokio::sync::mpsc;
-
-const UDS_PATH: &str = "\0airsend_ipc";
-
-const LOG_PATH: &str = "/data/local/tmp";
-const LOG_FILE: &str = "airsend_daemon.log";
+use localsend::transfer::hooks::HookPipeline;
+use localsend::transfer::upload::TextFilter;
+
+mod clipboard;
+mod config;
+mod favourites;
+mod history;
+mod ipc;
+mod logging;
+mod metrics;
+mod profile;
+mod socket;
+mod watch;
+
+use clipboard::{ClipboardGuard, Origin, Verdict};
+use history::{Direction, History, Kind, RecordReceived, Transfer};
+use metrics::Health;
+use config::{DaemonConfig, WatchFolder};
+use profile::{Parsed, Profile};
+use watch::Watches;
+
+/// 单次发送遇到可重试错误时的最多尝试次数
+const SEND_ATTEMPTS: u32 = 3;
 
 #[tokio::main]
 async fn main() -> Result<()> {
@@ -34,42 +49,161 @@ async fn main() -> Result<()> {
     std::env::remove_var("ALL_PROXY");
     std::env::remove_var("all_proxy");
 
-    let _log_guard = init_logging()?;
-    info!("AirSend Daemon 启动 (LocalSend v0.2.2 兼容模式)");
+    let profile = match Profile::from_args()? {
+        Parsed::Run(profile) => profile,
+        Parsed::Help => {
+            println!("{}", profile::USAGE);
+            return Ok(());
+        }
+    };
+
+    // 日志参数来自配置，所以先读配置；加载失败的原因等日志就绪后再记录
+    // 配置损坏时不阻止启动，回退到默认配置（不会回写覆盖原文件，见 AppState::config_writable）
+    let (config, config_error) = match DaemonConfig::load(&profile.config_path) {
+        Ok(config) => (config, None),
+        Err(e) => (DaemonConfig::default(), Some(e)),
+    };
+    let (_log_guard, log_control) = logging::init(&profile, &config.log)?;
+    info!("AirSend Daemon 启动 (LocalSend v0.2.2 兼容模式, 平台: {})", profile.platform.name());
+    // 📊 运行状况 (STATUS / 状态页)：错误从这里开始记录
+    let health = Arc::new(Health::default());
+    let config_writable = config_error.is_none();
+    if let Some(e) = config_error {
+        error!("配置加载失败，使用默认配置: {:#}", e);
+        health.note_error("config", &e);
+    }
 
     // 1. 强制前置：优先向内核注册 UDS，建立 IPC 物理接收端点
-    let listener = UnixListener::bind(UDS_PATH)
-        .context(format!("Failed to bind abstract UDS: {:?}", UDS_PATH))?;
-    info!("🚀 Successfully bound to UDS: {}", UDS_PATH);
-
-    // 2. 🛡️ 引入韧性轮询：等待系统网络底层设备 (wlan0/tun0) 挂载完成
-    let mut client = loop {
-        match Client::default().await {
-            Ok(c) => {
-                tracing::info!("🌐 网络设备就绪，LocalSend 客户端初始化成功！");
-                break c;
+    let listener = bind_ipc(&profile.ipc_socket).await?;
+    info!("🚀 Successfully bound to UDS: {}", socket::describe(&profile.ipc_socket));
+
+    // 🖥️ 平台层决定默认目录与系统集成 (媒体扫描/通知/剪贴板)
+    let platform = profile.platform.clone();
+
+    // 🪝 接收后处理流水线；📜 历史记录挂在末尾，记下移动/解压后的最终位置
+    let history = Arc::new(History::new());
+    let mut hooks = HookPipeline::from_config(&config.hooks, platform.clone());
+    hooks.push(RecordReceived(history.clone()));
+
+    // 🔁 剪贴板防回环：Mac 推来的文本写入剪贴板后，Xposed 钩子会原样 SEND_TEXT 回来
+    let clipboard = Arc::new(ClipboardGuard::new(clipboard::DEDUP_WINDOW));
+    let guard_for_filter = clipboard.clone();
+    let text_filter: TextFilter = Arc::new(move |text: &str| {
+        match guard_for_filter.check_incoming(text) {
+            // 放行的文本由 TextReceived 事件记进历史，那里带有发送方
+            Verdict::Accept => true,
+            verdict => {
+                info!("🔁 丢弃对端推来的剪贴板 ({:?})", verdict);
+                false
             }
-            Err(_) => {
-                tracing::warn!("等待网络硬件驱动就绪 (os error 19)... 2秒后重试");
+        }
+    });
+
+    // 2. 🌐 组播成员关系由协议栈按网卡 (wlan0/ap0/swlan0...) 动态加入/退出，
+    //    无需再等待 wlan0 就绪；这里只需在端口被上一个实例占用时重试
+    let client = loop {
+        let health_for_events = health.clone();
+        let history_for_events = history.clone();
+        let builder = client_builder(&config, &platform)
+            .hooks(hooks.clone())
+            .text_filter(text_filter.clone())
+            .event_sink(move |event: &localsend::events::Event| {
+                health_for_events.observe(event);
+                history_for_events.observe(event);
+            });
+        match builder.build().await {
+            Ok(c) => break c,
+            Err(e @ BuildError::Bind { .. }) => {
+                tracing::warn!("LocalSend 端口绑定失败: {}，2秒后重试", e);
+                health.note_error("bind", &e);
                 tokio::time::sleep(std::time::Duration::from_secs(2)).await;
             }
+            // 配置错误重试也没用，直接退出让用户修正
+            Err(e) => return Err(e).context("LocalSend 配置无效"),
         }
     };
+    if !config.network.multicast {
+        tracing::info!("🌐 组播已关闭，仅通过收藏设备与子网扫描发现对端");
+    }
+    let interfaces = client.interfaces().await;
+    if interfaces.is_empty() {
+        if config.network.multicast {
+            tracing::warn!("🌐 暂无可用网卡，协议栈将在网卡上线后自动加入组播");
+        }
+    } else {
+        for iface in &interfaces {
+            tracing::info!("🌐 组播已加入网卡 {} ({})", iface.name, iface.addr);
+        }
+    }
+    for iface in client.interfaces_v6().await {
+        tracing::info!("🌐 IPv6 组播已加入网卡 {} ({}%{})", iface.name, iface.addr, iface.index);
+    }
 
-    // 原有的构建 HTTP Client 逻辑保持不变
-    client.http_client = reqwest::Client::builder()
-        .danger_accept_invalid_certs(true)
-        .no_proxy() // 🔪 彻底物理切断所有内置代理探测逻辑
-        .build()
-        .context("Failed to build insecure HTTP client")?;
+    info!("📥 接收目录: {}", client.download_dir());
+
+    // 👁️ 监听目录：系统截图目录 (Android 缺省开启) + 配置里的自定义目录
+    let mut watch_folders = config.watch.clone();
+    if config.watch_screenshots.unwrap_or(profile.is_android()) {
+        watch_folders.extend(platform.screenshot_dirs().into_iter().map(|dir| WatchFolder {
+            path: dir.to_string_lossy().to_string(),
+            peer: None,
+            recursive: false,
+        }));
+    }
+
+    let (watches, watch_events) = Watches::new()?;
+    for folder in watch_folders {
+        if let Err(e) = watches.add(folder) {
+            tracing::warn!("⚠️ {:#}", e);
+        }
+    }
+
+    // 📊 可选的本机状态页；端口被占用不影响收发
+    let status_listener = match config.status_port {
+        Some(port) => match metrics::bind_status_http(port).await {
+            Ok(listener) => Some(listener),
+            Err(e) => {
+                tracing::warn!("⚠️ 状态页未启动: {:#}", e);
+                health.note_error("bind", &e);
+                None
+            }
+        },
+        None => None,
+    };
+    let status_addr = status_listener.as_ref().and_then(|l| l.local_addr().ok());
 
     let state = Arc::new(AppState {
         client,
         preferred_target: Mutex::new(None),
+        clipboard,
+        config: Mutex::new(config),
+        config_path: profile.config_path.clone(),
+        config_writable,
+        history,
+        watches,
+        platform_name: platform.name().to_string(),
+        started: std::time::Instant::now(),
+        log_control,
+        health,
+        status_addr,
+        ipc_socket: profile.ipc_socket.clone(),
     });
 
+    if let Some(listener) = status_listener {
+        info!("📊 状态页: http://{}/status 与 /metrics", status_addr.map(|a| a.to_string()).unwrap_or_default());
+        let state_for_status = state.clone();
+        tokio::spawn(async move {
+            if let Err(e) = metrics::serve_status_http(listener, state_for_status).await {
+                error!("状态页异常退出: {:#}", e);
+            }
+        });
+    }
+
+    // ⭐ 收藏设备：启动即探测一轮，之后定期保活
+    favourites::spawn_prober(state.clone());
+
     // 🚀 点火：启动底层物理监控协程
-    spawn_physical_watcher(state.clone());
+    watch::spawn_physical_watcher(state.clone(), watch_events);
 
     // 3. 启动协议栈：必须扔进 tokio 的并发调度池，决不能阻塞主任务！
 
@@ -77,134 +211,154 @@ async fn main() -> Result<()> {
     tokio::spawn(async move {
         if let Err(e) = state_for_server.client.start().await {
             error!("LocalSend protocol stack crashed: {:?}", e);
+            state_for_server.health.note_error("server", &e);
         }
     });
     info!("LocalSend 协议栈已在后台并发运行");
 
-    loop {
-        match listener.accept().await {
-            Ok((stream, _)) => {
-                let state_clone = state.clone();
-                tokio::spawn(async move {
-                    if let Err(e) = handle_client(stream, state_clone).await {
-                        error!("IPC Error: {:?}", e);
-                    }
-                });
+    let accept_loop = async {
+        loop {
+            match listener.accept().await {
+                Ok((stream, _)) => {
+                    let state_clone = state.clone();
+                    tokio::spawn(async move {
+                        if let Err(e) = handle_client(stream, state_clone).await {
+                            error!("IPC Error: {:?}", e);
+                        }
+                    });
+                }
+                Err(e) => error!("Accept error: {:?}", e),
             }
-            Err(e) => error!("Accept error: {:?}", e),
         }
+    };
+
+    // 前台模式 (systemd)：SIGTERM/SIGINT 时干净退出，而不是被 SIGKILL 收尸
+    tokio::select! {
+        _ = accept_loop => {}
+        _ = shutdown_signal() => info!("收到退出信号，AirSend Daemon 停止"),
     }
+    if !socket::is_abstract(&profile.ipc_socket) {
+        let _ = std::fs::remove_file(&profile.ipc_socket);
+    }
+    Ok(())
 }
 
-struct AppState {
-    client: Client,
-    preferred_target: Mutex<Option<String>>,
-}
+/// 绑定 IPC 套接字。抽象套接字随进程消失；路径套接字要先清掉上次崩溃
+/// 留下的文件，但仍有实例在监听时照旧报错，绑定后收紧为仅属主可读写
+async fn bind_ipc(path: &std::path::Path) -> Result<UnixListener> {
+    use std::os::unix::fs::{DirBuilderExt, FileTypeExt, PermissionsExt};
 
-// 确保你传入了包含 LocalSend 客户端的 state
-pub fn spawn_physical_watcher(state: Arc<AppState>) {
-    // 1. 创建 Tokio 原生的异步 Channel，桥接同步内核中断与异步运行时
-    let (tx, mut rx) = mpsc::unbounded_channel();
-
-    // 2. 将 notify 的事件回调闭包安全推入异步 Channel
-    let mut watcher = notify::recommended_watcher(move |res| {
-        let _ = tx.send(res);
-    }).expect("Failed to create inotify watcher");
-
-    // 3. 穷举双端火力覆盖：应对 AOSP 原生与国内 OEM (如 MIUI/HyperOS/ColorOS) 的魔改路径
-    let target_paths = [
-        "/data/media/0/Pictures/Screenshots", 
-        "/data/media/0/DCIM/Screenshots",     
-    ];
-
-    for watch_path in target_paths {
-        // 同步创建目录，确保探针挂载不报错
-        let _ = std::fs::create_dir_all(watch_path); 
-        
-        if let Err(e) = watcher.watch(Path::new(watch_path), RecursiveMode::NonRecursive) {
-            tracing::warn!("⚠️ 无法绑定 inotify 至 {}: {:?}", watch_path, e);
-        } else {
-            tracing::info!("👁️ 物理 EXT4 探针已深深扎入: {}", watch_path);
+    if socket::is_abstract(path) {
+        return UnixListener::bind(path).context(format!("Failed to bind abstract UDS: {:?}", path));
+    }
+    if let Some(dir) = path.parent() {
+        std::fs::DirBuilder::new()
+            .recursive(true)
+            .mode(0o700)
+            .create(dir)
+            .with_context(|| format!("Failed to create {}", dir.display()))?;
+    }
+    if let Ok(meta) = std::fs::symlink_metadata(path) {
+        if !meta.file_type().is_socket() {
+            anyhow::bail!("{} 已存在且不是套接字", path.display());
         }
+        if UnixStream::connect(path).await.is_ok() {
+            anyhow::bail!("已有 AirSend Daemon 在监听 {}", path.display());
+        }
+        std::fs::remove_file(path).with_context(|| format!("Failed to remove stale {}", path.display()))?;
     }
+    let listener = UnixListener::bind(path).with_context(|| format!("Failed to bind UDS: {}", path.display()))?;
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))
+        .with_context(|| format!("Failed to restrict {}", path.display()))?;
+    Ok(listener)
+}
 
-    // 4. 启动真正的 Tokio 异步消费协程，绝不阻塞主线程
-    tokio::spawn(async move {
-        // 核心：死死锁住 watcher 的生命周期，防止文件句柄被内核强制回收
-        let _keep_watcher_alive = watcher;
-
-        while let Some(res) = rx.recv().await {
-            match res {
-                Ok(event) => {
-                    // 匹配系统截图落盘的真实物理动作 (关闭写入或重命名 .pending)
-                    let is_target_event = match event.kind {
-                        EventKind::Access(AccessKind::Close(AccessMode::Write)) => true,
-                        EventKind::Modify(ModifyKind::Name(RenameMode::To)) => true,
-                        EventKind::Modify(ModifyKind::Name(RenameMode::Both)) => true,
-                        _ => false,
-                    };
-
-                    if is_target_event {
-                        if let Some(path_buf) = event.paths.first() {
-                            let path_str = path_buf.to_string_lossy().to_string();
-                            
-                            // 强力过滤系统 IO 碎片文件
-                            if path_str.ends_with(".tmp") || path_str.ends_with(".pending") || path_buf.file_name().unwrap_or_default().to_string_lossy().starts_with(".") {
-                                continue;
-                            }
-                            
-                            tracing::info!("📸 底层捕获截图物理落盘: {}", path_str);
-                            
-                            let state_clone = state.clone();
-                            tokio::spawn(async move {
-                                // 🔋 灵魂延时：等待 EXT4 Page Cache 刷盘，彻底消灭 0 字节鬼影文件
-                                tokio::time::sleep(std::time::Duration::from_millis(1000)).await;
-                                
-                                tracing::info!("🚀 正在绕过 App 层，直接向 Mac 发射物理路径: {}", path_str);
-                                
-                                // 直接调用 Daemon 内部的 HTTPS 发送引擎
-                                if let Err(e) = send_data(&state_clone, None, &path_str, false).await {
-                                    tracing::error!("❌ 截图底层直发失败: {:?}", e);
-                                }
-                            });
-                        }
-                    }
-                },
-                Err(e) => tracing::error!("inotify watch error: {:?}", e),
-            }
+async fn shutdown_signal() {
+    use tokio::signal::unix::{signal, SignalKind};
+    let mut term = match signal(SignalKind::terminate()) {
+        Ok(term) => term,
+        Err(e) => {
+            error!("无法监听 SIGTERM: {:?}", e);
+            return std::future::pending().await;
         }
-    });
+    };
+    tokio::select! {
+        _ = term.recv() => {}
+        _ = tokio::signal::ctrl_c() => {}
+    }
 }
 
+/// 由配置组装 LocalSend 客户端；端口、组播、目录均可覆盖，缺省值即 Android 上的行为
+fn client_builder(config: &DaemonConfig, platform: &Arc<dyn localsend::platform::Platform>) -> ClientBuilder {
+    let network = &config.network;
+    let mut device = DeviceInfo::default();
+    if let Some(alias) = &config.alias {
+        device.alias = alias.clone();
+    }
+    let mut builder = Client::builder()
+        .device(device)
+        .port(network.port)
+        .bind_addr(network.bind)
+        .multicast(network.multicast)
+        .multicast_group(network.multicast_group)
+        .platform(platform.clone())
+        .conflict_policy(config.conflict_policy)
+        .preserve_timestamps(config.preserve_timestamps)
+        // 对端一律是自签名证书；🔪 彻底物理切断所有内置代理探测逻辑
+        .tls(TlsConfig::self_signed())
+        .configure_http(|builder| builder.no_proxy());
+    if let Some(port) = network.multicast_port {
+        builder = builder.multicast_port(port);
+    }
+    if let Some(root) = &config.storage_root {
+        builder = builder.storage_root(root);
+    }
+    if let Some(dir) = &config.download_dir {
+        builder = builder.download_dir(dir);
+    }
+    // 📂 接收分流规则来自配置文件
+    if let Some(routes) = &config.routes {
+        builder = builder.routes(routes.clone());
+    }
+    builder
+}
 
-fn notify_android_system(file_path: &str) {
-    let _ = Command::new("am")
-        .args(&["broadcast", "-a", "android.intent.action.MEDIA_SCANNER_SCAN_FILE", "-d", &format!("file://{}", file_path)])
-        .spawn();
-    let filename = Path::new(file_path).file_name().and_then(|s| s.to_str()).unwrap_or("新文件");
-    let notification_cmd = format!("cmd notification post -S bigtext -t 'AirSend' 'airsend_rec' '已收到文件: {}'", filename);
-    let _ = Command::new("sh").args(&["-c", &notification_cmd]).spawn();
+struct AppState {
+    client: Client,
+    #[allow(dead_code)]
+    preferred_target: Mutex<Option<String>>,
+    clipboard: Arc<ClipboardGuard>,
+    config: Mutex<DaemonConfig>,
+    config_path: PathBuf,
+    /// 配置加载失败时为 false：内存里是默认配置，落盘会覆盖用户的文件
+    config_writable: bool,
+    history: Arc<History>,
+    watches: Watches,
+    platform_name: String,
+    started: std::time::Instant,
+    log_control: logging::LogControl,
+    health: Arc<Health>,
+    status_addr: Option<std::net::SocketAddr>,
+    ipc_socket: PathBuf,
 }
 
-fn init_logging() -> Result<tracing_appender::non_blocking::WorkerGuard> {
-    let file_appender = tracing_appender::rolling::never(LOG_PATH, LOG_FILE);
-    let (non_blocking, guard) = tracing_appender::non_blocking(file_appender);
-    
-    // 允许使用 RUST_LOG=trace 从环境变量动态控制级别
-    let env_filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
-
-    tracing_subscriber::registry()
-        .with(env_filter)
-        // 输出到文件 (无颜色)
-        .with(tracing_subscriber::fmt::layer().with_writer(non_blocking).with_ansi(false))
-        // 🔋 后台守护进程无需终端输出（service.sh 已将 stdout 重定向到日志文件）
-        .init();
-
-    Ok(guard)
+impl AppState {
+    /// 修改配置的命令先调用；配置加载失败时拒绝，等用户修好文件后重启
+    fn ensure_config_writable(&self) -> Result<()> {
+        if !self.config_writable {
+            anyhow::bail!(
+                "Config {} failed to load; fix it and restart the daemon before changing settings",
+                self.config_path.display()
+            );
+        }
+        Ok(())
+    }
 }
 
 async fn handle_client(stream: UnixStream, state: Arc<AppState>) -> Result<()> {
-    let (reader, mut writer) = stream.into_split();
+    let (reader, writer) = stream.into_split();
+    // 后台命令 (SCAN_PEERS) 完成后也要回写，写端因此共享
+    let writer = Arc::new(Mutex::new(writer));
     let mut buf_reader = BufReader::new(reader);
     let mut line = String::new();
     while buf_reader.read_line(&mut line).await? != 0 {
@@ -214,22 +368,117 @@ async fn handle_client(stream: UnixStream, state: Arc<AppState>) -> Result<()> {
             let cmd_owned = cmd.to_string();
             
             if cmd_owned == "GET_PEERS" {
-                #[derive(serde::Serialize)]
-                struct PeerDto { id: String, alias: String, device_model: String }
-                
-                let peers = state_ref.client.peers.lock().await;
-                let mut peer_list = Vec::new();
-                for (id, (_, info)) in peers.iter() {
-                    peer_list.push(PeerDto {
-                        id: id.clone(),
-                        alias: info.alias.clone(),
-                        device_model: info.device_model.clone().unwrap_or_else(|| "Unknown".to_string()),
-                    });
+                let response = format!("{}\n", peers_json(&state_ref).await);
+                if let Err(e) = writer.lock().await.write_all(response.as_bytes()).await {
+                    error!("Write GET_PEERS error: {:?}", e);
                 }
-                if let Ok(json) = serde_json::to_string(&peer_list) {
+            } else if cmd_owned == "SCAN_PEERS" {
+                // 组播被拦截的网络 (访客 Wi-Fi/企业网) 下由 App 主动触发子网扫描。
+                // 扫描要跑好几秒，放到后台，期间同一连接上的其他命令照常应答
+                let writer = writer.clone();
+                tokio::spawn(async move {
+                    if let Err(e) = state_ref.client.scan_subnets(ScanOptions::default()).await {
+                        error!("Subnet scan failed: {:?}", e);
+                        state_ref.health.note_error("scan", &e);
+                    }
+                    let response = format!("{}\n", peers_json(&state_ref).await);
+                    if let Err(e) = writer.lock().await.write_all(response.as_bytes()).await {
+                        error!("Write SCAN_PEERS error: {:?}", e);
+                    }
+                });
+            } else if let Some(address) = cmd_owned.strip_prefix("ADD_PEER:") {
+                // 手动添加设备：同步返回探测结果，便于 App 立即提示
+                let response = match favourites::add(&state_ref, address).await {
+                    Ok(device) => serde_json::json!({ "ok": true, "id": device.fingerprint, "alias": device.alias }),
+                    Err(e) => serde_json::json!({ "ok": false, "error": format!("{:#}", e) }),
+                };
+                if let Err(e) = writer.lock().await.write_all(format!("{}\n", response).as_bytes()).await {
+                    error!("Write ADD_PEER error: {:?}", e);
+                }
+            } else if let Some(key) = cmd_owned.strip_prefix("REMOVE_PEER:") {
+                let response = match favourites::remove(&state_ref, key).await {
+                    Ok(removed) => serde_json::json!({ "ok": removed }),
+                    Err(e) => serde_json::json!({ "ok": false, "error": format!("{:#}", e) }),
+                };
+                if let Err(e) = writer.lock().await.write_all(format!("{}\n", response).as_bytes()).await {
+                    error!("Write REMOVE_PEER error: {:?}", e);
+                }
+            } else if cmd_owned == "GET_CLIPBOARD_STATS" {
+                if let Ok(json) = serde_json::to_string(&state_ref.clipboard.stats()) {
                     let response = format!("{}\n", json);
-                    if let Err(e) = writer.write_all(response.as_bytes()).await {
-                        error!("Write GET_PEERS error: {:?}", e);
+                    if let Err(e) = writer.lock().await.write_all(response.as_bytes()).await {
+                        error!("Write GET_CLIPBOARD_STATS error: {:?}", e);
+                    }
+                }
+            } else if cmd_owned == "GET_LOG_LEVEL" {
+                reply(&writer, "GET_LOG_LEVEL", serde_json::json!({ "level": state_ref.log_control.level() })).await;
+            } else if let Some(level) = cmd_owned.strip_prefix("SET_LOG_LEVEL:") {
+                let response = match state_ref.log_control.set_level(level) {
+                    Ok(level) => {
+                        info!("📝 日志级别已调整为 {}", level);
+                        serde_json::json!({ "ok": true, "level": level })
+                    }
+                    Err(e) => serde_json::json!({ "ok": false, "error": format!("{:#}", e) }),
+                };
+                reply(&writer, "SET_LOG_LEVEL", response).await;
+            } else if cmd_owned == "STATUS" {
+                reply(&writer, "STATUS", status_json(&state_ref).await).await;
+            } else if let Some(limit) = cmd_owned.strip_prefix("GET_HISTORY") {
+                let limit = limit.trim_start_matches(':').parse().unwrap_or(50);
+                reply(&writer, "GET_HISTORY", serde_json::json!(state_ref.history.recent(limit))).await;
+            } else if cmd_owned == "WATCH_LIST" {
+                reply(&writer, "WATCH_LIST", serde_json::json!(state_ref.watches.list())).await;
+            } else if let Some(rest) = cmd_owned.strip_prefix("WATCH_ADD:") {
+                // WATCH_ADD:{"peer":..,"path":..}，peer 可省略 (发给第一个在线设备)
+                let response = match serde_json::from_str::<ipc::WatchAdd>(rest) {
+                    Ok(args) => {
+                        let peer = args.peer.filter(|p| !p.is_empty());
+                        let folder = WatchFolder { path: args.path, peer, recursive: false };
+                        match watch::add(&state_ref, folder).await {
+                            Ok(()) => serde_json::json!({ "ok": true }),
+                            Err(e) => serde_json::json!({ "ok": false, "error": format!("{:#}", e) }),
+                        }
+                    }
+                    Err(e) => serde_json::json!({ "ok": false, "error": format!("expected WATCH_ADD:{{\"peer\",\"path\"}}: {}", e) }),
+                };
+                reply(&writer, "WATCH_ADD", response).await;
+            } else if let Some(path) = cmd_owned.strip_prefix("WATCH_REMOVE:") {
+                let response = match watch::remove(&state_ref, path).await {
+                    Ok(removed) => serde_json::json!({ "ok": removed }),
+                    Err(e) => serde_json::json!({ "ok": false, "error": format!("{:#}", e) }),
+                };
+                reply(&writer, "WATCH_REMOVE", response).await;
+            } else if let Some(rest) = cmd_owned.strip_prefix("SEND_FILE_WAIT:") {
+                // 与 SEND_FILE_TO 相同，但等待结果并回写 {ok,error}；目标为空时自动选择
+                let response = match serde_json::from_str::<ipc::SendFile>(rest) {
+                    Ok(args) => send_result_json(send_data(&state_ref, args.peer.filter(|p| !p.is_empty()), &args.path, false).await),
+                    Err(e) => serde_json::json!({ "ok": false, "error": format!("expected SEND_FILE_WAIT:{{\"peer\",\"path\"}}: {}", e) }),
+                };
+                reply(&writer, "SEND_FILE_WAIT", response).await;
+            } else if let Some(rest) = cmd_owned.strip_prefix("SEND_TEXT_WAIT:") {
+                let response = match serde_json::from_str::<ipc::SendText>(rest) {
+                    Ok(args) => {
+                        state_ref.clipboard.record(&args.text, Origin::Local);
+                        send_result_json(send_data(&state_ref, args.peer.filter(|p| !p.is_empty()), &args.text, true).await)
+                    }
+                    Err(e) => serde_json::json!({ "ok": false, "error": format!("expected SEND_TEXT_WAIT:{{\"peer\",\"text\"}}: {}", e) }),
+                };
+                reply(&writer, "SEND_TEXT_WAIT", response).await;
+            } else if cmd_owned == "TAIL_EVENTS" {
+                // 长连接：持续推送事件，直到对端断开
+                let mut events = state_ref.history.subscribe();
+                loop {
+                    match events.recv().await {
+                        Ok(event) => {
+                            let line = format!("{}\n", serde_json::to_string(&event).unwrap_or_default());
+                            if writer.lock().await.write_all(line.as_bytes()).await.is_err() {
+                                return Ok(());
+                            }
+                        }
+                        Err(tokio::sync::broadcast::error::RecvError::Lagged(skipped)) => {
+                            tracing::warn!("TAIL_EVENTS 订阅者过慢，丢弃 {} 条事件", skipped);
+                        }
+                        Err(tokio::sync::broadcast::error::RecvError::Closed) => return Ok(()),
                     }
                 }
             } else {
@@ -245,13 +494,53 @@ async fn handle_client(stream: UnixStream, state: Arc<AppState>) -> Result<()> {
     Ok(())
 }
 
+async fn reply(writer: &Mutex<tokio::net::unix::OwnedWriteHalf>, cmd: &str, response: serde_json::Value) {
+    if let Err(e) = writer.lock().await.write_all(format!("{}\n", response).as_bytes()).await {
+        error!("Write {} error: {:?}", cmd, e);
+    }
+}
+
+fn send_result_json(result: Result<()>) -> serde_json::Value {
+    match result {
+        Ok(()) => serde_json::json!({ "ok": true }),
+        Err(e) => serde_json::json!({ "ok": false, "error": format!("{:#}", e) }),
+    }
+}
+
+async fn status_json(state: &AppState) -> serde_json::Value {
+    serde_json::to_value(metrics::snapshot(state).await).unwrap_or_default()
+}
+
+async fn peers_json(state: &AppState) -> String {
+    #[derive(serde::Serialize)]
+    struct PeerDto { id: String, alias: String, device_model: String, manual: bool }
+
+    let peers = state.client.peers().lock().await.clone();
+    let mut peer_list = Vec::with_capacity(peers.len());
+    for (id, (_, info)) in peers {
+        peer_list.push(PeerDto {
+            manual: favourites::is_manual(state, &id).await,
+            id,
+            alias: info.alias,
+            device_model: info.device_model.unwrap_or_else(|| "Unknown".to_string()),
+        });
+    }
+    serde_json::to_string(&peer_list).unwrap_or_else(|_| "[]".to_string())
+}
+
 async fn process_command(cmd: &str, state: &AppState) -> Result<()> {
     if let Some(text) = cmd.strip_prefix("SEND_TEXT:") {
-        send_data(state, None, text, true).await?;
+        // 剪贴板自动同步通道：拦截刚从 Mac 同步过来的回声与连续重复
+        match state.clipboard.check_outgoing(text) {
+            Verdict::Accept => send_data(state, None, text, true).await?,
+            verdict => info!("🔁 跳过剪贴板发送 ({:?})", verdict),
+        }
     } else if let Some(rest) = cmd.strip_prefix("SEND_TEXT_TO:") {
         if let Some(idx) = rest.find(':') {
             let target_id = &rest[..idx];
             let text = &rest[idx+1..];
+            // 用户显式分享不拦截，但要记下来，避免 Mac 端把它再回传
+            state.clipboard.record(text, Origin::Local);
             send_data(state, Some(target_id.to_string()), text, true).await?;
         }
     } else if let Some(path) = cmd.strip_prefix("SEND_FILE:") {
@@ -267,80 +556,96 @@ async fn process_command(cmd: &str, state: &AppState) -> Result<()> {
 }
 
 async fn send_data(state: &AppState, target_id_opt: Option<String>, data: &str, is_text: bool) -> Result<()> {
+    let queued = state.health.queued();
     let mut retries = 0;
     // 💡 提取出 target_id 和 target_addr
-    let (target_id, target_addr) = loop {
+    let (target_id, target_addr, target_alias) = loop {
         {
-            let peers = state.client.peers.lock().await;
+            let peers = state.client.peers().lock().await;
             if let Some(tid) = &target_id_opt {
-                if let Some((addr, _)) = peers.get(tid) {
+                if let Some((addr, info)) = peers.get(tid) {
                     tracing::info!("🔍 指定发送: [{}] {}", tid, addr);
-                    break (tid.clone(), addr.to_string());
+                    break (tid.clone(), addr.to_string(), info.alias.clone());
+                }
+                // 监听目录等配置里更常写别名而不是指纹
+                if let Some((id, (addr, info))) = peers.iter().find(|(_, (_, info))| info.alias.eq_ignore_ascii_case(tid)) {
+                    tracing::info!("🔍 按别名指定发送: {} -> [{}] {}", tid, id, addr);
+                    break (id.clone(), addr.to_string(), info.alias.clone());
                 }
             } else {
-                if let Some((id, (addr, _))) = peers.iter().next() {
+                if let Some((id, (addr, info))) = peers.iter().next() {
                     tracing::info!("🔍 UDP 缓存命中! 发现目标自动抓取: [{}] {}", id, addr);
-                    break (id.clone(), addr.to_string());
+                    break (id.clone(), addr.to_string(), info.alias.clone());
                 }
             }
         }
-        if retries >= 10 { anyhow::bail!("No target found"); }
-        tokio::time::sleep(Duration::from_millis(500)).await;
+        if retries == 10 {
+            // 组播 5 秒内一无所获：多半是网络过滤了组播，退回 HTTP 子网扫描再找一轮
+            tracing::warn!("组播未发现任何设备，启动子网扫描兜底...");
+            if let Err(e) = state.client.scan_subnets(ScanOptions::default()).await {
+                tracing::error!("子网扫描失败: {:?}", e);
+                state.health.note_error("scan", &e);
+            }
+        }
+        if retries > 10 {
+            let error = anyhow::anyhow!("No target found");
+            record_sent(state, target_id_opt.unwrap_or_default(), data, is_text, Err(&error));
+            return Err(error);
+        }
+        state.client.clock().sleep(Duration::from_millis(500)).await;
         retries += 1;
     };
 
-    if is_text {
-        tracing::info!("🚀 正在向 [{}] {} 发起 HTTPS 握手...", target_id, target_addr);
-        // 🚨 关键修复 1：传入 target_id 而不是 target_addr
-        if let Err(e) = send_text_protocol(&state.client, &target_id, data).await {
-            tracing::error!("❌ HTTPS 发送彻底失败，底层错误链:\n{:#?}", e);
-            return Err(e.into());
+    drop(queued);
+    let _sending = state.health.sending();
+
+    // 对端忙 (409/429) 或网络抖动时稍后重试；被拒绝、PIN 错误之类重试也没用
+    let mut attempt = 1;
+    let result = loop {
+        let result = if is_text {
+            tracing::info!("🚀 正在向 [{}] {} 发起 HTTPS 握手...", target_id, target_addr);
+            // 🚨 关键修复 1：传入 target_id 而不是 target_addr
+            state.client.send_text(target_id.clone(), data).await
+        } else {
+            // 🚨 关键修复 2：send_file 同样需要 target_id 作为参数
+            state.client.send_file(target_id.clone(), PathBuf::from(data)).await
+        };
+        match result {
+            Err(e) if e.is_retryable() && attempt < SEND_ATTEMPTS => {
+                tracing::warn!("⏳ 发送暂时失败 ({})，{} 秒后第 {} 次重试", e, attempt, attempt + 1);
+                state.client.clock().sleep(Duration::from_secs(attempt as u64)).await;
+                attempt += 1;
+            }
+            result => break result.map_err(anyhow::Error::from),
         }
-    } else {
-        // 🚨 关键修复 2：send_file 同样需要 target_id 作为参数
-        state.client.send_file(target_id, PathBuf::from(data)).await?;
+    };
+    if let (true, Err(e)) = (is_text, &result) {
+        tracing::error!("❌ HTTPS 发送彻底失败，底层错误链:\n{:#?}", e);
     }
+    record_sent(state, target_alias, data, is_text, result.as_ref().map(|_| ()));
+    result?;
     tracing::info!("✅ 发送成功！");
     Ok(())
 }
 
-// 🚨 关键修复 3：参数名改为 peer_id，并在方法内准确传递给 prepare_upload
-async fn send_text_protocol(client: &Client, peer_id: &str, text: &str) -> Result<()> {
-    let text_bytes = text.as_bytes();
-    let file_id = format!("sync_{}", uuid::Uuid::new_v4());
-    let mut files = HashMap::new();
-    files.insert(file_id.clone(), FileMetadata {
-        id: file_id.clone(),
-        file_name: "clipboard.txt".to_string(),
-        size: text_bytes.len() as u64,
-        file_type: "text/plain".to_string(),
-        sha256: None,
-        preview: None,
-        metadata: None,
+fn record_sent(state: &AppState, peer: String, data: &str, is_text: bool, result: std::result::Result<(), &anyhow::Error>) {
+    let (kind, name, size) = if is_text {
+        (Kind::Text, "clipboard".to_string(), data.len() as u64)
+    } else {
+        (Kind::File, data.to_string(), std::fs::metadata(data).map(|m| m.len()).unwrap_or(0))
+    };
+    state.history.record(Transfer {
+        time: history::now(),
+        direction: Direction::Sent,
+        kind,
+        peer,
+        name,
+        size,
+        ok: result.is_ok(),
+        error: result.err().map(|e| format!("{:#}", e)),
     });
-    
-    tracing::info!("🔄 正在执行 prepare_upload 握手...");
-    // 🚨 关键修复 4：把正确的设备指纹 (peer_id) 传给底层 API
-    let response = client.prepare_upload(peer_id.to_string(), files).await?;
-    tracing::info!("✅ 握手通过，拿到 Session ID: {}", response.session_id);
-    
-    if let Some(token) = response.files.get(&file_id) {
-        client.upload(response.session_id, file_id, token.clone(), Bytes::copy_from_slice(text_bytes)).await?;
+    if let Err(e) = result {
+        state.health.note_error("send", e);
     }
-    Ok(())
 }
 
-// 逆向推送管道：将接收到的文本击穿回 Android App 层
-pub async fn push_text_to_app(text: &str) -> anyhow::Result<()> {
-    tracing::info!("🔄 准备向 Android App 推送剪贴板数据...");
-    
-    // 连接到 App 侧建立的抽象命名空间 Socket
-    let mut stream = UnixStream::connect("\0airsend_app_ipc").await
-        .context("Failed to connect to App's reverse IPC socket (\\0airsend_app_ipc)")?;
-        
-    stream.write_all(text.as_bytes()).await?;
-    stream.shutdown().await?; // 显式关闭发送端，触发 App 侧的 readText() 结束
-    
-    tracing::info!("✅ 成功将文本推送到 Android App");
-    Ok(())
-}
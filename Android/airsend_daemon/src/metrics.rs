@@ -0,0 +1,423 @@
+use std::collections::VecDeque;
+use std::fmt::{Display, Write as _};
+use std::net::{Ipv4Addr, SocketAddr};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use anyhow::{Context, Result};
+use axum::http::header;
+use axum::response::IntoResponse;
+use axum::routing::get;
+use axum::{Extension, Json, Router};
+use localsend::events::Event;
+use localsend::transfer::session::SessionStatus;
+use serde::Serialize;
+
+use crate::clipboard::ClipboardStats;
+use crate::history::{self, Totals};
+use crate::AppState;
+
+/// STATUS 里保留的最近错误条数
+const MAX_ERRORS: usize = 10;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ErrorEntry {
+    pub time: String,
+    /// 出错的环节：send / scan / server / bind / config
+    pub context: &'static str,
+    pub message: String,
+}
+
+/// 运行状况：排队与进行中的发送、最近的错误、对端发现情况。
+/// 传输量的累计在 [`history::Totals`] 里。
+#[derive(Default)]
+pub struct Health {
+    queued: AtomicUsize,
+    sending: AtomicUsize,
+    errors: Mutex<VecDeque<ErrorEntry>>,
+    errors_total: AtomicU64,
+    peers_discovered: AtomicU64,
+    last_peer_discovered: Mutex<Option<String>>,
+}
+
+/// 计数期间在对应仪表上 +1，离开作用域自动 -1
+pub struct Slot<'a>(&'a AtomicUsize);
+
+impl Drop for Slot<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+impl Health {
+    pub fn note_error(&self, context: &'static str, error: impl Display) {
+        self.errors_total.fetch_add(1, Ordering::Relaxed);
+        let mut errors = self.errors.lock().unwrap_or_else(|e| e.into_inner());
+        if errors.len() == MAX_ERRORS {
+            errors.pop_front();
+        }
+        errors.push_back(ErrorEntry { time: history::now(), context, message: format!("{:#}", error) });
+    }
+
+    /// 发送请求在等待目标出现
+    pub fn queued(&self) -> Slot<'_> {
+        self.queued.fetch_add(1, Ordering::Relaxed);
+        Slot(&self.queued)
+    }
+
+    /// 发送请求已找到目标，正在传输 (含重试退避)
+    pub fn sending(&self) -> Slot<'_> {
+        self.sending.fetch_add(1, Ordering::Relaxed);
+        Slot(&self.sending)
+    }
+
+    /// 作为协议栈的事件接收端；在网络任务里内联调用，只做计数
+    pub fn observe(&self, event: &Event) {
+        if let Event::PeerDiscovered { .. } = event {
+            self.peers_discovered.fetch_add(1, Ordering::Relaxed);
+            *self.last_peer_discovered.lock().unwrap_or_else(|e| e.into_inner()) = Some(history::now());
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct Sockets {
+    /// 实际监听的地址；服务器尚未起来时为空
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub http: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub multicast: Option<String>,
+    pub ipc: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status_http: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct Interface {
+    pub name: String,
+    pub addr: String,
+    /// `ipv4` / `ipv6`
+    pub family: &'static str,
+}
+
+#[derive(Serialize)]
+pub struct Discovery {
+    pub multicast: bool,
+    pub interfaces: Vec<Interface>,
+    /// 新出现的对端次数 (组播、注册、扫描)
+    pub peers_discovered: u64,
+    /// 最近一次出现新对端的时间；已知对端的重复广播不算
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_peer_discovered: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct Transfers {
+    pub incoming: usize,
+    pub outgoing: usize,
+    pub queued: usize,
+    pub sending: usize,
+}
+
+/// STATUS 与 /status 的内容；/metrics 由同一份数据渲染
+#[derive(Serialize)]
+pub struct Snapshot {
+    pub version: &'static str,
+    pub platform: String,
+    pub alias: String,
+    pub fingerprint: String,
+    pub port: u16,
+    pub download_dir: String,
+    pub peers: usize,
+    pub watching: usize,
+    pub uptime_secs: u64,
+    pub clipboard: ClipboardStats,
+    pub sockets: Sockets,
+    pub discovery: Discovery,
+    pub transfers: Transfers,
+    pub totals: Totals,
+    pub errors_total: u64,
+    pub last_errors: Vec<ErrorEntry>,
+}
+
+pub async fn snapshot(state: &AppState) -> Snapshot {
+    let client = &state.client;
+    let health = &state.health;
+    let network = state.config.lock().await.network.clone();
+
+    let mut interfaces: Vec<Interface> = client
+        .interfaces()
+        .await
+        .into_iter()
+        .map(|i| Interface { name: i.name, addr: i.addr.to_string(), family: "ipv4" })
+        .collect();
+    interfaces.extend(
+        client
+            .interfaces_v6()
+            .await
+            .into_iter()
+            .map(|i| Interface { name: i.name, addr: format!("{}%{}", i.addr, i.index), family: "ipv6" }),
+    );
+
+    let (mut incoming, mut outgoing) = (0, 0);
+    for session in client.sessions().lock().await.values() {
+        if session.status != SessionStatus::Active {
+            continue;
+        }
+        if session.sender.fingerprint == client.device().fingerprint {
+            outgoing += 1;
+        } else {
+            incoming += 1;
+        }
+    }
+
+    Snapshot {
+        version: env!("CARGO_PKG_VERSION"),
+        platform: state.platform_name.clone(),
+        alias: client.device().alias.clone(),
+        fingerprint: client.device().fingerprint.clone(),
+        port: client.port(),
+        download_dir: client.download_dir().to_string(),
+        peers: client.peers().lock().await.len(),
+        watching: state.watches.list().len(),
+        uptime_secs: state.started.elapsed().as_secs(),
+        clipboard: state.clipboard.stats(),
+        sockets: Sockets {
+            http: client.http_addr().map(|addr| addr.to_string()),
+            multicast: network
+                .multicast
+                .then(|| format!("{}:{}", network.multicast_group, network.multicast_port.unwrap_or(network.port))),
+            ipc: crate::socket::describe(&state.ipc_socket),
+            status_http: state.status_addr.map(|addr| addr.to_string()),
+        },
+        discovery: Discovery {
+            multicast: network.multicast,
+            interfaces,
+            peers_discovered: health.peers_discovered.load(Ordering::Relaxed),
+            last_peer_discovered: health.last_peer_discovered.lock().unwrap_or_else(|e| e.into_inner()).clone(),
+        },
+        transfers: Transfers {
+            incoming,
+            outgoing,
+            queued: health.queued.load(Ordering::Relaxed),
+            sending: health.sending.load(Ordering::Relaxed),
+        },
+        totals: state.history.totals(),
+        errors_total: health.errors_total.load(Ordering::Relaxed),
+        last_errors: health.errors.lock().unwrap_or_else(|e| e.into_inner()).iter().cloned().collect(),
+    }
+}
+
+/// Prometheus 文本格式 (text/plain; version=0.0.4)
+pub fn prometheus(s: &Snapshot) -> String {
+    let mut out = String::new();
+    let mut metric = |name: &str, kind: &str, help: &str, samples: &[(&str, u64)]| {
+        let _ = writeln!(out, "# HELP {} {}", name, help);
+        let _ = writeln!(out, "# TYPE {} {}", name, kind);
+        for (labels, value) in samples {
+            let _ = writeln!(out, "{}{} {}", name, labels, value);
+        }
+    };
+    let t = &s.totals;
+    metric("airsend_uptime_seconds", "gauge", "Seconds since the daemon started.", &[("", s.uptime_secs)]);
+    metric("airsend_peers", "gauge", "Peers currently known.", &[("", s.peers as u64)]);
+    metric("airsend_peers_discovered_total", "counter", "New peers seen by discovery.", &[("", s.discovery.peers_discovered)]);
+    let joined = |family: &str| s.discovery.interfaces.iter().filter(|i| i.family == family).count() as u64;
+    metric(
+        "airsend_multicast_interfaces",
+        "gauge",
+        "Interfaces joined to the multicast group, by address family.",
+        &[("{family=\"ipv4\"}", joined("ipv4")), ("{family=\"ipv6\"}", joined("ipv6"))],
+    );
+    metric(
+        "airsend_sessions",
+        "gauge",
+        "Active LocalSend sessions.",
+        &[("{direction=\"incoming\"}", s.transfers.incoming as u64), ("{direction=\"outgoing\"}", s.transfers.outgoing as u64)],
+    );
+    metric("airsend_sends_queued", "gauge", "Sends waiting for their peer to appear.", &[("", s.transfers.queued as u64)]);
+    metric("airsend_sends_in_progress", "gauge", "Sends in progress, including retry backoff.", &[("", s.transfers.sending as u64)]);
+    metric(
+        "airsend_transfers_total",
+        "counter",
+        "Completed transfers.",
+        &[
+            ("{direction=\"sent\",kind=\"file\"}", t.files_sent),
+            ("{direction=\"sent\",kind=\"text\"}", t.texts_sent),
+            ("{direction=\"received\",kind=\"file\"}", t.files_received),
+            ("{direction=\"received\",kind=\"text\"}", t.texts_received),
+        ],
+    );
+    metric(
+        "airsend_bytes_total",
+        "counter",
+        "Payload bytes transferred.",
+        &[("{direction=\"sent\"}", t.bytes_sent), ("{direction=\"received\"}", t.bytes_received)],
+    );
+    metric("airsend_send_failures_total", "counter", "Sends that failed after all retries.", &[("", t.failed_sends)]);
+    metric("airsend_errors_total", "counter", "Errors recorded in the status report.", &[("", s.errors_total)]);
+    metric(
+        "airsend_clipboard_suppressed_total",
+        "counter",
+        "Clipboard texts dropped as echoes or duplicates.",
+        &[("{reason=\"echo\"}", s.clipboard.suppressed_echo), ("{reason=\"duplicate\"}", s.clipboard.suppressed_duplicate)],
+    );
+    out
+}
+
+/// 本机状态页：`/status` (JSON) 与 `/metrics` (Prometheus)。只监听 127.0.0.1，
+/// 局域网上的其他设备看不到。
+pub async fn bind_status_http(port: u16) -> Result<tokio::net::TcpListener> {
+    let addr = SocketAddr::from((Ipv4Addr::LOCALHOST, port));
+    tokio::net::TcpListener::bind(addr)
+        .await
+        .with_context(|| format!("Failed to bind status endpoint {}", addr))
+}
+
+pub async fn serve_status_http(listener: tokio::net::TcpListener, state: Arc<AppState>) -> Result<()> {
+    let app = Router::new()
+        .route("/status", get(|Extension(state): Extension<Arc<AppState>>| async move { Json(snapshot(&state).await) }))
+        .route(
+            "/metrics",
+            get(|Extension(state): Extension<Arc<AppState>>| async move {
+                let body = prometheus(&snapshot(&state).await);
+                ([(header::CONTENT_TYPE, "text/plain; version=0.0.4")], body).into_response()
+            }),
+        )
+        .layer(Extension(state));
+    axum::serve(listener, app).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot() -> Snapshot {
+        let interface = |name: &str, addr: &str, family| Interface { name: name.to_string(), addr: addr.to_string(), family };
+        Snapshot {
+            version: "0.1.0",
+            platform: "linux".to_string(),
+            alias: "Box".to_string(),
+            fingerprint: "fp".to_string(),
+            port: 53317,
+            download_dir: "/tmp".to_string(),
+            peers: 2,
+            watching: 1,
+            uptime_secs: 90,
+            clipboard: ClipboardStats { suppressed_echo: 4, suppressed_duplicate: 1, ..Default::default() },
+            sockets: Sockets {
+                http: Some("[::]:53317".to_string()),
+                multicast: None,
+                ipc: "@airsend_ipc".to_string(),
+                status_http: None,
+            },
+            discovery: Discovery {
+                multicast: true,
+                interfaces: vec![
+                    interface("wlan0", "192.168.1.5", "ipv4"),
+                    interface("wlan0", "192.168.1.6", "ipv4"),
+                    interface("wlan0", "fe80::1%3", "ipv6"),
+                ],
+                peers_discovered: 7,
+                last_peer_discovered: None,
+            },
+            transfers: Transfers { incoming: 1, outgoing: 0, queued: 2, sending: 1 },
+            totals: Totals { files_sent: 3, bytes_sent: 300, texts_received: 5, bytes_received: 50, failed_sends: 1, ..Default::default() },
+            errors_total: 6,
+            last_errors: Vec::new(),
+        }
+    }
+
+    fn samples(text: &str) -> Vec<&str> {
+        text.lines().filter(|line| !line.starts_with('#')).collect()
+    }
+
+    #[test]
+    fn every_metric_has_help_and_type() {
+        let text = prometheus(&snapshot());
+        let mut names: Vec<&str> = samples(&text)
+            .iter()
+            .map(|line| line.split(['{', ' ']).next().unwrap())
+            .collect();
+        names.dedup();
+        for name in names {
+            assert!(text.contains(&format!("# HELP {} ", name)), "{}", name);
+            assert!(
+                text.contains(&format!("# TYPE {} gauge\n", name)) || text.contains(&format!("# TYPE {} counter\n", name)),
+                "{}",
+                name
+            );
+        }
+        for line in samples(&text) {
+            let value = line.rsplit(' ').next().unwrap();
+            assert!(value.parse::<u64>().is_ok(), "{}", line);
+        }
+    }
+
+    #[test]
+    fn renders_values_and_labels() {
+        let text = prometheus(&snapshot());
+        let samples = samples(&text);
+        for expected in [
+            "airsend_uptime_seconds 90",
+            "airsend_peers 2",
+            "airsend_peers_discovered_total 7",
+            "airsend_multicast_interfaces{family=\"ipv4\"} 2",
+            "airsend_multicast_interfaces{family=\"ipv6\"} 1",
+            "airsend_sessions{direction=\"incoming\"} 1",
+            "airsend_sessions{direction=\"outgoing\"} 0",
+            "airsend_sends_queued 2",
+            "airsend_sends_in_progress 1",
+            "airsend_transfers_total{direction=\"sent\",kind=\"file\"} 3",
+            "airsend_transfers_total{direction=\"received\",kind=\"text\"} 5",
+            "airsend_bytes_total{direction=\"sent\"} 300",
+            "airsend_bytes_total{direction=\"received\"} 50",
+            "airsend_send_failures_total 1",
+            "airsend_errors_total 6",
+            "airsend_clipboard_suppressed_total{reason=\"echo\"} 4",
+            "airsend_clipboard_suppressed_total{reason=\"duplicate\"} 1",
+        ] {
+            assert!(samples.contains(&expected), "missing {}\n{}", expected, text);
+        }
+    }
+
+    #[test]
+    fn status_json_reports_families_and_skips_unknown_sockets() {
+        let mut snapshot = snapshot();
+        snapshot.sockets.http = None;
+        let json = serde_json::to_value(&snapshot).unwrap();
+        assert_eq!(json["discovery"]["interfaces"][2]["family"], "ipv6");
+        assert!(json["sockets"].get("http").is_none());
+        assert!(json["sockets"].get("status_http").is_none());
+        assert_eq!(json["sockets"]["ipc"], "@airsend_ipc");
+    }
+
+    #[test]
+    fn health_counts_slots_errors_and_discoveries() {
+        let health = Health::default();
+        {
+            let _queued = health.queued();
+            let _sending = health.sending();
+            assert_eq!(health.queued.load(Ordering::Relaxed), 1);
+            assert_eq!(health.sending.load(Ordering::Relaxed), 1);
+        }
+        assert_eq!(health.queued.load(Ordering::Relaxed), 0);
+        assert_eq!(health.sending.load(Ordering::Relaxed), 0);
+
+        for i in 0..MAX_ERRORS + 2 {
+            health.note_error("send", i);
+        }
+        let errors = health.errors.lock().unwrap();
+        assert_eq!(errors.len(), MAX_ERRORS);
+        assert_eq!(errors.front().unwrap().message, "2");
+        assert_eq!(health.errors_total.load(Ordering::Relaxed), (MAX_ERRORS + 2) as u64);
+        drop(errors);
+
+        let device = localsend::models::device::DeviceInfo::default();
+        health.observe(&Event::PeerDiscovered { addr: ([10, 0, 0, 2], 53317).into(), device: device.clone() });
+        health.observe(&Event::TransferRejected { sender: device });
+        assert_eq!(health.peers_discovered.load(Ordering::Relaxed), 1);
+        assert!(health.last_peer_discovered.lock().unwrap().is_some());
+    }
+}
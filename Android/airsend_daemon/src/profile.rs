@@ -0,0 +1,169 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use anyhow::{bail, Context, Result};
+use localsend::platform::{self, Android, Linux, Platform};
+
+use crate::socket;
+
+pub const CONFIG_NAME: &str = "airsend_config.json";
+pub const LOG_NAME: &str = "airsend_daemon.log";
+pub const USAGE: &str =
+    "用法: airsend_daemon [--platform android|linux] [--config PATH] [--socket PATH] [--foreground]";
+
+/// 运行环境：Magisk 模块里的 root 守护进程，或 Linux 桌面/服务器上的无头节点。
+///
+/// 平台按编译目标自动选择，可用 `--platform` 或 `AIRSEND_PLATFORM` 覆盖；
+/// 配置与日志默认放在平台的状态目录 (Android: `/data/local/tmp`，
+/// Linux: `$XDG_STATE_HOME/airsend`)。
+pub struct Profile {
+    pub platform: Arc<dyn Platform>,
+    pub config_path: PathBuf,
+    pub log_dir: PathBuf,
+    /// IPC 监听位置，见 [`socket`]
+    pub ipc_socket: PathBuf,
+    /// 前台模式：日志写 stderr (交给 systemd/journald)，收到 SIGTERM 干净退出
+    pub foreground: bool,
+}
+
+/// 命令行解析结果；`--help` 交给调用方打印并退出
+pub enum Parsed {
+    Run(Profile),
+    Help,
+}
+
+impl Profile {
+    pub fn from_args() -> Result<Parsed> {
+        Self::parse(std::env::args().skip(1), |key| std::env::var(key).ok())
+    }
+
+    fn parse(mut args: impl Iterator<Item = String>, env: impl Fn(&str) -> Option<String>) -> Result<Parsed> {
+        let mut platform_name = env("AIRSEND_PLATFORM");
+        let mut config_path = None;
+        let mut ipc_socket = env(socket::SOCKET_ENV).map(PathBuf::from);
+        let mut foreground = false;
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().with_context(|| format!("{} 缺少参数值", arg));
+            match arg.as_str() {
+                "--platform" => platform_name = Some(value()?),
+                "--config" => config_path = Some(PathBuf::from(value()?)),
+                "--socket" => ipc_socket = Some(PathBuf::from(value()?)),
+                "--foreground" | "-f" => foreground = true,
+                "--help" | "-h" => return Ok(Parsed::Help),
+                other => bail!("未知参数: {}", other),
+            }
+        }
+
+        let platform: Arc<dyn Platform> = match platform_name.as_deref() {
+            None => platform::native(),
+            Some("android") => Arc::new(Android),
+            Some("linux") => Arc::new(Linux::detect()),
+            Some(other) => bail!("未知平台: {} (可选 android / linux)", other),
+        };
+        let state_dir = platform.state_dir();
+        let ipc_socket = ipc_socket.unwrap_or_else(|| {
+            let runtime_dir = env("XDG_RUNTIME_DIR");
+            socket::default_path(platform.name() == "android", runtime_dir.as_deref(), || state_dir.clone())
+        });
+        Ok(Parsed::Run(Self {
+            config_path: config_path.unwrap_or_else(|| state_dir.join(CONFIG_NAME)),
+            log_dir: state_dir,
+            ipc_socket,
+            platform,
+            foreground,
+        }))
+    }
+
+    pub fn is_android(&self) -> bool {
+        self.platform.name() == "android"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str], env: &[(&str, &str)]) -> Result<Parsed> {
+        let env: Vec<(String, String)> = env.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+        Profile::parse(args.iter().map(|a| a.to_string()), move |key| {
+            env.iter().find(|(k, _)| k == key).map(|(_, v)| v.clone())
+        })
+    }
+
+    fn run(args: &[&str], env: &[(&str, &str)]) -> Profile {
+        match parse(args, env).unwrap() {
+            Parsed::Run(profile) => profile,
+            Parsed::Help => panic!("unexpected --help"),
+        }
+    }
+
+    #[test]
+    fn help_stops_parsing() {
+        assert!(matches!(parse(&["--help"], &[]).unwrap(), Parsed::Help));
+        assert!(matches!(parse(&["-f", "-h", "--bogus"], &[]).unwrap(), Parsed::Help));
+    }
+
+    #[test]
+    fn rejects_unknown_arguments_and_platforms() {
+        assert!(parse(&["--bogus"], &[]).is_err());
+        assert!(parse(&["--platform", "windows"], &[]).is_err());
+        assert!(parse(&[], &[("AIRSEND_PLATFORM", "windows")]).is_err());
+    }
+
+    #[test]
+    fn rejects_missing_values() {
+        for flag in ["--platform", "--config", "--socket"] {
+            let err = parse(&[flag], &[]).err().unwrap();
+            assert!(err.to_string().contains(flag), "{}", err);
+        }
+    }
+
+    #[test]
+    fn android_uses_the_abstract_socket() {
+        let profile = run(&["--platform", "android"], &[("XDG_RUNTIME_DIR", "/run/user/0")]);
+        assert!(profile.is_android());
+        assert_eq!(profile.ipc_socket, PathBuf::from(socket::ABSTRACT_NAME));
+        assert_eq!(profile.config_path, profile.log_dir.join(CONFIG_NAME));
+        assert!(!profile.foreground);
+    }
+
+    #[test]
+    fn linux_uses_a_path_socket_in_the_runtime_dir() {
+        let profile = run(&["--platform", "linux", "-f"], &[("XDG_RUNTIME_DIR", "/run/user/1000")]);
+        assert!(!profile.is_android());
+        assert_eq!(profile.ipc_socket, PathBuf::from("/run/user/1000/airsend.sock"));
+        assert!(!socket::is_abstract(&profile.ipc_socket));
+        assert!(profile.foreground);
+    }
+
+    #[test]
+    fn linux_falls_back_to_the_state_dir() {
+        let profile = run(&["--platform", "linux"], &[]);
+        assert_eq!(profile.ipc_socket, profile.log_dir.join(socket::SOCKET_NAME));
+        // 相对路径的 XDG_RUNTIME_DIR 按规范忽略
+        let profile = run(&["--platform", "linux"], &[("XDG_RUNTIME_DIR", "run")]);
+        assert_eq!(profile.ipc_socket, profile.log_dir.join(socket::SOCKET_NAME));
+    }
+
+    #[test]
+    fn flags_override_environment() {
+        let env = [("AIRSEND_PLATFORM", "android"), (socket::SOCKET_ENV, "/tmp/env.sock")];
+        let profile = run(&[], &env);
+        assert!(profile.is_android());
+        assert_eq!(profile.ipc_socket, PathBuf::from("/tmp/env.sock"));
+
+        let profile = run(
+            &["--platform", "linux", "--socket", "/tmp/flag.sock", "--config", "/etc/airsend.json"],
+            &env,
+        );
+        assert!(!profile.is_android());
+        assert_eq!(profile.ipc_socket, PathBuf::from("/tmp/flag.sock"));
+        assert_eq!(profile.config_path, PathBuf::from("/etc/airsend.json"));
+    }
+
+    #[test]
+    fn describes_sockets() {
+        assert_eq!(socket::describe(std::path::Path::new(socket::ABSTRACT_NAME)), "@airsend_ipc");
+        assert_eq!(socket::describe(std::path::Path::new("/run/user/1000/airsend.sock")), "/run/user/1000/airsend.sock");
+    }
+}
@@ -0,0 +1,40 @@
+//! IPC 套接字的位置，守护进程与 `airsend` CLI 共用。
+//!
+//! Android 上沿用抽象命名空间 `@airsend_ipc`，由 SELinux 隔离访问。
+//! Linux 上抽象套接字对所有本地用户可见，任何账户都能借 SEND_FILE 发走
+//! 守护进程可读的文件，所以改用 `$XDG_RUNTIME_DIR/airsend.sock` (权限 0600)。
+
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+
+/// Android 上的抽象套接字名 (首字节为 NUL)
+pub const ABSTRACT_NAME: &str = "\0airsend_ipc";
+pub const SOCKET_NAME: &str = "airsend.sock";
+/// 覆盖套接字位置，守护进程与 CLI 都认
+pub const SOCKET_ENV: &str = "AIRSEND_SOCKET";
+
+/// 缺省位置：Android 用抽象名；Linux 放在 `runtime_dir`，没有会话运行目录
+/// (如无 logind 的服务器) 时退回 `fallback_dir` (守护进程的状态目录)。
+/// 只接受绝对路径的 `$XDG_RUNTIME_DIR`，与 XDG 规范一致。
+pub fn default_path(android: bool, runtime_dir: Option<&str>, fallback_dir: impl FnOnce() -> PathBuf) -> PathBuf {
+    if android {
+        return PathBuf::from(ABSTRACT_NAME);
+    }
+    match runtime_dir.map(Path::new) {
+        Some(dir) if dir.is_absolute() => dir.join(SOCKET_NAME),
+        _ => fallback_dir().join(SOCKET_NAME),
+    }
+}
+
+pub fn is_abstract(path: &Path) -> bool {
+    path.as_os_str().as_bytes().first() == Some(&0)
+}
+
+/// 给人看的写法：抽象套接字按惯例以 `@` 开头
+pub fn describe(path: &Path) -> String {
+    if is_abstract(path) {
+        format!("@{}", String::from_utf8_lossy(&path.as_os_str().as_bytes()[1..]))
+    } else {
+        path.display().to_string()
+    }
+}
@@ -0,0 +1,224 @@
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use anyhow::{Context, Result};
+use notify::{
+    event::AccessKind, event::AccessMode, event::ModifyKind, event::RenameMode, EventKind,
+    RecommendedWatcher, RecursiveMode, Watcher,
+};
+use tokio::sync::mpsc;
+
+use crate::config::WatchFolder;
+use crate::history::{self, Event};
+use crate::{send_data, AppState};
+
+type Events = mpsc::UnboundedReceiver<notify::Result<notify::Event>>;
+
+/// 监听目录集合：系统截图目录 + 配置/IPC 添加的目录，运行期可增删
+pub struct Watches {
+    // 核心：死死锁住 watcher 的生命周期，防止文件句柄被内核强制回收
+    watcher: Mutex<RecommendedWatcher>,
+    folders: Mutex<Vec<WatchFolder>>,
+}
+
+impl Watches {
+    pub fn new() -> Result<(Self, Events)> {
+        // 创建 Tokio 原生的异步 Channel，桥接同步内核中断与异步运行时
+        let (tx, rx) = mpsc::unbounded_channel();
+        let watcher = notify::recommended_watcher(move |res| {
+            let _ = tx.send(res);
+        })
+        .context("Failed to create inotify watcher")?;
+        Ok((Self { watcher: Mutex::new(watcher), folders: Mutex::new(Vec::new()) }, rx))
+    }
+
+    pub fn add(&self, folder: WatchFolder) -> Result<()> {
+        let watch_path = Path::new(&folder.path);
+        // 同步创建目录，确保探针挂载不报错
+        let _ = std::fs::create_dir_all(watch_path);
+
+        let mode = if folder.recursive { RecursiveMode::Recursive } else { RecursiveMode::NonRecursive };
+        self.watcher
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .watch(watch_path, mode)
+            .with_context(|| format!("无法绑定 inotify 至 {}", watch_path.display()))?;
+        tracing::info!("👁️ 物理 EXT4 探针已深深扎入: {}", watch_path.display());
+
+        let mut folders = self.folders.lock().unwrap_or_else(|e| e.into_inner());
+        folders.retain(|f| f.path != folder.path);
+        folders.push(folder);
+        Ok(())
+    }
+
+    /// 返回是否确实移除了该目录
+    pub fn remove(&self, path: &str) -> bool {
+        let mut folders = self.folders.lock().unwrap_or_else(|e| e.into_inner());
+        let before = folders.len();
+        folders.retain(|f| f.path != path);
+        if folders.len() == before {
+            return false;
+        }
+        let _ = self.watcher.lock().unwrap_or_else(|e| e.into_inner()).unwatch(Path::new(path));
+        true
+    }
+
+    pub fn list(&self) -> Vec<WatchFolder> {
+        self.folders.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// 最长前缀匹配，嵌套的监听目录以更具体者为准
+    fn target_for(&self, path: &Path) -> Option<String> {
+        let folders = self.folders.lock().unwrap_or_else(|e| e.into_inner());
+        folders
+            .iter()
+            .filter(|f| path.starts_with(&f.path))
+            .max_by_key(|f| f.path.len())
+            .and_then(|f| f.peer.clone())
+    }
+}
+
+/// WATCH_ADD：立即生效并写入配置
+pub async fn add(state: &AppState, folder: WatchFolder) -> Result<()> {
+    state.ensure_config_writable()?;
+    state.watches.add(folder.clone())?;
+    let mut config = state.config.lock().await;
+    config.watch.retain(|f| f.path != folder.path);
+    config.watch.push(folder.clone());
+    config.save(&state.config_path)?;
+    state.history.emit(Event::WatchAdded { time: history::now(), path: folder.path });
+    Ok(())
+}
+
+/// WATCH_REMOVE：截图目录等非配置项同样可以在运行期摘除；
+/// 写在配置里的目录要回写配置，配置加载失败时拒绝
+pub async fn remove(state: &AppState, path: &str) -> Result<bool> {
+    let mut config = state.config.lock().await;
+    let configured = config.watch.iter().any(|f| f.path == path);
+    if configured {
+        state.ensure_config_writable()?;
+    }
+    let removed = state.watches.remove(path);
+    if configured {
+        config.watch.retain(|f| f.path != path);
+        config.save(&state.config_path)?;
+    }
+    drop(config);
+    if removed {
+        state.history.emit(Event::WatchRemoved { time: history::now(), path: path.to_string() });
+    }
+    Ok(removed)
+}
+
+pub fn spawn_physical_watcher(state: Arc<AppState>, mut rx: Events) {
+    // 启动真正的 Tokio 异步消费协程，绝不阻塞主线程
+    tokio::spawn(async move {
+        while let Some(res) = rx.recv().await {
+            match res {
+                Ok(event) => {
+                    // 匹配系统截图落盘的真实物理动作 (关闭写入或重命名 .pending)
+                    let is_target_event = matches!(
+                        event.kind,
+                        EventKind::Access(AccessKind::Close(AccessMode::Write))
+                            | EventKind::Modify(ModifyKind::Name(RenameMode::To))
+                            | EventKind::Modify(ModifyKind::Name(RenameMode::Both))
+                    );
+
+                    if is_target_event {
+                        if let Some(path_buf) = event.paths.first() {
+                            let path_str = path_buf.to_string_lossy().to_string();
+
+                            // 强力过滤系统 IO 碎片文件
+                            if path_str.ends_with(".tmp") || path_str.ends_with(".pending") || path_buf.file_name().unwrap_or_default().to_string_lossy().starts_with(".") {
+                                continue;
+                            }
+
+                            tracing::info!("📸 监听目录捕获新文件落盘: {}", path_str);
+
+                            let target = state.watches.target_for(path_buf);
+                            let state_clone = state.clone();
+                            tokio::spawn(async move {
+                                // 🔋 灵魂延时：等待 EXT4 Page Cache 刷盘，彻底消灭 0 字节鬼影文件
+                                state_clone.client.clock().sleep(std::time::Duration::from_millis(1000)).await;
+
+                                tracing::info!("🚀 正在绕过 App 层，直接向 Mac 发射物理路径: {}", path_str);
+
+                                // 直接调用 Daemon 内部的 HTTPS 发送引擎
+                                if let Err(e) = send_data(&state_clone, target, &path_str, false).await {
+                                    tracing::error!("❌ 截图底层直发失败: {:?}", e);
+                                }
+                            });
+                        }
+                    }
+                },
+                Err(e) => tracing::error!("inotify watch error: {:?}", e),
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn folder(path: &Path, peer: Option<&str>) -> WatchFolder {
+        WatchFolder { path: path.to_string_lossy().to_string(), peer: peer.map(str::to_string), recursive: false }
+    }
+
+    #[test]
+    fn add_creates_the_folder_and_replaces_duplicates() {
+        let dir = tempfile::tempdir().unwrap();
+        let shots = dir.path().join("Screenshots");
+        let (watches, _events) = Watches::new().unwrap();
+
+        watches.add(folder(&shots, None)).unwrap();
+        assert!(shots.is_dir());
+        watches.add(folder(&shots, Some("Mac"))).unwrap();
+
+        let list = watches.list();
+        assert_eq!(list.len(), 1);
+        assert_eq!(list[0].peer.as_deref(), Some("Mac"));
+    }
+
+    #[test]
+    fn remove_reports_whether_anything_was_watched() {
+        let dir = tempfile::tempdir().unwrap();
+        let (watches, _events) = Watches::new().unwrap();
+        watches.add(folder(dir.path(), None)).unwrap();
+
+        let path = dir.path().to_string_lossy().to_string();
+        assert!(watches.remove(&path));
+        assert!(!watches.remove(&path));
+        assert!(watches.list().is_empty());
+    }
+
+    #[test]
+    fn most_specific_folder_picks_the_peer() {
+        let dir = tempfile::tempdir().unwrap();
+        let nested = dir.path().join("work");
+        let (watches, _events) = Watches::new().unwrap();
+        watches.add(folder(dir.path(), Some("Mac"))).unwrap();
+        watches.add(folder(&nested, Some("Linux:box"))).unwrap();
+
+        assert_eq!(watches.target_for(&dir.path().join("a.png")).as_deref(), Some("Mac"));
+        assert_eq!(watches.target_for(&nested.join("a.png")).as_deref(), Some("Linux:box"));
+        // 前缀按路径组件比较，`work2` 不属于 `work`
+        assert_eq!(watches.target_for(&dir.path().join("work2/a.png")).as_deref(), Some("Mac"));
+        assert_eq!(watches.target_for(Path::new("/elsewhere/a.png")), None);
+    }
+
+    #[tokio::test]
+    async fn new_files_raise_events() {
+        let dir = tempfile::tempdir().unwrap();
+        let (watches, mut events) = Watches::new().unwrap();
+        watches.add(folder(dir.path(), None)).unwrap();
+
+        std::fs::write(dir.path().join("shot.png"), b"png").unwrap();
+        let event = tokio::time::timeout(std::time::Duration::from_secs(5), events.recv())
+            .await
+            .expect("no inotify event")
+            .unwrap()
+            .unwrap();
+        assert!(event.paths.iter().any(|p| p.ends_with("shot.png")));
+    }
+}
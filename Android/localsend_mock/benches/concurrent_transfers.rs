@@ -0,0 +1,173 @@
+//! How transfers share a client: uploads to a slow receiver and uploads
+//! arriving from a sender, one after the other versus all at once. Session
+//! state must not serialize them, so the concurrent runs should finish in
+//! roughly the time of the slowest transfer rather than the sum.
+//!
+//! `cargo bench -p localsend_mock` (add `-- FILES SIZE_KIB STALL_MS` to
+//! change the defaults of 8 files of 1024 KiB behind a 100 ms link).
+
+use std::collections::HashMap;
+use std::net::{Ipv4Addr, SocketAddr, TcpListener};
+use std::time::{Duration, Instant};
+
+use localsend::models::device::DeviceInfo;
+use localsend::models::file::FileMetadata;
+use localsend::oneshot::Target;
+use localsend::transfer::hooks::HookPipeline;
+use localsend::transfer::routing::RoutingTable;
+use localsend::Client;
+use localsend_mock::{file_entry, Endpoint, Fault, MockPeer, MockSender};
+use serde_json::{Map, Value};
+use tempfile::TempDir;
+use tokio::task::JoinSet;
+
+struct Setup {
+    files: usize,
+    size: usize,
+    stall: Duration,
+}
+
+impl Setup {
+    fn from_args() -> Self {
+        // `cargo bench` passes `--bench` through; skip flags
+        let args: Vec<usize> = std::env::args().skip(1).filter_map(|a| a.parse().ok()).collect();
+        Self {
+            files: args.first().copied().unwrap_or(8),
+            size: args.get(1).copied().unwrap_or(1024) * 1024,
+            stall: Duration::from_millis(args.get(2).copied().unwrap_or(100) as u64),
+        }
+    }
+
+    fn megabytes(&self, transfers: usize) -> f64 {
+        (transfers * self.size) as f64 / (1024.0 * 1024.0)
+    }
+}
+
+fn free_port() -> u16 {
+    TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port()
+}
+
+/// A serving client that already knows a mock receiver stalling every upload.
+async fn client(setup: &Setup) -> (Client, TempDir, MockPeer, String, MockSender) {
+    let dir = tempfile::tempdir().unwrap();
+    let device = DeviceInfo { alias: "Bench".to_string(), protocol: "http".to_string(), ..Default::default() };
+    let client = Client::builder()
+        .device(device)
+        .port(free_port())
+        .bind_addr(Ipv4Addr::LOCALHOST.into())
+        .multicast(false)
+        .download_dir(dir.path())
+        .routes(RoutingTable { rules: Vec::new() })
+        .hooks(HookPipeline::new())
+        .build()
+        .await
+        .unwrap();
+
+    let addr = SocketAddr::from(([127, 0, 0, 1], client.port()));
+    let serving = client.clone();
+    tokio::spawn(async move { serving.start_http_server().await.unwrap() });
+    while tokio::net::TcpStream::connect(addr).await.is_err() {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+
+    let peer = MockPeer::start().await.unwrap();
+    peer.fail(Endpoint::Upload, Fault::Stall(setup.stall));
+    let (fingerprint, _) = client.find_peer(&Target::Address(peer.addr().to_string()), Duration::from_secs(2)).await.unwrap();
+    (client, dir, peer, fingerprint, MockSender::new(addr))
+}
+
+/// Outgoing uploads: (file id, token) pairs of one prepared session.
+async fn prepare_outgoing(client: &Client, peer: String, setup: &Setup) -> (String, Vec<(String, String)>) {
+    let files: HashMap<String, FileMetadata> = (0..setup.files)
+        .map(|i| {
+            let id = format!("out{}", i);
+            let file = FileMetadata {
+                id: id.clone(),
+                file_name: format!("{}.bin", id),
+                size: setup.size as u64,
+                file_type: "application/octet-stream".to_string(),
+                sha256: None,
+                preview: None,
+                metadata: None,
+            };
+            (id, file)
+        })
+        .collect();
+    let response = client.prepare_upload(peer, files).await.unwrap();
+    (response.session_id, response.files.into_iter().collect())
+}
+
+/// Incoming uploads the mock sender will push into the client.
+async fn prepare_incoming(sender: &MockSender, round: &str, setup: &Setup) -> (String, Vec<(String, String)>) {
+    let files: Map<String, Value> = (0..setup.files)
+        .map(|i| {
+            let id = format!("{}-in{}", round, i);
+            let entry = file_entry(&id, &format!("{}.bin", id), setup.size, "application/octet-stream");
+            (id, entry)
+        })
+        .collect();
+    let response = sender.prepare_upload(Value::Object(files)).await.unwrap().json().unwrap();
+    let session = response["sessionId"].as_str().unwrap().to_string();
+    let tokens = response["files"].as_object().unwrap().iter().map(|(id, t)| (id.clone(), t.as_str().unwrap().to_string())).collect();
+    (session, tokens)
+}
+
+/// Runs every transfer of one round, either awaiting each in turn or all
+/// together, and returns the wall time.
+async fn round(client: &Client, peer: &str, sender: &MockSender, name: &str, concurrent: bool, setup: &Setup) -> Duration {
+    let (out_session, outgoing) = prepare_outgoing(client, peer.to_string(), setup).await;
+    let (in_session, incoming) = prepare_incoming(sender, name, setup).await;
+    let body = bytes::Bytes::from(vec![0x5a; setup.size]);
+
+    let mut tasks = JoinSet::new();
+    let started = Instant::now();
+    for (file_id, token) in outgoing {
+        let (client, session, body) = (client.clone(), out_session.clone(), body.clone());
+        tasks.spawn(async move { client.upload(session, file_id, token, body).await.unwrap() });
+        if !concurrent {
+            tasks.join_next().await.unwrap().unwrap();
+        }
+    }
+    for (file_id, token) in incoming {
+        let (sender, session, body) = (MockSender::new(sender.target), in_session.clone(), body.clone());
+        tasks.spawn(async move {
+            let response = sender.upload(&session, &file_id, &token, &body).await.unwrap();
+            assert_eq!(response.status, 200);
+        });
+        if !concurrent {
+            tasks.join_next().await.unwrap().unwrap();
+        }
+    }
+    while let Some(done) = tasks.join_next().await {
+        done.unwrap();
+    }
+    started.elapsed()
+}
+
+fn main() {
+    let setup = Setup::from_args();
+    let rt = tokio::runtime::Builder::new_multi_thread().enable_all().build().unwrap();
+    rt.block_on(async {
+        let (client, _dir, _peer, fingerprint, sender) = client(&setup).await;
+        let transfers = 2 * setup.files;
+        println!(
+            "{} uploads out (receiver stalls {:?}) + {} uploads in, {} KiB each",
+            setup.files,
+            setup.stall,
+            setup.files,
+            setup.size / 1024
+        );
+
+        let sequential = round(&client, &fingerprint, &sender, "seq", false, &setup).await;
+        let concurrent = round(&client, &fingerprint, &sender, "par", true, &setup).await;
+        for (name, elapsed) in [("sequential", sequential), ("concurrent", concurrent)] {
+            println!(
+                "{:>10}: {:>8.1} ms  {:>8.1} MiB/s",
+                name,
+                elapsed.as_secs_f64() * 1000.0,
+                setup.megabytes(transfers) / elapsed.as_secs_f64()
+            );
+        }
+        println!("   speedup: {:.1}x", sequential.as_secs_f64() / concurrent.as_secs_f64());
+    });
+}
@@ -0,0 +1,163 @@
+//! `localsend-mock`: a fake LocalSend v2 peer for poking at real devices and
+//! at the Swift `HTTPTransferServer` from a shell.
+//!
+//!     localsend-mock serve --bind 0.0.0.0:53317 --fault prepare-upload=409
+//!     localsend-mock serve --fault upload=drop*1 --info odd-device.json
+//!     localsend-mock send --to 192.168.1.20:53317 photo.jpg --truncate
+
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use anyhow::{bail, Context, Result};
+use clap::{Parser, Subcommand};
+use localsend_mock::{file_entry, Endpoint, Fault, MockPeer, MockSender};
+use serde_json::{json, Value};
+
+#[derive(Parser)]
+#[command(name = "localsend-mock", version, about = "Scriptable fake LocalSend v2 peer")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Act as a receiver and print every exchange as a JSON line
+    Serve {
+        #[arg(long, default_value = "0.0.0.0:53317")]
+        bind: SocketAddr,
+        /// JSON file with the DeviceInfo to advertise (need not be valid)
+        #[arg(long)]
+        info: Option<PathBuf>,
+        /// ENDPOINT=FAULT[*TIMES], e.g. `prepare-upload=403`, `upload=drop*1`,
+        /// `info=stall=5`, `prepare-upload=malformed`
+        #[arg(long = "fault")]
+        faults: Vec<String>,
+    },
+    /// Act as a sender against a real server
+    Send {
+        /// Receiver `ip:port`
+        #[arg(long)]
+        to: SocketAddr,
+        #[arg(required = true)]
+        paths: Vec<PathBuf>,
+        /// JSON file with the DeviceInfo to present (need not be valid)
+        #[arg(long)]
+        info: Option<PathBuf>,
+        /// MIME type claimed for every file
+        #[arg(long, default_value = "application/octet-stream")]
+        file_type: String,
+        /// Send half of each file, then hang up
+        #[arg(long)]
+        truncate: bool,
+    },
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    match run(Cli::parse()).await {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::FAILURE,
+        Err(e) => {
+            eprintln!("localsend-mock: {:#}", e);
+            ExitCode::from(2)
+        }
+    }
+}
+
+async fn run(cli: Cli) -> Result<bool> {
+    match cli.command {
+        Command::Serve { bind, info, faults } => {
+            let info = info.map(|path| read_json(&path)).transpose()?;
+            let peer = MockPeer::bind(bind, info).await.with_context(|| format!("Failed to bind {}", bind))?;
+            for spec in &faults {
+                let (endpoint, fault, times) = parse_fault(spec)?;
+                match times {
+                    Some(times) => peer.fail_times(endpoint, fault, times),
+                    None => peer.fail(endpoint, fault),
+                };
+            }
+            eprintln!("Mock peer listening on {} as {}", peer.addr(), peer.info());
+
+            let mut events = peer.subscribe();
+            while let Ok(exchange) = events.recv().await {
+                println!(
+                    "{}",
+                    json!({
+                        "endpoint": exchange.endpoint.name(),
+                        "status": exchange.status,
+                        "query": exchange.query,
+                        "bytes": exchange.body.len(),
+                        "json": exchange.json(),
+                    })
+                );
+            }
+            Ok(true)
+        }
+        Command::Send { to, paths, info, file_type, truncate } => {
+            let mut sender = MockSender::new(to);
+            if let Some(path) = info {
+                sender = sender.with_info(read_json(&path)?);
+            }
+
+            let register = sender.register().await.context("register failed")?;
+            println!("register: {} {}", register.status, String::from_utf8_lossy(&register.body));
+
+            let mut files = serde_json::Map::new();
+            let mut contents = Vec::new();
+            for path in &paths {
+                let data = std::fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+                let id = uuid::Uuid::new_v4().to_string();
+                let name = path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+                files.insert(id.clone(), file_entry(&id, &name, data.len(), &file_type));
+                contents.push((id, name, data));
+            }
+
+            let prepared = sender.prepare_upload(Value::Object(files)).await.context("prepare-upload failed")?;
+            println!("prepare-upload: {} {}", prepared.status, String::from_utf8_lossy(&prepared.body));
+            if prepared.status != 200 {
+                return Ok(prepared.status == 204);
+            }
+            let prepared = prepared.json().context("prepare-upload returned invalid JSON")?;
+            let session_id = prepared["sessionId"].as_str().context("no sessionId")?;
+
+            let mut all_ok = true;
+            for (id, name, data) in contents {
+                let Some(token) = prepared["files"][&id].as_str() else {
+                    println!("upload {}: skipped by receiver", name);
+                    continue;
+                };
+                if truncate {
+                    sender.upload_truncated(session_id, &id, token, &data[..data.len() / 2], data.len()).await?;
+                    println!("upload {}: hung up after {} of {} bytes", name, data.len() / 2, data.len());
+                    continue;
+                }
+                let response = sender.upload(session_id, &id, token, &data).await?;
+                println!("upload {}: {}", name, response.status);
+                all_ok &= response.status == 200;
+            }
+            Ok(all_ok)
+        }
+    }
+}
+
+/// `ENDPOINT=FAULT[*TIMES]`
+fn parse_fault(spec: &str) -> Result<(Endpoint, Fault, Option<usize>)> {
+    let Some((endpoint, fault)) = spec.split_once('=') else {
+        bail!("--fault expects ENDPOINT=FAULT, got {:?}", spec);
+    };
+    let (fault, times) = match fault.rsplit_once('*') {
+        Some((fault, times)) => (fault, Some(times.parse().with_context(|| format!("bad repeat count {:?}", times))?)),
+        None => (fault, None),
+    };
+    if times == Some(0) {
+        bail!("repeat count must be at least 1, got {:?}", spec);
+    }
+    Ok((endpoint.parse().map_err(anyhow::Error::msg)?, fault.parse().map_err(anyhow::Error::msg)?, times))
+}
+
+fn read_json(path: &PathBuf) -> Result<Value> {
+    let text = std::fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    serde_json::from_str(&text).with_context(|| format!("{} is not JSON", path.display()))
+}
@@ -0,0 +1,252 @@
+//! Just enough HTTP/1.1 to speak LocalSend, written by hand so faults can be
+//! injected at the byte level (half a body, no reply, garbage JSON) where a
+//! real server framework would refuse to misbehave.
+
+use std::collections::HashMap;
+use std::io;
+use std::net::SocketAddr;
+
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+/// Request heads larger than this are rejected.
+const MAX_HEAD: usize = 64 * 1024;
+
+#[derive(Debug, Clone)]
+pub struct Request {
+    pub method: String,
+    pub path: String,
+    pub query: HashMap<String, String>,
+    pub headers: HashMap<String, String>,
+    /// Bytes already read past the head; the rest is still on the socket.
+    pub body_start: Vec<u8>,
+}
+
+impl Request {
+    pub fn content_length(&self) -> usize {
+        self.headers.get("content-length").and_then(|v| v.parse().ok()).unwrap_or(0)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Response {
+    pub status: u16,
+    pub body: Vec<u8>,
+}
+
+impl Response {
+    pub fn json(&self) -> serde_json::Result<serde_json::Value> {
+        serde_json::from_slice(&self.body)
+    }
+}
+
+/// Reads a request head. `None` means the peer closed the connection or sent
+/// something that isn't HTTP (a TLS handshake, for instance).
+pub async fn read_request(stream: &mut TcpStream) -> io::Result<Option<Request>> {
+    let Some((head, body_start)) = read_head(stream).await? else {
+        return Ok(None);
+    };
+    let mut lines = head.split("\r\n");
+    let mut request_line = lines.next().unwrap_or_default().split(' ');
+    let (Some(method), Some(target)) = (request_line.next(), request_line.next()) else {
+        return Ok(None);
+    };
+    let (path, query) = match target.split_once('?') {
+        Some((path, query)) => (path, parse_query(query)),
+        None => (target, HashMap::new()),
+    };
+    Ok(Some(Request {
+        method: method.to_string(),
+        path: path.to_string(),
+        query,
+        headers: parse_headers(lines),
+        body_start,
+    }))
+}
+
+/// Reads the rest of the body, up to `content-length`.
+pub async fn read_body(stream: &mut TcpStream, request: &Request) -> io::Result<Vec<u8>> {
+    read_body_prefix(stream, request, request.content_length()).await
+}
+
+/// Reads at most `limit` bytes of the body.
+pub async fn read_body_prefix(stream: &mut TcpStream, request: &Request, limit: usize) -> io::Result<Vec<u8>> {
+    let limit = limit.min(request.content_length());
+    let mut body = request.body_start.clone();
+    body.truncate(limit);
+    let mut chunk = [0u8; 8192];
+    while body.len() < limit {
+        let want = (limit - body.len()).min(chunk.len());
+        let n = stream.read(&mut chunk[..want]).await?;
+        if n == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        body.extend_from_slice(&chunk[..n]);
+    }
+    Ok(body)
+}
+
+pub async fn write_response(stream: &mut TcpStream, status: u16, content_type: &str, body: &[u8]) -> io::Result<()> {
+    let head = format!(
+        "HTTP/1.1 {} {}\r\ncontent-type: {}\r\ncontent-length: {}\r\nconnection: close\r\n\r\n",
+        status,
+        reason(status),
+        content_type,
+        body.len()
+    );
+    stream.write_all(head.as_bytes()).await?;
+    stream.write_all(body).await?;
+    stream.shutdown().await
+}
+
+/// Sends one request over a fresh connection and reads the whole reply.
+pub async fn request(addr: SocketAddr, method: &str, target: &str, body: &[u8]) -> io::Result<Response> {
+    let mut stream = TcpStream::connect(addr).await?;
+    write_request_head(&mut stream, addr, method, target, body.len()).await?;
+    stream.write_all(body).await?;
+    read_response(&mut stream).await
+}
+
+/// Announces a `declared` byte body but only sends `body`, then hangs up.
+pub async fn request_truncated(addr: SocketAddr, target: &str, body: &[u8], declared: usize) -> io::Result<()> {
+    let mut stream = TcpStream::connect(addr).await?;
+    write_request_head(&mut stream, addr, "POST", target, declared).await?;
+    stream.write_all(body).await?;
+    stream.flush().await
+}
+
+async fn write_request_head(stream: &mut TcpStream, addr: SocketAddr, method: &str, target: &str, length: usize) -> io::Result<()> {
+    let head = format!(
+        "{} {} HTTP/1.1\r\nhost: {}\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n",
+        method, target, addr, length
+    );
+    stream.write_all(head.as_bytes()).await
+}
+
+async fn read_response(stream: &mut TcpStream) -> io::Result<Response> {
+    let (head, mut body) = read_head(stream).await?.ok_or(io::ErrorKind::UnexpectedEof)?;
+    let mut lines = head.split("\r\n");
+    let status = lines
+        .next()
+        .and_then(|line| line.split(' ').nth(1))
+        .and_then(|code| code.parse().ok())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "bad status line"))?;
+    let headers = parse_headers(lines);
+    if headers.get("transfer-encoding").is_some_and(|v| v.eq_ignore_ascii_case("chunked")) {
+        stream.read_to_end(&mut body).await?;
+        return Ok(Response { status, body: dechunk(&body) });
+    }
+    match headers.get("content-length").and_then(|v| v.parse::<usize>().ok()) {
+        Some(length) => {
+            while body.len() < length {
+                let mut chunk = vec![0u8; length - body.len()];
+                let n = stream.read(&mut chunk).await?;
+                if n == 0 {
+                    break;
+                }
+                body.extend_from_slice(&chunk[..n]);
+            }
+        }
+        None => {
+            stream.read_to_end(&mut body).await?;
+        }
+    }
+    Ok(Response { status, body })
+}
+
+async fn read_head(stream: &mut (impl AsyncRead + Unpin)) -> io::Result<Option<(String, Vec<u8>)>> {
+    let mut buf = Vec::new();
+    let mut chunk = [0u8; 4096];
+    loop {
+        if let Some(end) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+            let rest = buf.split_off(end + 4);
+            buf.truncate(end);
+            return match String::from_utf8(buf) {
+                Ok(head) => Ok(Some((head, rest))),
+                Err(_) => Ok(None),
+            };
+        }
+        if buf.len() > MAX_HEAD {
+            return Ok(None);
+        }
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 {
+            return Ok(None);
+        }
+        buf.extend_from_slice(&chunk[..n]);
+    }
+}
+
+fn parse_headers<'a>(lines: impl Iterator<Item = &'a str>) -> HashMap<String, String> {
+    lines
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, value)| (name.trim().to_ascii_lowercase(), value.trim().to_string()))
+        .collect()
+}
+
+/// LocalSend ids and tokens are URL-safe, so only `%XX` escapes and `+` are
+/// decoded.
+fn parse_query(query: &str) -> HashMap<String, String> {
+    query
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .map(|(key, value)| (percent_decode(key), percent_decode(value)))
+        .collect()
+}
+
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'%' if i + 2 < bytes.len() => {
+                let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).ok();
+                match hex.and_then(|hex| u8::from_str_radix(hex, 16).ok()) {
+                    Some(byte) => {
+                        out.push(byte);
+                        i += 3;
+                        continue;
+                    }
+                    None => out.push(b'%'),
+                }
+            }
+            b'+' => out.push(b' '),
+            byte => out.push(byte),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+fn dechunk(mut data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    while let Some(end) = data.windows(2).position(|w| w == b"\r\n") {
+        let size = std::str::from_utf8(&data[..end])
+            .ok()
+            .and_then(|line| usize::from_str_radix(line.split(';').next().unwrap_or("").trim(), 16).ok())
+            .unwrap_or(0);
+        data = &data[end + 2..];
+        if size == 0 || data.len() < size {
+            break;
+        }
+        out.extend_from_slice(&data[..size]);
+        data = data.get(size + 2..).unwrap_or_default();
+    }
+    out
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        204 => "No Content",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        404 => "Not Found",
+        409 => "Conflict",
+        429 => "Too Many Requests",
+        500 => "Internal Server Error",
+        _ => "Unknown",
+    }
+}
@@ -0,0 +1,18 @@
+//! A scriptable fake LocalSend v2 peer for checking protocol compliance
+//! without real devices.
+//!
+//! [`MockPeer`] plays the receiver: it serves `/info`, `/register`,
+//! `/prepare-upload`, `/upload` and `/cancel`, records every exchange, and can
+//! be told to fail per endpoint with a [`Fault`]. [`MockSender`] plays the
+//! sender against a real server and can send odd `DeviceInfo`, malformed
+//! JSON or uploads that stop halfway.
+//!
+//! Plain HTTP only; point the client under test at the mock with
+//! `protocol: "http"`.
+
+pub mod http;
+pub mod peer;
+pub mod sender;
+
+pub use peer::{default_info, Endpoint, Exchange, Fault, MockPeer};
+pub use sender::{file_entry, MockSender};
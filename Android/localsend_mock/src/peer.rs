@@ -0,0 +1,375 @@
+//! The receiving side: a LocalSend v2 server that records every exchange and
+//! fails on request.
+
+use std::collections::HashMap;
+use std::io;
+use std::net::SocketAddr;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use serde_json::{json, Value};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
+
+use crate::http::{self, Request};
+
+/// The v2 endpoints the mock serves.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Endpoint {
+    Info,
+    Register,
+    PrepareUpload,
+    Upload,
+    Cancel,
+}
+
+impl Endpoint {
+    pub const ALL: [Endpoint; 5] =
+        [Endpoint::Info, Endpoint::Register, Endpoint::PrepareUpload, Endpoint::Upload, Endpoint::Cancel];
+
+    pub fn path(self) -> &'static str {
+        match self {
+            Endpoint::Info => "/api/localsend/v2/info",
+            Endpoint::Register => "/api/localsend/v2/register",
+            Endpoint::PrepareUpload => "/api/localsend/v2/prepare-upload",
+            Endpoint::Upload => "/api/localsend/v2/upload",
+            Endpoint::Cancel => "/api/localsend/v2/cancel",
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Endpoint::Info => "info",
+            Endpoint::Register => "register",
+            Endpoint::PrepareUpload => "prepare-upload",
+            Endpoint::Upload => "upload",
+            Endpoint::Cancel => "cancel",
+        }
+    }
+
+    fn from_path(path: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|e| e.path() == path)
+    }
+}
+
+impl FromStr for Endpoint {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|e| e.name() == s)
+            .ok_or_else(|| format!("unknown endpoint {:?} (info, register, prepare-upload, upload, cancel)", s))
+    }
+}
+
+/// How an endpoint misbehaves.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Fault {
+    /// Reply with this status and an empty body (403 rejected, 409 blocked
+    /// by another session, 429 rate limited, ...).
+    Status(u16),
+    /// Reply 200 with a truncated JSON document.
+    MalformedJson,
+    /// Wait this long, then behave normally.
+    Stall(Duration),
+    /// Hang up without replying. Uploads read half the body first.
+    Drop,
+}
+
+impl FromStr for Fault {
+    type Err = String;
+
+    /// `403`, `malformed`, `drop` or `stall=SECS`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "malformed" => Ok(Fault::MalformedJson),
+            "drop" => Ok(Fault::Drop),
+            _ => {
+                if let Some(secs) = s.strip_prefix("stall=") {
+                    let secs: f64 = secs.parse().map_err(|_| format!("bad stall duration {:?}", secs))?;
+                    return Ok(Fault::Stall(Duration::from_secs_f64(secs)));
+                }
+                match s.parse::<u16>() {
+                    Ok(status) if (100..600).contains(&status) => Ok(Fault::Status(status)),
+                    _ => Err(format!("unknown fault {:?} (STATUS, malformed, drop, stall=SECS)", s)),
+                }
+            }
+        }
+    }
+}
+
+struct Rule {
+    fault: Fault,
+    /// `None` keeps failing forever.
+    remaining: Option<usize>,
+}
+
+/// One request as the mock saw it.
+#[derive(Debug, Clone)]
+pub struct Exchange {
+    pub endpoint: Endpoint,
+    pub query: HashMap<String, String>,
+    /// As much of the body as was read.
+    pub body: Vec<u8>,
+    /// `None` when the mock hung up instead of replying.
+    pub status: Option<u16>,
+}
+
+impl Exchange {
+    pub fn json(&self) -> Option<Value> {
+        serde_json::from_slice(&self.body).ok()
+    }
+}
+
+struct Upload {
+    token: String,
+}
+
+struct Session {
+    files: HashMap<String, Upload>,
+    cancelled: bool,
+}
+
+#[derive(Default)]
+struct State {
+    rules: HashMap<Endpoint, Vec<Rule>>,
+    exchanges: Vec<Exchange>,
+    sessions: HashMap<String, Session>,
+}
+
+/// A fake LocalSend receiver on a local port.
+pub struct MockPeer {
+    addr: SocketAddr,
+    info: Value,
+    state: Arc<Mutex<State>>,
+    events: broadcast::Sender<Exchange>,
+    task: JoinHandle<()>,
+}
+
+/// What the mock advertises unless told otherwise.
+pub fn default_info(port: u16) -> Value {
+    json!({
+        "alias": "MockPeer",
+        "version": "2.1",
+        "deviceModel": "localsend-mock",
+        "deviceType": "headless",
+        "fingerprint": uuid::Uuid::new_v4().to_string(),
+        "port": port,
+        "protocol": "http",
+        "download": false,
+    })
+}
+
+impl MockPeer {
+    /// Listens on `127.0.0.1` on a free port with [`default_info`].
+    pub async fn start() -> io::Result<Self> {
+        Self::bind("127.0.0.1:0".parse().unwrap(), None).await
+    }
+
+    /// Listens on `addr`, advertising `info` (any JSON, valid or not) from
+    /// `/info` and `/register`.
+    pub async fn bind(addr: SocketAddr, info: Option<Value>) -> io::Result<Self> {
+        let listener = TcpListener::bind(addr).await?;
+        let addr = listener.local_addr()?;
+        let info = info.unwrap_or_else(|| default_info(addr.port()));
+        let state = Arc::new(Mutex::new(State::default()));
+        let (events, _) = broadcast::channel(256);
+
+        let task = {
+            let info = info.clone();
+            let state = state.clone();
+            let events = events.clone();
+            tokio::spawn(async move {
+                while let Ok((stream, _)) = listener.accept().await {
+                    let (info, state, events) = (info.clone(), state.clone(), events.clone());
+                    tokio::spawn(async move {
+                        let _ = serve(stream, &info, &state, &events).await;
+                    });
+                }
+            })
+        };
+        Ok(Self { addr, info, state, events, task })
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// The advertised `DeviceInfo`.
+    pub fn info(&self) -> &Value {
+        &self.info
+    }
+
+    pub fn fingerprint(&self) -> Option<&str> {
+        self.info["fingerprint"].as_str()
+    }
+
+    /// Makes `endpoint` fail with `fault` on every request from now on.
+    pub fn fail(&self, endpoint: Endpoint, fault: Fault) -> &Self {
+        self.push_rule(endpoint, Rule { fault, remaining: None })
+    }
+
+    /// Makes `endpoint` fail with `fault` for the next `times` requests.
+    /// Rules queue up, so "drop, then 429, then succeed" is three calls.
+    /// `times == 0` adds nothing.
+    pub fn fail_times(&self, endpoint: Endpoint, fault: Fault, times: usize) -> &Self {
+        if times == 0 {
+            return self;
+        }
+        self.push_rule(endpoint, Rule { fault, remaining: Some(times) })
+    }
+
+    /// Back to spec behaviour on every endpoint.
+    pub fn heal(&self) {
+        self.lock().rules.clear();
+    }
+
+    /// Every request so far, oldest first.
+    pub fn exchanges(&self) -> Vec<Exchange> {
+        self.lock().exchanges.clone()
+    }
+
+    /// Requests to one endpoint, oldest first.
+    pub fn exchanges_for(&self, endpoint: Endpoint) -> Vec<Exchange> {
+        self.lock().exchanges.iter().filter(|e| e.endpoint == endpoint).cloned().collect()
+    }
+
+    /// Bodies of accepted uploads, keyed by file id.
+    pub fn uploaded(&self) -> HashMap<String, Vec<u8>> {
+        self.lock()
+            .exchanges
+            .iter()
+            .filter(|e| e.endpoint == Endpoint::Upload && e.status == Some(200))
+            .filter_map(|e| Some((e.query.get("fileId")?.clone(), e.body.clone())))
+            .collect()
+    }
+
+    /// Live feed of exchanges as they complete.
+    pub fn subscribe(&self) -> broadcast::Receiver<Exchange> {
+        self.events.subscribe()
+    }
+
+    fn push_rule(&self, endpoint: Endpoint, rule: Rule) -> &Self {
+        self.lock().rules.entry(endpoint).or_default().push(rule);
+        self
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Drop for MockPeer {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// Takes the next fault for `endpoint`, if any, consuming one-shot rules.
+fn next_fault(state: &Mutex<State>, endpoint: Endpoint) -> Option<Fault> {
+    let mut state = state.lock().unwrap_or_else(|e| e.into_inner());
+    let rules = state.rules.get_mut(&endpoint)?;
+    let rule = rules.first_mut()?;
+    let fault = rule.fault.clone();
+    if let Some(remaining) = &mut rule.remaining {
+        *remaining -= 1;
+        if *remaining == 0 {
+            rules.remove(0);
+        }
+    }
+    Some(fault)
+}
+
+async fn serve(mut stream: TcpStream, info: &Value, state: &Mutex<State>, events: &broadcast::Sender<Exchange>) -> io::Result<()> {
+    let Some(request) = http::read_request(&mut stream).await? else {
+        return Ok(());
+    };
+    let Some(endpoint) = Endpoint::from_path(&request.path) else {
+        let _ = http::read_body(&mut stream, &request).await;
+        return http::write_response(&mut stream, 404, "text/plain", b"not found").await;
+    };
+
+    let fault = next_fault(state, endpoint);
+    let record = |body: Vec<u8>, status: Option<u16>| {
+        let exchange = Exchange { endpoint, query: request.query.clone(), body, status };
+        state.lock().unwrap_or_else(|e| e.into_inner()).exchanges.push(exchange.clone());
+        let _ = events.send(exchange);
+    };
+
+    match fault {
+        Some(Fault::Drop) => {
+            // Half the body, then hang up: what a phone leaving Wi-Fi looks like
+            let half = request.content_length() / 2;
+            let body = http::read_body_prefix(&mut stream, &request, half).await.unwrap_or_default();
+            record(body, None);
+            return Ok(());
+        }
+        Some(Fault::Stall(duration)) => tokio::time::sleep(duration).await,
+        _ => {}
+    }
+
+    let body = http::read_body(&mut stream, &request).await?;
+    let (status, reply) = match fault {
+        Some(Fault::Status(status)) => (status, Vec::new()),
+        Some(Fault::MalformedJson) => (200, br#"{"sessionId": "broken", "files": {"#.to_vec()),
+        _ => handle(endpoint, &request, &body, info, state),
+    };
+    record(body, Some(status));
+    let content_type = if reply.is_empty() { "text/plain" } else { "application/json" };
+    http::write_response(&mut stream, status, content_type, &reply).await
+}
+
+/// Spec behaviour for each endpoint.
+fn handle(endpoint: Endpoint, request: &Request, body: &[u8], info: &Value, state: &Mutex<State>) -> (u16, Vec<u8>) {
+    let mut state = state.lock().unwrap_or_else(|e| e.into_inner());
+    match endpoint {
+        Endpoint::Info | Endpoint::Register => (200, info.to_string().into_bytes()),
+        Endpoint::PrepareUpload => {
+            let Ok(request) = serde_json::from_slice::<Value>(body) else {
+                return (400, Vec::new());
+            };
+            let Some(files) = request["files"].as_object() else {
+                return (400, Vec::new());
+            };
+            if request["info"]["fingerprint"].as_str().is_none() {
+                return (400, Vec::new());
+            }
+            let tokens: HashMap<String, String> =
+                files.keys().map(|id| (id.clone(), uuid::Uuid::new_v4().to_string())).collect();
+            let session_id = uuid::Uuid::new_v4().to_string();
+            state.sessions.insert(
+                session_id.clone(),
+                Session {
+                    files: tokens.iter().map(|(id, token)| (id.clone(), Upload { token: token.clone() })).collect(),
+                    cancelled: false,
+                },
+            );
+            (200, json!({ "sessionId": session_id, "files": tokens }).to_string().into_bytes())
+        }
+        Endpoint::Upload => {
+            let (Some(session_id), Some(file_id), Some(token)) =
+                (request.query.get("sessionId"), request.query.get("fileId"), request.query.get("token"))
+            else {
+                return (400, Vec::new());
+            };
+            match state.sessions.get(session_id) {
+                None => (400, Vec::new()),
+                Some(session) if session.cancelled => (409, Vec::new()),
+                Some(session) => match session.files.get(file_id) {
+                    Some(upload) if upload.token == *token => (200, Vec::new()),
+                    _ => (403, Vec::new()),
+                },
+            }
+        }
+        Endpoint::Cancel => match request.query.get("sessionId").and_then(|id| state.sessions.get_mut(id)) {
+            Some(session) => {
+                session.cancelled = true;
+                (200, Vec::new())
+            }
+            None => (400, Vec::new()),
+        },
+    }
+}
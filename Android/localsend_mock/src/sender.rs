@@ -0,0 +1,77 @@
+//! The sending side: drives a real LocalSend server with requests the mock
+//! composes itself, including ones no well-behaved client would send.
+
+use std::io;
+use std::net::SocketAddr;
+
+use serde_json::{json, Value};
+
+use crate::http::{self, Response};
+use crate::peer::default_info;
+
+/// A fake LocalSend sender aimed at one server.
+pub struct MockSender {
+    pub target: SocketAddr,
+    /// Sent as the `info` of `/register` and `/prepare-upload`; may be any
+    /// JSON, valid `DeviceInfo` or not.
+    pub info: Value,
+}
+
+impl MockSender {
+    pub fn new(target: SocketAddr) -> Self {
+        Self { target, info: default_info(0) }
+    }
+
+    pub fn with_info(mut self, info: Value) -> Self {
+        self.info = info;
+        self
+    }
+
+    pub async fn info(&self) -> io::Result<Response> {
+        http::request(self.target, "GET", "/api/localsend/v2/info", b"").await
+    }
+
+    pub async fn register(&self) -> io::Result<Response> {
+        http::request(self.target, "POST", "/api/localsend/v2/register", self.info.to_string().as_bytes()).await
+    }
+
+    /// `files` is the `files` map of the request: file id → `FileMetadata`.
+    pub async fn prepare_upload(&self, files: Value) -> io::Result<Response> {
+        let body = json!({ "info": self.info, "files": files });
+        self.prepare_upload_raw(body.to_string().as_bytes()).await
+    }
+
+    /// Posts `body` verbatim, for malformed requests.
+    pub async fn prepare_upload_raw(&self, body: &[u8]) -> io::Result<Response> {
+        http::request(self.target, "POST", "/api/localsend/v2/prepare-upload", body).await
+    }
+
+    pub async fn upload(&self, session_id: &str, file_id: &str, token: &str, body: &[u8]) -> io::Result<Response> {
+        http::request(self.target, "POST", &upload_target(session_id, file_id, token), body).await
+    }
+
+    /// Starts an upload of `declared` bytes, sends only `body` and hangs up.
+    pub async fn upload_truncated(
+        &self,
+        session_id: &str,
+        file_id: &str,
+        token: &str,
+        body: &[u8],
+        declared: usize,
+    ) -> io::Result<()> {
+        http::request_truncated(self.target, &upload_target(session_id, file_id, token), body, declared).await
+    }
+
+    pub async fn cancel(&self, session_id: &str) -> io::Result<Response> {
+        http::request(self.target, "POST", &format!("/api/localsend/v2/cancel?sessionId={}", session_id), b"").await
+    }
+}
+
+fn upload_target(session_id: &str, file_id: &str, token: &str) -> String {
+    format!("/api/localsend/v2/upload?sessionId={}&fileId={}&token={}", session_id, file_id, token)
+}
+
+/// A `files` entry for [`MockSender::prepare_upload`].
+pub fn file_entry(id: &str, name: &str, size: usize, file_type: &str) -> Value {
+    json!({ "id": id, "fileName": name, "size": size, "fileType": file_type })
+}
@@ -0,0 +1,342 @@
+//! The patched localsend crate against the mock: its client talking to a
+//! misbehaving receiver, and its server fed by a misbehaving sender.
+
+use std::net::{Ipv4Addr, SocketAddr, TcpListener};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use localsend::error::LocalSendError;
+use localsend::models::device::DeviceInfo;
+use localsend::models::file::FileMetadata;
+use localsend::oneshot::Target;
+use localsend::transfer::hooks::HookPipeline;
+use localsend::transfer::routing::RoutingTable;
+use localsend::Client;
+use localsend_mock::{file_entry, Endpoint, Fault, MockPeer, MockSender};
+use serde_json::json;
+use tempfile::TempDir;
+
+const TIMEOUT: Duration = Duration::from_secs(2);
+
+fn free_port() -> u16 {
+    TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port()
+}
+
+/// A crate client on its own port and temp dir, speaking plain HTTP.
+async fn client() -> (Client, TempDir) {
+    let dir = tempfile::tempdir().unwrap();
+    let device = DeviceInfo { alias: "UnderTest".to_string(), protocol: "http".to_string(), ..Default::default() };
+    let client = Client::builder()
+        .device(device)
+        .port(free_port())
+        .bind_addr(Ipv4Addr::LOCALHOST.into())
+        .multicast(false)
+        .download_dir(dir.path())
+        .routes(RoutingTable { rules: Vec::new() })
+        .hooks(HookPipeline::new())
+        .build()
+        .await
+        .unwrap();
+    (client, dir)
+}
+
+/// A crate client with its server up, plus a mock sender aimed at it.
+async fn server() -> (Client, TempDir, MockSender) {
+    let (client, dir) = client().await;
+    let addr = SocketAddr::from(([127, 0, 0, 1], client.port()));
+    let serving = client.clone();
+    tokio::spawn(async move { serving.start_http_server().await.unwrap() });
+    for _ in 0..100 {
+        if tokio::net::TcpStream::connect(addr).await.is_ok() {
+            return (client, dir, MockSender::new(addr));
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    panic!("server did not come up");
+}
+
+/// Client plus a mock receiver it already knows about.
+async fn client_and_peer() -> (Client, TempDir, MockPeer, String) {
+    let (client, dir) = client().await;
+    let peer = MockPeer::start().await.unwrap();
+    let (fingerprint, _) = client.find_peer(&Target::Address(peer.addr().to_string()), TIMEOUT).await.unwrap();
+    (client, dir, peer, fingerprint)
+}
+
+fn write_file(dir: &TempDir, name: &str, contents: &[u8]) -> PathBuf {
+    let path = dir.path().join(name);
+    std::fs::write(&path, contents).unwrap();
+    path
+}
+
+fn files_in(dir: &TempDir) -> Vec<String> {
+    let mut names: Vec<String> = std::fs::read_dir(dir.path())
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+        .collect();
+    names.sort();
+    names
+}
+
+// ---- client against a mock receiver ----
+
+#[tokio::test]
+async fn client_sends_spec_requests() {
+    let (client, dir, peer, fingerprint) = client_and_peer().await;
+    let path = write_file(&dir, "song.bin", b"la la la");
+
+    client.send_file(fingerprint, path).await.unwrap();
+
+    let prepare = peer.exchanges_for(Endpoint::PrepareUpload)[0].json().unwrap();
+    for field in ["alias", "version", "fingerprint", "port", "protocol"] {
+        assert!(!prepare["info"][field].is_null(), "info.{} missing", field);
+    }
+    let (id, file) = prepare["files"].as_object().unwrap().iter().next().unwrap();
+    assert_eq!(file["id"], json!(id));
+    assert_eq!(file["fileName"], "song.bin");
+    assert_eq!(file["size"], 8);
+    assert!(file["fileType"].is_string());
+
+    let upload = &peer.exchanges_for(Endpoint::Upload)[0];
+    for param in ["sessionId", "fileId", "token"] {
+        assert!(upload.query.contains_key(param), "{} missing", param);
+    }
+    assert_eq!(peer.uploaded()[id], b"la la la");
+}
+
+#[tokio::test]
+async fn client_maps_refusals_to_errors() {
+    let (client, dir, peer, fingerprint) = client_and_peer().await;
+    let path = write_file(&dir, "a.bin", b"x");
+
+    peer.fail_times(Endpoint::PrepareUpload, Fault::Status(403), 1)
+        .fail_times(Endpoint::PrepareUpload, Fault::Status(409), 1)
+        .fail_times(Endpoint::PrepareUpload, Fault::Status(429), 1);
+    let mut errors = Vec::new();
+    for _ in 0..3 {
+        errors.push(client.send_file(fingerprint.clone(), path.clone()).await.unwrap_err());
+    }
+    assert!(matches!(errors[0], LocalSendError::Rejected));
+    assert!(matches!(errors[1], LocalSendError::SessionBlocked));
+    assert!(matches!(errors[2], LocalSendError::TooManyRequests));
+    assert!(!errors[0].is_retryable() && errors[1].is_retryable() && errors[2].is_retryable());
+    assert!(peer.uploaded().is_empty());
+
+    client.send_file(fingerprint, path).await.unwrap();
+    assert_eq!(peer.uploaded().len(), 1);
+}
+
+#[tokio::test]
+async fn zero_repeat_faults_are_ignored() {
+    let (client, dir, peer, fingerprint) = client_and_peer().await;
+    peer.fail_times(Endpoint::PrepareUpload, Fault::Status(403), 0);
+
+    client.send_file(fingerprint.clone(), write_file(&dir, "a.bin", b"x")).await.unwrap();
+    client.send_file(fingerprint, write_file(&dir, "b.bin", b"y")).await.unwrap();
+    assert_eq!(peer.uploaded().len(), 2);
+}
+
+#[tokio::test]
+async fn client_survives_malformed_json() {
+    let (client, dir, peer, fingerprint) = client_and_peer().await;
+    peer.fail(Endpoint::PrepareUpload, Fault::MalformedJson);
+
+    let result = client.send_file(fingerprint, write_file(&dir, "a.bin", b"x")).await;
+    assert!(matches!(result, Err(LocalSendError::RequestError(_))));
+    assert!(peer.exchanges_for(Endpoint::Upload).is_empty());
+}
+
+#[tokio::test]
+async fn client_keeps_unexpected_status_and_body() {
+    let (client, dir, peer, fingerprint) = client_and_peer().await;
+    let path = write_file(&dir, "a.bin", b"x");
+
+    peer.fail_times(Endpoint::PrepareUpload, Fault::Status(503), 1);
+    match client.send_file(fingerprint.clone(), path.clone()).await {
+        Err(e @ LocalSendError::UnexpectedStatus { status: 503, .. }) => assert!(e.is_retryable()),
+        other => panic!("unexpected {:?}", other),
+    }
+
+    peer.fail_times(Endpoint::Upload, Fault::Status(500), 1);
+    match client.send_file(fingerprint, path).await {
+        Err(LocalSendError::UploadFailed { status: 500, session_id, .. }) => assert!(!session_id.is_empty()),
+        other => panic!("unexpected {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn client_gives_up_on_stalled_peer() {
+    let (client, _dir) = client().await;
+    let peer = MockPeer::start().await.unwrap();
+    peer.fail(Endpoint::Info, Fault::Stall(Duration::from_secs(30)));
+
+    let started = Instant::now();
+    assert!(client.fetch_info(peer.addr(), Duration::from_millis(300)).await.is_err());
+    assert!(started.elapsed() < Duration::from_secs(5));
+}
+
+#[tokio::test]
+async fn client_reports_dropped_upload_and_can_retry() {
+    let (client, dir, peer, fingerprint) = client_and_peer().await;
+    let path = write_file(&dir, "big.bin", &[7u8; 64 * 1024]);
+    peer.fail_times(Endpoint::Upload, Fault::Drop, 1);
+
+    assert!(client.send_file(fingerprint.clone(), path.clone()).await.is_err());
+    assert!(peer.uploaded().is_empty());
+
+    client.send_file(fingerprint, path).await.unwrap();
+    assert_eq!(peer.uploaded().into_values().next().unwrap().len(), 64 * 1024);
+}
+
+#[tokio::test]
+async fn client_accepts_odd_device_info() {
+    let (client, _dir) = client().await;
+    // An unknown device type, fields we have never heard of, and none of the
+    // optional ones
+    let info = json!({
+        "alias": "Toaster",
+        "version": "2.0",
+        "deviceType": "toaster",
+        "fingerprint": "odd-1",
+        "firmware": { "crumbs": true },
+    });
+    let peer = MockPeer::bind("127.0.0.1:0".parse().unwrap(), Some(info)).await.unwrap();
+
+    let device = client.fetch_info(peer.addr(), TIMEOUT).await.unwrap();
+    assert_eq!(device.alias, "Toaster");
+    assert_eq!(device.port, peer.addr().port());
+    assert_eq!(device.protocol, "http");
+}
+
+#[tokio::test]
+async fn client_cancel_reaches_peer() {
+    let (client, dir, peer, fingerprint) = client_and_peer().await;
+    let file = FileMetadata::from_path(&write_file(&dir, "a.bin", b"x")).unwrap();
+    let response = client
+        .prepare_upload(fingerprint, [(file.id.clone(), file)].into_iter().collect())
+        .await
+        .unwrap();
+
+    client.cancel_upload(response.session_id.clone()).await.unwrap();
+    let cancel = &peer.exchanges_for(Endpoint::Cancel)[0];
+    assert_eq!(cancel.query["sessionId"], response.session_id);
+    assert_eq!(cancel.status, Some(200));
+}
+
+#[tokio::test]
+async fn client_cancels_while_upload_stalls() {
+    let (client, dir, peer, fingerprint) = client_and_peer().await;
+    let file = FileMetadata::from_path(&write_file(&dir, "slow.bin", b"x")).unwrap();
+    let response = client
+        .prepare_upload(fingerprint, [(file.id.clone(), file.clone())].into_iter().collect())
+        .await
+        .unwrap();
+    peer.fail(Endpoint::Upload, Fault::Stall(Duration::from_secs(1)));
+
+    let uploading = client.clone();
+    let (session_id, token) = (response.session_id.clone(), response.files[&file.id].clone());
+    let upload = tokio::spawn(async move { uploading.upload(session_id, file.id, token, "x".into()).await });
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    // The stalled upload must not keep the session table locked
+    let started = Instant::now();
+    client.cancel_upload(response.session_id).await.unwrap();
+    assert!(started.elapsed() < Duration::from_millis(500), "cancel waited {:?}", started.elapsed());
+    assert!(matches!(upload.await.unwrap(), Err(LocalSendError::UploadFailed { status: 409, .. })));
+}
+
+// ---- server against a mock sender ----
+
+#[tokio::test]
+async fn server_registers_odd_device_info() {
+    let (client, _dir, sender) = server().await;
+    let sender = sender.with_info(json!({
+        "alias": "Fridge",
+        "version": "2.1",
+        "deviceType": "appliance",
+        "fingerprint": "odd-2",
+        "port": 53999,
+        "protocol": "http",
+        "temperature": 4,
+    }));
+
+    let response = sender.register().await.unwrap();
+    assert_eq!(response.status, 200);
+    assert_eq!(response.json().unwrap()["fingerprint"], json!(client.device().fingerprint));
+    assert_eq!(client.peers().lock().await["odd-2"].0.port(), 53999);
+}
+
+#[tokio::test]
+async fn server_rejects_malformed_prepare_upload() {
+    let (_client, dir, sender) = server().await;
+
+    for body in [&b"{\"info\": "[..], b"null", b"{\"files\": {}}"] {
+        let status = sender.prepare_upload_raw(body).await.unwrap().status;
+        assert_eq!(status, 400, "{:?}", String::from_utf8_lossy(body));
+    }
+
+    let response = sender.prepare_upload(json!({ "f1": file_entry("f1", "a.bin", 1, "application/octet-stream") })).await.unwrap();
+    assert_eq!(response.status, 200);
+    assert!(files_in(&dir).is_empty());
+}
+
+#[tokio::test]
+async fn server_enforces_session_and_token() {
+    let (_client, dir, sender) = server().await;
+    let prepared = sender
+        .prepare_upload(json!({ "f1": file_entry("f1", "a.bin", 3, "application/octet-stream") }))
+        .await
+        .unwrap()
+        .json()
+        .unwrap();
+    let session = prepared["sessionId"].as_str().unwrap();
+
+    let forged = sender.upload(session, "f1", "forged", b"abc").await.unwrap();
+    assert_eq!(forged.status, 403);
+    assert_eq!(forged.json().unwrap()["message"], "Invalid token");
+    let unknown = sender.upload("no-such-session", "f1", "forged", b"abc").await.unwrap();
+    assert_eq!(unknown.status, 400);
+    assert!(unknown.json().unwrap()["message"].as_str().unwrap().contains("no-such-session"));
+    assert!(files_in(&dir).is_empty());
+
+    let token = prepared["files"]["f1"].as_str().unwrap();
+    assert_eq!(sender.upload(session, "f1", token, b"abc").await.unwrap().status, 200);
+    assert_eq!(files_in(&dir), ["a.bin"]);
+}
+
+#[tokio::test]
+async fn server_discards_truncated_upload() {
+    let (_client, dir, sender) = server().await;
+    let data = vec![1u8; 32 * 1024];
+    let prepared = sender
+        .prepare_upload(json!({ "f1": file_entry("f1", "half.bin", data.len(), "application/octet-stream") }))
+        .await
+        .unwrap()
+        .json()
+        .unwrap();
+    let (session, token) = (prepared["sessionId"].as_str().unwrap(), prepared["files"]["f1"].as_str().unwrap());
+
+    sender.upload_truncated(session, "f1", token, &data[..data.len() / 2], data.len()).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    assert!(files_in(&dir).is_empty());
+
+    // The session survives, so the sender can simply try again
+    assert_eq!(sender.upload(session, "f1", token, &data).await.unwrap().status, 200);
+    assert_eq!(std::fs::read(dir.path().join("half.bin")).unwrap(), data);
+}
+
+#[tokio::test]
+async fn server_honours_cancel() {
+    let (_client, dir, sender) = server().await;
+    let prepared = sender
+        .prepare_upload(json!({ "f1": file_entry("f1", "a.bin", 1, "application/octet-stream") }))
+        .await
+        .unwrap()
+        .json()
+        .unwrap();
+    let (session, token) = (prepared["sessionId"].as_str().unwrap(), prepared["files"]["f1"].as_str().unwrap());
+
+    assert_eq!(sender.cancel(session).await.unwrap().status, 200);
+    assert_eq!(sender.upload(session, "f1", token, b"x").await.unwrap().status, 400);
+    assert!(files_in(&dir).is_empty());
+}
@@ -0,0 +1,343 @@
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use crate::clock::{Clock, SystemClock};
+use crate::error::BuildError;
+use crate::events::{EventSink, Events};
+use crate::models::device::DeviceInfo;
+use crate::net::{self, TlsConfig};
+use crate::platform::{self, Platform};
+use crate::transfer::accept::AcceptPolicy;
+use crate::transfer::conflict::ConflictPolicy;
+use crate::transfer::hooks::{self, HookPipeline};
+use crate::transfer::routing::RoutingTable;
+use crate::transfer::upload::TextFilter;
+use crate::{bind_discovery_socket, try_bind_discovery_socket_v6, Client, MULTICAST_GROUP, MULTICAST_GROUP_V6};
+
+/// Builds a [`Client`]. Everything the client would otherwise take from the
+/// environment (sockets, directories, time, HTTP stack) can be supplied, so
+/// several clients can run side by side in one process or a sandbox.
+///
+/// Settings are checked by [`build`](Self::build), which reports problems as
+/// a [`BuildError`] before any socket is opened.
+///
+/// ```no_run
+/// # async fn demo() -> Result<(), localsend::error::BuildError> {
+/// use localsend::net::TlsConfig;
+/// use localsend::transfer::accept::AcceptPolicy;
+///
+/// let client = localsend::Client::builder()
+///     .port(53400)
+///     .bind_addr("127.0.0.1".parse().unwrap())
+///     .multicast(false)
+///     .storage_root("/tmp/airsend")
+///     .tls(TlsConfig::self_signed())
+///     .accept_policy(AcceptPolicy::Pin("1234".into()))
+///     .event_sink(|event: &localsend::events::Event| println!("{:?}", event))
+///     .build()
+///     .await?;
+/// # Ok(()) }
+/// ```
+pub struct ClientBuilder {
+    device: DeviceInfo,
+    port: Option<u16>,
+    bind_addr: IpAddr,
+    multicast: bool,
+    multicast_group: Ipv4Addr,
+    multicast_group_v6: Ipv6Addr,
+    multicast_port: Option<u16>,
+    storage_root: Option<PathBuf>,
+    download_dir: Option<PathBuf>,
+    routes: Option<RoutingTable>,
+    platform: Option<Arc<dyn Platform>>,
+    clock: Arc<dyn Clock>,
+    http_client: Option<reqwest::Client>,
+    tls: Option<TlsConfig>,
+    configure_http: Option<HttpOptions>,
+    accept_policy: AcceptPolicy,
+    text_filter: Option<TextFilter>,
+    conflict_policy: ConflictPolicy,
+    preserve_timestamps: bool,
+    hooks: Option<HookPipeline>,
+    event_sinks: Vec<Arc<dyn EventSink>>,
+}
+
+type HttpOptions = Box<dyn FnOnce(reqwest::ClientBuilder) -> reqwest::ClientBuilder + Send>;
+
+impl Default for ClientBuilder {
+    fn default() -> Self {
+        Self {
+            device: DeviceInfo::default(),
+            port: None,
+            bind_addr: IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            multicast: true,
+            multicast_group: MULTICAST_GROUP,
+            multicast_group_v6: MULTICAST_GROUP_V6,
+            multicast_port: None,
+            storage_root: None,
+            download_dir: None,
+            routes: None,
+            platform: None,
+            clock: Arc::new(SystemClock),
+            http_client: None,
+            tls: None,
+            configure_http: None,
+            accept_policy: AcceptPolicy::default(),
+            text_filter: None,
+            conflict_policy: ConflictPolicy::default(),
+            preserve_timestamps: true,
+            hooks: None,
+            event_sinks: Vec::new(),
+        }
+    }
+}
+
+impl ClientBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// What we announce about ourselves. Its `port` is replaced by
+    /// [`port`](Self::port) when that is set.
+    pub fn device(mut self, device: DeviceInfo) -> Self {
+        self.device = device;
+        self
+    }
+
+    /// HTTP port, also advertised to peers. Defaults to the device's port
+    /// (53317).
+    pub fn port(mut self, port: u16) -> Self {
+        self.port = Some(port);
+        self
+    }
+
+    /// Address the HTTP server listens on. The unspecified address (the
+    /// default) listens dual-stack on every interface.
+    pub fn bind_addr(mut self, addr: IpAddr) -> Self {
+        self.bind_addr = addr;
+        self
+    }
+
+    /// Whether to join multicast groups and announce. Off, the client only
+    /// finds peers through HTTP (`register`, favourites, subnet scans).
+    pub fn multicast(mut self, enabled: bool) -> Self {
+        self.multicast = enabled;
+        self
+    }
+
+    pub fn multicast_group(mut self, group: Ipv4Addr) -> Self {
+        self.multicast_group = group;
+        self
+    }
+
+    pub fn multicast_group_v6(mut self, group: Ipv6Addr) -> Self {
+        self.multicast_group_v6 = group;
+        self
+    }
+
+    /// UDP port for discovery. Defaults to the HTTP port, as LocalSend does.
+    pub fn multicast_port(mut self, port: u16) -> Self {
+        self.multicast_port = Some(port);
+        self
+    }
+
+    /// Places the default directories under `root` instead of where the
+    /// platform keeps them: `root/Download/AirSend` and
+    /// `root/Pictures/AirSend`.
+    pub fn storage_root(mut self, root: impl Into<PathBuf>) -> Self {
+        self.storage_root = Some(root.into());
+        self
+    }
+
+    pub fn download_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.download_dir = Some(dir.into());
+        self
+    }
+
+    /// Defaults to sending photos and videos to the pictures directory.
+    pub fn routes(mut self, routes: RoutingTable) -> Self {
+        self.routes = Some(routes);
+        self
+    }
+
+    /// Defaults to [`platform::native`].
+    pub fn platform(mut self, platform: Arc<dyn Platform>) -> Self {
+        self.platform = Some(platform);
+        self
+    }
+
+    pub fn clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Replaces the default HTTP client. Start from
+    /// [`net::http_client_builder`] to keep scoped IPv6 peers reachable.
+    /// Cannot be combined with [`tls`](Self::tls) or
+    /// [`configure_http`](Self::configure_http).
+    pub fn http_client(mut self, client: reqwest::Client) -> Self {
+        self.http_client = Some(client);
+        self
+    }
+
+    /// Certificate handling for HTTPS peers.
+    pub fn tls(mut self, tls: TlsConfig) -> Self {
+        self.tls = Some(tls);
+        self
+    }
+
+    /// Adjusts the default HTTP client, e.g. `|b| b.no_proxy()`.
+    pub fn configure_http(
+        mut self,
+        options: impl FnOnce(reqwest::ClientBuilder) -> reqwest::ClientBuilder + Send + 'static,
+    ) -> Self {
+        self.configure_http = Some(Box::new(options));
+        self
+    }
+
+    /// Which incoming transfers to accept. Defaults to all of them.
+    pub fn accept_policy(mut self, policy: AcceptPolicy) -> Self {
+        self.accept_policy = policy;
+        self
+    }
+
+    pub fn text_filter(mut self, filter: TextFilter) -> Self {
+        self.text_filter = Some(filter);
+        self
+    }
+
+    pub fn conflict_policy(mut self, policy: ConflictPolicy) -> Self {
+        self.conflict_policy = policy;
+        self
+    }
+
+    /// Apply the sender's mtime/atime to received files. On by default.
+    pub fn preserve_timestamps(mut self, enabled: bool) -> Self {
+        self.preserve_timestamps = enabled;
+        self
+    }
+
+    /// Defaults to [`hooks::default_steps`] on the configured platform.
+    pub fn hooks(mut self, hooks: HookPipeline) -> Self {
+        self.hooks = Some(hooks);
+        self
+    }
+
+    /// Adds a receiver for [`Event`](crate::events::Event)s; may be called
+    /// more than once.
+    pub fn event_sink(mut self, sink: impl EventSink + 'static) -> Self {
+        self.event_sinks.push(Arc::new(sink));
+        self
+    }
+
+    fn validate(&self) -> Result<(), BuildError> {
+        if self.device.alias.trim().is_empty() {
+            return Err(BuildError::EmptyAlias);
+        }
+        if self.port.unwrap_or(self.device.port) == 0 || self.multicast_port == Some(0) {
+            return Err(BuildError::ZeroPort);
+        }
+        if !matches!(self.device.protocol.as_str(), "http" | "https") {
+            return Err(BuildError::UnknownProtocol(self.device.protocol.clone()));
+        }
+        if !self.multicast_group.is_multicast() {
+            return Err(BuildError::NotMulticast(self.multicast_group.into()));
+        }
+        if !self.multicast_group_v6.is_multicast() {
+            return Err(BuildError::NotMulticast(self.multicast_group_v6.into()));
+        }
+        if matches!(&self.accept_policy, AcceptPolicy::Pin(pin) if pin.is_empty()) {
+            return Err(BuildError::EmptyPin);
+        }
+        if let Some(dir) = &self.download_dir {
+            if dir.exists() && !dir.is_dir() {
+                return Err(BuildError::NotADirectory(dir.clone()));
+            }
+        }
+        if self.http_client.is_some() && (self.tls.is_some() || self.configure_http.is_some()) {
+            return Err(BuildError::HttpClientConflict);
+        }
+        Ok(())
+    }
+
+    pub async fn build(self) -> Result<Client, BuildError> {
+        self.validate()?;
+        let port = self.port.unwrap_or(self.device.port);
+        let mut device = self.device;
+        device.port = port;
+        let multicast_port = self.multicast_port.unwrap_or(port);
+
+        let http_client = match self.http_client {
+            Some(client) => client,
+            None => {
+                let mut builder = net::http_client_builder();
+                if let Some(tls) = self.tls {
+                    builder = tls.apply(builder);
+                }
+                if let Some(options) = self.configure_http {
+                    builder = options(builder);
+                }
+                builder.build().map_err(BuildError::HttpClient)?
+            }
+        };
+
+        // Group traffic only reaches sockets bound to the wildcard address
+        let discovery_ip = match self.bind_addr {
+            IpAddr::V4(ip) if !self.multicast => ip,
+            _ => Ipv4Addr::UNSPECIFIED,
+        };
+        let discovery_addr = SocketAddrV4::new(discovery_ip, multicast_port);
+        let socket = bind_discovery_socket(discovery_addr)
+            .await
+            .map_err(|source| BuildError::Bind { addr: SocketAddr::V4(discovery_addr), source })?;
+        let socket_v6 = if self.multicast { try_bind_discovery_socket_v6(multicast_port) } else { None };
+
+        let platform = self.platform.unwrap_or_else(platform::native);
+        let (default_downloads, pictures) = match &self.storage_root {
+            Some(root) => (root.join("Download/AirSend"), root.join("Pictures/AirSend")),
+            None => (platform.download_dir(), platform.pictures_dir()),
+        };
+        let download_dir = self.download_dir.unwrap_or(default_downloads);
+        let routes = self.routes.unwrap_or_else(|| RoutingTable::media_to(pictures));
+        let hooks = self
+            .hooks
+            .unwrap_or_else(|| HookPipeline::from_config(&hooks::default_steps(), platform.clone()));
+
+        let client = Client {
+            device,
+            socket: socket.into(),
+            multicast_addr: SocketAddrV4::new(self.multicast_group, multicast_port),
+            socket_v6,
+            multicast_addr_v6: SocketAddrV6::new(self.multicast_group_v6, multicast_port, 0, 0),
+            port,
+            bind_addr: self.bind_addr,
+            http_addr: Default::default(),
+            multicast: self.multicast,
+            peers: Default::default(),
+            http_client,
+            sessions: Default::default(),
+            download_dir: download_dir.to_string_lossy().to_string(),
+            text_filter: self.text_filter,
+            accept_policy: self.accept_policy,
+            routes: Arc::new(routes),
+            conflict_policy: self.conflict_policy,
+            preserve_timestamps: self.preserve_timestamps,
+            hooks,
+            platform,
+            clock: self.clock,
+            events: Events::new(self.event_sinks),
+            memberships: Default::default(),
+            memberships_v6: Default::default(),
+            scan_lock: Default::default(),
+        };
+        client.refresh_interfaces().await;
+        Ok(client)
+    }
+}
+
+impl Client {
+    pub fn builder() -> ClientBuilder {
+        ClientBuilder::new()
+    }
+}
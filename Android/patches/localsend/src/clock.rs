@@ -0,0 +1,82 @@
+//! Time as seen by the client: wall-clock reads for routing placeholders and
+//! history, and sleeps for the announce loop and callers' settle delays.
+//! Tests swap in a [`ManualClock`] and move time by hand.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::time::Duration;
+
+use chrono::{DateTime, Local};
+use tokio::sync::watch;
+
+pub type Sleep = Pin<Box<dyn Future<Output = ()> + Send>>;
+
+pub trait Clock: Send + Sync {
+    fn now(&self) -> DateTime<Local>;
+
+    fn sleep(&self, duration: Duration) -> Sleep;
+}
+
+/// The real thing: `Local::now` and `tokio::time::sleep`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Local> {
+        Local::now()
+    }
+
+    fn sleep(&self, duration: Duration) -> Sleep {
+        Box::pin(tokio::time::sleep(duration))
+    }
+}
+
+/// A clock that only moves when told to. Sleeps finish once [`advance`]
+/// or [`set`] carries the time past their deadline.
+///
+/// [`advance`]: ManualClock::advance
+/// [`set`]: ManualClock::set
+pub struct ManualClock {
+    now: watch::Sender<DateTime<Local>>,
+}
+
+impl ManualClock {
+    pub fn new(start: DateTime<Local>) -> Self {
+        Self { now: watch::Sender::new(start) }
+    }
+
+    pub fn advance(&self, duration: Duration) {
+        self.now.send_modify(|now| *now = later(*now, duration).unwrap_or(*now));
+    }
+
+    pub fn set(&self, time: DateTime<Local>) {
+        self.now.send_replace(time);
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> DateTime<Local> {
+        *self.now.borrow()
+    }
+
+    fn sleep(&self, duration: Duration) -> Sleep {
+        let deadline = later(self.now(), duration);
+        let mut now = self.now.subscribe();
+        Box::pin(async move {
+            let Some(deadline) = deadline else {
+                return std::future::pending().await;
+            };
+            while *now.borrow_and_update() < deadline {
+                if now.changed().await.is_err() {
+                    // Clock dropped: time will never come
+                    std::future::pending::<()>().await;
+                }
+            }
+        })
+    }
+}
+
+/// `None` when the result would not fit in a `DateTime`.
+fn later(time: DateTime<Local>, duration: Duration) -> Option<DateTime<Local>> {
+    time.checked_add_signed(chrono::Duration::from_std(duration).ok()?)
+}
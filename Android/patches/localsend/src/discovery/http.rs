@@ -1,46 +1,57 @@
-use std::{collections::HashMap, net::SocketAddr, sync::Arc};
-use tokio::sync::Mutex;
+use std::{net::SocketAddr, time::Duration};
 
-use axum::{extract::{ConnectInfo, State}, Extension, Json};
+use axum::{extract::{rejection::JsonRejection, ConnectInfo, State}, Extension, Json};
 
-use crate::{models::device::DeviceInfo, Client};
+use crate::{discovery::remember_peer, error::LocalSendError, events::Events, models::device::DeviceInfo, net::{base_url, canonical}, Client, Peers};
 
 impl Client {
     pub async fn announce_http(&self, ip: Option<SocketAddr>, protocol: &str) -> crate::error::Result<()> {
         if let Some(ip) = ip {
-            let url = format!("{}://{}/api/localsend/v2/register", protocol, ip);
-            // 使用 Client 实例自己的 http_client (这已经被我们在 main.rs 里注入了不安全证书配置)
+            let url = format!("{}/api/localsend/v2/register", base_url(protocol, &ip));
+            // 使用 Client 实例自己的 http_client (证书/代理策略由 ClientBuilder 决定)
             self.http_client.post(&url).json(&self.device).send().await?;
         }
         Ok(())
     }
 
-    pub async fn announce_http_legacy(&self) -> crate::error::Result<()> {
-        // send the reqwest to all local ip addresses from 192.168.0.0 to 192.168.255.255
-        let mut address_list = Vec::new();
-        for j in 0..256 {
-            for k in 0..256 {
-                address_list.push(format!("192.168.{:03}.{}:53317", j, k));
+    /// Asks a host directly for its `DeviceInfo`, for peers multicast can't
+    /// reach. Tries our own protocol first, then the other one; the returned
+    /// info carries whichever protocol answered.
+    pub async fn fetch_info(&self, addr: SocketAddr, timeout: Duration) -> crate::error::Result<DeviceInfo> {
+        let fallback = if self.device.protocol == "https" { "http" } else { "https" };
+        let mut last_err = LocalSendError::PeerNotFound;
+        for protocol in [self.device.protocol.as_str(), fallback] {
+            let url = format!("{}/api/localsend/v2/info", base_url(protocol, &addr));
+            let response = match self.http_client.get(&url).timeout(timeout).send().await {
+                Ok(response) => response,
+                Err(e) => {
+                    last_err = e.into();
+                    continue;
+                }
+            };
+            if !response.status().is_success() {
+                last_err = LocalSendError::from_response(addr, response).await;
+                continue;
             }
+            let mut device: DeviceInfo = response.json().await?;
+            device.protocol = protocol.to_string();
+            device.port = addr.port();
+            return Ok(device);
         }
-
-        let protocol = &self.device.protocol;
-        for ip in address_list {
-            let url = format!("{}://{}/api/localsend/v2/register", protocol, ip);
-            self.http_client.post(&url).json(&self.device).send().await?;
-        }
-        Ok(())
+        Err(last_err)
     }
 }
 
 pub async fn register_device(
-    State(peers): State<Arc<Mutex<HashMap<String, (SocketAddr, DeviceInfo)>>>>,
+    State(peers): State<Peers>,
     Extension(client): Extension<DeviceInfo>,
+    Extension(events): Extension<Events>,
     ConnectInfo(addr): ConnectInfo<SocketAddr>,
-    Json(device): Json<DeviceInfo>,
-) -> Json<DeviceInfo> {
-    let mut addr = addr;
+    device: Result<Json<DeviceInfo>, JsonRejection>,
+) -> crate::error::Result<Json<DeviceInfo>> {
+    let Json(device) = device.map_err(|e| LocalSendError::InvalidBody(e.body_text()))?;
+    let mut addr = canonical(addr);
     addr.set_port(device.port);
-    peers.lock().await.insert(device.fingerprint.clone(), (addr, device.clone()));
-    Json(client)
+    remember_peer(&peers, &events, addr, device).await;
+    Ok(Json(client))
 }
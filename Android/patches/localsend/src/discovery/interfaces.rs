@@ -0,0 +1,277 @@
+use std::collections::HashMap;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6};
+use std::sync::Arc;
+
+use socket2::{Domain, InterfaceIndexOrAddress, Protocol, SockRef, Socket, Type};
+use tokio::net::UdpSocket;
+use tracing::{info, warn};
+
+use crate::net::is_link_local;
+use crate::Client;
+
+/// Interface name prefixes that never carry LAN multicast: VPN tunnels,
+/// point-to-point links and cellular modems.
+const EXCLUDED_PREFIXES: &[&str] = &["tun", "ppp", "rmnet", "ccmni", "dummy", "ipsec", "wg"];
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct NetInterface {
+    pub name: String,
+    /// Group membership is held by index: the kernel ties it to the address
+    /// used when joining otherwise, which may go away before the interface.
+    pub index: u32,
+    pub addr: Ipv4Addr,
+    pub netmask: Ipv4Addr,
+}
+
+impl NetInterface {
+    pub fn is_eligible(&self) -> bool {
+        !self.addr.is_loopback()
+            && !self.addr.is_unspecified()
+            && !is_excluded(&self.name)
+    }
+}
+
+/// An interface carrying an IPv6 link-local address, identified by index
+/// since that is what IPv6 multicast membership is keyed on.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct NetInterfaceV6 {
+    pub name: String,
+    pub index: u32,
+    pub addr: Ipv6Addr,
+}
+
+fn is_excluded(name: &str) -> bool {
+    EXCLUDED_PREFIXES.iter().any(|p| name.starts_with(p))
+}
+
+/// A multicast group membership on one interface, plus the socket used to
+/// send announcements out of that interface.
+pub struct Membership {
+    pub interface: NetInterface,
+    pub sender: Arc<UdpSocket>,
+}
+
+pub struct MembershipV6 {
+    pub interface: NetInterfaceV6,
+    pub sender: Arc<UdpSocket>,
+}
+
+/// Memberships keyed by interface name and address, so every IPv4 address
+/// of a multi-address interface gets its own announcement sender. The group
+/// itself is joined once per interface.
+pub type Memberships = Arc<tokio::sync::Mutex<HashMap<(String, Ipv4Addr), Membership>>>;
+pub type MembershipsV6 = Arc<tokio::sync::Mutex<HashMap<String, MembershipV6>>>;
+
+/// Lists the IPv4 interfaces that should take part in discovery.
+pub fn eligible_interfaces() -> Vec<NetInterface> {
+    let addrs = match if_addrs::get_if_addrs() {
+        Ok(addrs) => addrs,
+        Err(e) => {
+            warn!(error = %e, "failed to enumerate network interfaces");
+            return Vec::new();
+        }
+    };
+
+    addrs
+        .into_iter()
+        .filter_map(|iface| match (iface.addr, iface.index) {
+            (if_addrs::IfAddr::V4(v4), Some(index)) => Some(NetInterface {
+                name: iface.name,
+                index,
+                addr: v4.ip,
+                netmask: v4.netmask,
+            }),
+            _ => None,
+        })
+        .filter(NetInterface::is_eligible)
+        .collect()
+}
+
+/// Lists interfaces with an IPv6 link-local address usable for discovery.
+pub fn eligible_interfaces_v6() -> Vec<NetInterfaceV6> {
+    let addrs = match if_addrs::get_if_addrs() {
+        Ok(addrs) => addrs,
+        Err(_) => return Vec::new(),
+    };
+
+    let mut interfaces: Vec<NetInterfaceV6> = Vec::new();
+    for iface in addrs {
+        let (if_addrs::IfAddr::V6(v6), Some(index)) = (&iface.addr, iface.index) else {
+            continue;
+        };
+        if !is_link_local(&v6.ip) || is_excluded(&iface.name) {
+            continue;
+        }
+        if interfaces.iter().any(|i| i.name == iface.name) {
+            continue;
+        }
+        interfaces.push(NetInterfaceV6 { name: iface.name, index, addr: v6.ip });
+    }
+    interfaces
+}
+
+/// Binds the IPv6 discovery socket on `[::]:port`. IPv6-only so it can sit
+/// next to the IPv4 socket on the same port.
+pub fn bind_discovery_socket_v6(port: u16) -> std::io::Result<UdpSocket> {
+    let socket = Socket::new(Domain::IPV6, Type::DGRAM, Some(Protocol::UDP))?;
+    socket.set_only_v6(true)?;
+    socket.set_multicast_loop_v6(true)?;
+    socket.bind(&SocketAddr::V6(SocketAddrV6::new(Ipv6Addr::UNSPECIFIED, port, 0, 0)).into())?;
+    socket.set_nonblocking(true)?;
+    UdpSocket::from_std(socket.into())
+}
+
+fn bind_sender_v6(interface: &NetInterfaceV6) -> std::io::Result<UdpSocket> {
+    let socket = Socket::new(Domain::IPV6, Type::DGRAM, Some(Protocol::UDP))?;
+    socket.set_only_v6(true)?;
+    socket.set_multicast_if_v6(interface.index)?;
+    socket.set_multicast_loop_v6(true)?;
+    socket.bind(&SocketAddr::V6(SocketAddrV6::new(interface.addr, 0, 0, interface.index)).into())?;
+    socket.set_nonblocking(true)?;
+    UdpSocket::from_std(socket.into())
+}
+
+fn join_v4(socket: &UdpSocket, group: Ipv4Addr, index: u32) -> std::io::Result<()> {
+    SockRef::from(socket).join_multicast_v4_n(&group, &InterfaceIndexOrAddress::Index(index))
+}
+
+fn leave_v4(socket: &UdpSocket, group: Ipv4Addr, index: u32) -> std::io::Result<()> {
+    SockRef::from(socket).leave_multicast_v4_n(&group, &InterfaceIndexOrAddress::Index(index))
+}
+
+fn bind_sender(interface: &NetInterface) -> std::io::Result<UdpSocket> {
+    let socket = Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::UDP))?;
+    socket.set_multicast_if_v4(&interface.addr)?;
+    socket.set_multicast_loop_v4(true)?;
+    socket.set_multicast_ttl_v4(255)?;
+    socket.bind(&SocketAddr::V4(SocketAddrV4::new(interface.addr, 0)).into())?;
+    socket.set_nonblocking(true)?;
+    UdpSocket::from_std(socket.into())
+}
+
+impl Client {
+    /// Re-enumerates interfaces, joining the multicast group on new ones and
+    /// leaving it on vanished ones. Returns `true` if anything changed.
+    pub async fn refresh_interfaces(&self) -> bool {
+        if !self.multicast {
+            return false;
+        }
+        let current = eligible_interfaces();
+        let group = *self.multicast_addr.ip();
+        let mut memberships = self.memberships.lock().await;
+        let mut changed = false;
+
+        let vanished: Vec<NetInterface> = memberships
+            .values()
+            .filter(|m| !current.contains(&m.interface))
+            .map(|m| m.interface.clone())
+            .collect();
+        memberships.retain(|_, membership| current.contains(&membership.interface));
+        for interface in vanished {
+            // Group membership is per interface: only leave once its last
+            // address is gone. The kernel may already have dropped it for us.
+            if !memberships.values().any(|m| m.interface.index == interface.index) {
+                let _ = leave_v4(&self.socket, group, interface.index);
+            }
+            info!(interface = %interface.name, addr = %interface.addr, "interface went away");
+            changed = true;
+        }
+
+        for interface in current {
+            let key = (interface.name.clone(), interface.addr);
+            if memberships.contains_key(&key) {
+                continue;
+            }
+            // A second address on an interface that already joined the group
+            // only needs its own sender; joining again would fail with EADDRINUSE.
+            let joined = memberships.values().any(|m| m.interface.index == interface.index);
+            if !joined {
+                if let Err(e) = join_v4(&self.socket, group, interface.index) {
+                    warn!(%group, interface = %interface.name, addr = %interface.addr, error = %e, "failed to join multicast group");
+                    continue;
+                }
+            }
+            let sender = match bind_sender(&interface) {
+                Ok(sender) => Arc::new(sender),
+                Err(e) => {
+                    warn!(interface = %interface.name, addr = %interface.addr, error = %e, "failed to bind multicast sender");
+                    if !joined {
+                        let _ = leave_v4(&self.socket, group, interface.index);
+                    }
+                    continue;
+                }
+            };
+            info!(%group, interface = %interface.name, addr = %interface.addr, "joined multicast group");
+            memberships.insert(key, Membership { interface, sender });
+            changed = true;
+        }
+
+        drop(memberships);
+
+        let changed_v6 = self.refresh_interfaces_v6().await;
+        changed || changed_v6
+    }
+
+    async fn refresh_interfaces_v6(&self) -> bool {
+        let Some(socket) = &self.socket_v6 else {
+            return false;
+        };
+        let current = eligible_interfaces_v6();
+        let group = *self.multicast_addr_v6.ip();
+        let mut memberships = self.memberships_v6.lock().await;
+        let mut changed = false;
+
+        memberships.retain(|key, membership| {
+            if current.contains(&membership.interface) {
+                return true;
+            }
+            let _ = socket.leave_multicast_v6(&group, membership.interface.index);
+            info!(interface = %key, "IPv6 interface went away");
+            changed = true;
+            false
+        });
+
+        for interface in current {
+            if memberships.contains_key(&interface.name) {
+                continue;
+            }
+            if let Err(e) = socket.join_multicast_v6(&group, interface.index) {
+                warn!(%group, interface = %interface.name, error = %e, "failed to join IPv6 multicast group");
+                continue;
+            }
+            let sender = match bind_sender_v6(&interface) {
+                Ok(sender) => Arc::new(sender),
+                Err(e) => {
+                    warn!(interface = %interface.name, error = %e, "failed to bind IPv6 multicast sender");
+                    let _ = socket.leave_multicast_v6(&group, interface.index);
+                    continue;
+                }
+            };
+            info!(%group, interface = %interface.name, addr = %interface.addr, "joined IPv6 multicast group");
+            memberships.insert(interface.name.clone(), MembershipV6 { interface, sender });
+            changed = true;
+        }
+
+        changed
+    }
+
+    /// Interfaces currently used for multicast discovery.
+    pub async fn interfaces(&self) -> Vec<NetInterface> {
+        self.memberships
+            .lock()
+            .await
+            .values()
+            .map(|m| m.interface.clone())
+            .collect()
+    }
+
+    /// Interfaces currently used for IPv6 multicast discovery.
+    pub async fn interfaces_v6(&self) -> Vec<NetInterfaceV6> {
+        self.memberships_v6
+            .lock()
+            .await
+            .values()
+            .map(|m| m.interface.clone())
+            .collect()
+    }
+}
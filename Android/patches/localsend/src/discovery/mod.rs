@@ -1,9 +1,14 @@
 use std::net::SocketAddr;
 
-use crate::{models::device::DeviceInfo, Client};
+use tracing::{debug, warn};
+
+use crate::events::{Event, Events};
+use crate::{models::device::DeviceInfo, net::is_ipv6, Client, Peers};
 
 pub mod http;
+pub mod interfaces;
 pub mod multicast;
+pub mod scan;
 
 impl Client {
     pub async fn announce(&self, socket: Option<SocketAddr>) -> crate::error::Result<()> {
@@ -12,17 +17,30 @@ impl Client {
         Ok(())
     }
 
+    /// Records a peer found some other way (a favourite, a probed address)
+    /// just like discovery would, including [`Event::PeerDiscovered`] when new.
+    pub async fn remember_peer(&self, addr: SocketAddr, device: DeviceInfo) {
+        remember_peer(&self.peers, &self.events, addr, device).await;
+    }
+
+    #[tracing::instrument(skip_all, fields(%src))]
     async fn process_device(&self, message: &str, src: SocketAddr ) {
         if let Ok(device) = serde_json::from_str::<DeviceInfo>(message) {
             if device.fingerprint == self.device.fingerprint {
                 return;
             }
 
-            let mut src = src;
+            let mut src = crate::net::canonical(src);
             src.set_port(device.port); // Update the port to the one the device sent
 
-            let mut peers = self.peers.lock().await;
-            peers.insert(device.fingerprint.clone(), (src.clone(), device.clone()));
+            // A dual-stack peer announces on both families; stick with IPv4
+            // once known so the address doesn't flap between announcements.
+            if let Some((known, _)) = self.peers.lock().await.get(&device.fingerprint) {
+                if is_ipv6(&src) && !is_ipv6(known) {
+                    src = *known;
+                }
+            }
+            remember_peer(&self.peers, &self.events, src, device.clone()).await;
 
             if device.announce != Some(true) {
                 return;
@@ -30,13 +48,22 @@ impl Client {
 
             // Announce in return upon receiving a valid device message and it wants announcements
             if let Err(e) = self.announce_multicast().await {
-                eprintln!("Error during multicast announcement: {}", e);
+                warn!(peer = %device.fingerprint, error = %e, "multicast announcement in return failed");
             }
             if let Err(e) = self.announce_http(Some(src), &device.protocol).await {
-                eprintln!("Error during HTTP announcement: {}", e);
+                warn!(peer = %device.fingerprint, error = %e, "HTTP announcement in return failed");
             };
         } else {
-            eprintln!("Received invalid message: {}", message);
+            // Anyone on the LAN can send this; log its size, not its content
+            debug!(bytes = message.len(), "ignoring invalid discovery message");
         }
     }
 }
+
+/// Records `device` at `addr`, telling the event sinks when it is new.
+pub(crate) async fn remember_peer(peers: &Peers, events: &Events, addr: SocketAddr, device: DeviceInfo) {
+    let known = peers.lock().await.insert(device.fingerprint.clone(), (addr, device.clone())).is_some();
+    if !known {
+        events.emit(Event::PeerDiscovered { addr, device });
+    }
+}
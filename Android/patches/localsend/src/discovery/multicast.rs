@@ -1,26 +1,94 @@
+use std::net::SocketAddrV6;
+
+use tokio::net::UdpSocket;
+use tracing::{info, warn};
+
 use crate::Client;
 
 impl Client {
     pub async fn announce_multicast(&self) -> crate::error::Result<()> {
+        if !self.multicast {
+            return Ok(());
+        }
         let msg = self.device.to_json()?;
-        let addr = self.multicast_addr.clone();
-        self.socket.send_to(msg.as_bytes(), addr).await?;
-        Ok(())
+        let addr = self.multicast_addr;
+
+        let senders: Vec<_> = self
+            .memberships
+            .lock()
+            .await
+            .values()
+            .map(|m| (m.interface.name.clone(), m.sender.clone()))
+            .collect();
+
+        let senders_v6: Vec<_> = self
+            .memberships_v6
+            .lock()
+            .await
+            .values()
+            .map(|m| (m.interface.name.clone(), m.interface.index, m.sender.clone()))
+            .collect();
+
+        // No eligible interface yet: let the kernel pick a route
+        if senders.is_empty() && senders_v6.is_empty() {
+            self.socket.send_to(msg.as_bytes(), addr).await?;
+            return Ok(());
+        }
+
+        // Announce on every interface; only fail if none of them worked
+        let mut last_err = None;
+        let mut delivered = false;
+        for (name, index, sender) in senders_v6 {
+            let addr_v6 = SocketAddrV6::new(*self.multicast_addr_v6.ip(), self.multicast_addr_v6.port(), 0, index);
+            match sender.send_to(msg.as_bytes(), addr_v6).await {
+                Ok(_) => delivered = true,
+                Err(e) => {
+                    warn!(interface = %name, error = %e, "IPv6 multicast announcement failed");
+                    last_err = Some(e);
+                }
+            }
+        }
+        for (name, sender) in senders {
+            match sender.send_to(msg.as_bytes(), addr).await {
+                Ok(_) => delivered = true,
+                Err(e) => {
+                    warn!(interface = %name, error = %e, "multicast announcement failed");
+                    last_err = Some(e);
+                }
+            }
+        }
+        match last_err {
+            Some(e) if !delivered => Err(e.into()),
+            _ => Ok(()),
+        }
     }
 
     pub async fn listen_multicast(&self) -> crate::error::Result<()> {
-        let mut buf = [0; 65536];
-        println!("Socket local addr: {:?}", self.socket.local_addr()?);
-        println!("Listening on multicast addr: {}", self.multicast_addr);
+        if !self.multicast {
+            return Ok(());
+        }
+        info!(local = %self.socket.local_addr()?, group = %self.multicast_addr, "listening for multicast announcements");
+
+        match &self.socket_v6 {
+            Some(socket_v6) => {
+                info!(group = %self.multicast_addr_v6, "listening for IPv6 multicast announcements");
+                tokio::try_join!(self.receive_loop(&self.socket), self.receive_loop(socket_v6))?;
+            }
+            None => self.receive_loop(&self.socket).await?,
+        }
+        Ok(())
+    }
 
+    async fn receive_loop(&self, socket: &UdpSocket) -> crate::error::Result<()> {
+        let mut buf = vec![0; 65536];
         loop {
-            match self.socket.recv_from(&mut buf).await {
+            match socket.recv_from(&mut buf).await {
                 Ok((size, src)) => {
                     let received_msg = String::from_utf8_lossy(&buf[..size]);
                     self.process_device(&received_msg, src).await;
                 }
                 Err(e) => {
-                    eprintln!("Error receiving message: {}", e);
+                    warn!(error = %e, "error receiving discovery message");
                     tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
                 }
             }
@@ -0,0 +1,150 @@
+use std::io::ErrorKind;
+use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4};
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+use tracing::info;
+
+use crate::discovery::interfaces::{eligible_interfaces, NetInterface};
+use crate::discovery::remember_peer;
+use crate::models::device::DeviceInfo;
+use crate::net::base_url;
+use crate::Client;
+
+/// Subnets wider than this (a /22 or bigger) are narrowed to the /24 around
+/// our own address, otherwise a /16 hotspot or corporate network would take
+/// minutes.
+const MAX_SCAN_PREFIX: u32 = 23;
+
+#[derive(Debug, Clone)]
+pub struct ScanOptions {
+    /// Probes in flight at once.
+    pub concurrency: usize,
+    /// Per-host budget covering connect, TLS and response.
+    pub timeout: Duration,
+}
+
+impl Default for ScanOptions {
+    fn default() -> Self {
+        Self {
+            concurrency: 64,
+            timeout: Duration::from_millis(800),
+        }
+    }
+}
+
+/// Host addresses worth probing on the interface's subnet, excluding our own.
+pub fn scan_targets(interface: &NetInterface) -> Vec<Ipv4Addr> {
+    let ip = u32::from(interface.addr);
+    let mut mask = u32::from(interface.netmask);
+    if mask.count_ones() < MAX_SCAN_PREFIX {
+        mask = u32::MAX << 8;
+    }
+    let network = ip & mask;
+    let broadcast = network | !mask;
+    let hosts = match broadcast - network {
+        // /32: nobody else on the link
+        0 => return Vec::new(),
+        // /31 point-to-point link (RFC 3021): both addresses are hosts
+        1 => network..=broadcast,
+        _ => network + 1..=broadcast - 1,
+    };
+    hosts.filter(|host| *host != ip).map(Ipv4Addr::from).collect()
+}
+
+impl Client {
+    /// Fallback discovery for networks that drop multicast: registers with
+    /// every host on the local subnets over HTTP and records whoever answers.
+    /// Returns the number of peers found.
+    #[tracing::instrument(skip_all)]
+    pub async fn scan_subnets(&self, options: ScanOptions) -> crate::error::Result<usize> {
+        let targets: Vec<SocketAddr> = eligible_interfaces()
+            .iter()
+            .flat_map(scan_targets)
+            .map(|ip| SocketAddr::V4(SocketAddrV4::new(ip, self.port)))
+            .collect();
+        info!(hosts = targets.len(), "scanning subnets for LocalSend peers");
+        self.scan_hosts(targets, options).await
+    }
+
+    /// Registers with each of `hosts` and records whoever answers. Returns
+    /// the number of peers found.
+    pub async fn scan_hosts(&self, hosts: Vec<SocketAddr>, options: ScanOptions) -> crate::error::Result<usize> {
+        // Serialise scans; a second caller simply waits for the running one
+        let _scan = self.scan_lock.lock().await;
+
+        let semaphore = Arc::new(Semaphore::new(options.concurrency.max(1)));
+        let mut probes = JoinSet::new();
+        for addr in hosts {
+            let client = self.clone();
+            let semaphore = semaphore.clone();
+            let timeout = options.timeout;
+            probes.spawn(async move {
+                let _permit = semaphore.acquire_owned().await.ok()?;
+                client.probe(addr, timeout).await
+            });
+        }
+
+        let mut found = 0;
+        while let Some(result) = probes.join_next().await {
+            let Ok(Some((addr, device))) = result else {
+                continue;
+            };
+            if device.fingerprint == self.device.fingerprint {
+                continue;
+            }
+            remember_peer(&self.peers, &self.events, addr, device).await;
+            found += 1;
+        }
+
+        info!(found, "subnet scan finished");
+        Ok(found)
+    }
+
+    /// Registers with a single host, trying our own protocol first and then
+    /// the other one, since peers don't all agree on HTTPS.
+    async fn probe(&self, addr: SocketAddr, timeout: Duration) -> Option<(SocketAddr, DeviceInfo)> {
+        let fallback = if self.device.protocol == "https" { "http" } else { "https" };
+        for protocol in [self.device.protocol.as_str(), fallback] {
+            let url = format!("{}/api/localsend/v2/register", base_url(protocol, &addr));
+            let response = match self.http_client.post(&url).json(&self.device).timeout(timeout).send().await {
+                Ok(response) => response,
+                // Nothing listening or unreachable: the other protocol won't help.
+                // A TLS handshake against a plain-HTTP peer also surfaces as a
+                // connect error, and is exactly when the fallback is needed.
+                Err(e) if e.is_timeout() || is_unreachable(&e) => return None,
+                Err(_) => continue,
+            };
+            if let Ok(device) = response.json::<DeviceInfo>().await {
+                let mut addr = addr;
+                addr.set_port(device.port);
+                return Some((addr, device));
+            }
+        }
+        None
+    }
+}
+
+/// Whether the TCP connection itself failed, as opposed to TLS or HTTP on an
+/// established connection.
+fn is_unreachable(e: &reqwest::Error) -> bool {
+    let mut source = std::error::Error::source(e);
+    while let Some(err) = source {
+        if let Some(io) = err.downcast_ref::<std::io::Error>() {
+            if matches!(
+                io.kind(),
+                ErrorKind::ConnectionRefused
+                    | ErrorKind::TimedOut
+                    | ErrorKind::HostUnreachable
+                    | ErrorKind::NetworkUnreachable
+                    | ErrorKind::AddrNotAvailable
+            ) {
+                return true;
+            }
+        }
+        source = err.source();
+    }
+    false
+}
@@ -1,3 +1,11 @@
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use serde_json::json;
+
+/// Longest peer response body kept in an error, in bytes.
+const MAX_BODY_BYTES: usize = 512;
+
 #[derive(Debug, thiserror::Error)]
 pub enum LocalSendError {
     #[error("IO error: {0}")]
@@ -27,8 +35,8 @@ pub enum LocalSendError {
     #[error("Peer not found")]
     PeerNotFound,
 
-    #[error("Upload failed")]
-    UploadFailed,
+    #[error("Upload of {file_id} in session {session_id} failed with {status}: {body}")]
+    UploadFailed { session_id: String, file_id: String, status: u16, body: String },
 
     #[error("Invalid token")]
     InvalidToken,
@@ -36,8 +44,152 @@ pub enum LocalSendError {
     #[error("Session inactive")]
     SessionInactive,
 
-    #[error("Cancel Failed")]
-    CancelFailed,
+    #[error("Unknown session {0}")]
+    SessionNotFound(String),
+
+    #[error("File {0} is already being uploaded or has arrived")]
+    DuplicateUpload(String),
+
+    #[error("Malformed request: {0}")]
+    InvalidBody(String),
+
+    #[error("Cancelling session {session_id} failed with {status}: {body}")]
+    CancelFailed { session_id: String, status: u16, body: String },
+
+    #[error("Peer {peer} answered {status}: {body}")]
+    UnexpectedStatus { peer: String, status: u16, body: String },
+
+    #[error("Transfer rejected by peer")]
+    Rejected,
+
+    #[error("Transfer cancelled by peer")]
+    Cancelled,
+
+    #[error("Timed out")]
+    Timeout,
+
+    #[error("Invalid path {name:?}: {reason}")]
+    InvalidPath { name: String, reason: String },
+
+    #[error("Invalid client configuration: {0}")]
+    Build(#[from] BuildError),
+}
+
+impl LocalSendError {
+    /// Status the server answers with when a handler fails this way, per the
+    /// LocalSend v2 protocol.
+    pub fn status_code(&self) -> StatusCode {
+        match self {
+            LocalSendError::InvalidPin => StatusCode::UNAUTHORIZED,
+            LocalSendError::Rejected | LocalSendError::InvalidToken => StatusCode::FORBIDDEN,
+            LocalSendError::SessionBlocked | LocalSendError::DuplicateUpload(_) => StatusCode::CONFLICT,
+            LocalSendError::TooManyRequests => StatusCode::TOO_MANY_REQUESTS,
+            LocalSendError::SessionNotFound(_)
+            | LocalSendError::SessionInactive
+            | LocalSendError::InvalidPath { .. }
+            | LocalSendError::InvalidBody(_)
+            | LocalSendError::SerializationError(_) => StatusCode::BAD_REQUEST,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    /// Whether trying the same operation again later may succeed: the peer
+    /// was busy or briefly unreachable, as opposed to refusing or the
+    /// request being wrong.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            LocalSendError::SessionBlocked | LocalSendError::TooManyRequests | LocalSendError::Timeout => true,
+            LocalSendError::RequestError(e) => {
+                e.is_timeout() || e.is_connect() || e.status().is_some_and(|s| retryable_status(s.as_u16()))
+            }
+            LocalSendError::IOError(e) => matches!(
+                e.kind(),
+                std::io::ErrorKind::ConnectionReset
+                    | std::io::ErrorKind::ConnectionAborted
+                    | std::io::ErrorKind::TimedOut
+                    | std::io::ErrorKind::Interrupted
+            ),
+            LocalSendError::UploadFailed { status, .. }
+            | LocalSendError::CancelFailed { status, .. }
+            | LocalSendError::UnexpectedStatus { status, .. } => retryable_status(*status),
+            _ => false,
+        }
+    }
+
+    /// Maps a peer's error response to the matching variant, keeping the
+    /// body for anything the protocol gives no meaning to.
+    pub(crate) async fn from_response(peer: impl ToString, response: reqwest::Response) -> Self {
+        let status = response.status().as_u16();
+        match status {
+            401 => LocalSendError::InvalidPin,
+            403 => LocalSendError::Rejected,
+            // Busy with another sender's session
+            409 => LocalSendError::SessionBlocked,
+            429 => LocalSendError::TooManyRequests,
+            _ => LocalSendError::UnexpectedStatus { peer: peer.to_string(), status, body: body_text(response).await },
+        }
+    }
+}
+
+fn retryable_status(status: u16) -> bool {
+    matches!(status, 408 | 409 | 429) || status >= 500
+}
+
+/// The start of a response body, for error messages.
+pub(crate) async fn body_text(response: reqwest::Response) -> String {
+    let body = response.bytes().await.unwrap_or_default();
+    let mut text = String::from_utf8_lossy(&body[..body.len().min(MAX_BODY_BYTES)]).to_string();
+    if body.len() > MAX_BODY_BYTES {
+        text.push('…');
+    }
+    text
+}
+
+/// Answers `{"message": ...}` like the reference implementation. Failures on
+/// our side only get a generic message, since their detail carries local
+/// paths and OS error text; it is logged instead.
+impl IntoResponse for LocalSendError {
+    fn into_response(self) -> Response {
+        let status = self.status_code();
+        let message = if status.is_server_error() {
+            tracing::error!(error = %self, "request failed");
+            "Internal server error".to_string()
+        } else {
+            self.to_string()
+        };
+        (status, Json(json!({ "message": message }))).into_response()
+    }
+}
+
+/// Why [`ClientBuilder::build`](crate::builder::ClientBuilder::build) refused.
+#[derive(Debug, thiserror::Error)]
+pub enum BuildError {
+    #[error("Device alias is empty")]
+    EmptyAlias,
+
+    #[error("Port 0 cannot be advertised to peers")]
+    ZeroPort,
+
+    #[error("Unknown protocol {0:?}, expected \"http\" or \"https\"")]
+    UnknownProtocol(String),
+
+    #[error("{0} is not a multicast address")]
+    NotMulticast(std::net::IpAddr),
+
+    #[error("Accept policy has an empty PIN")]
+    EmptyPin,
+
+    #[error("Download directory {0:?} is not a directory")]
+    NotADirectory(std::path::PathBuf),
+
+    #[error("TLS and HTTP options cannot be applied to a caller-supplied HTTP client")]
+    HttpClientConflict,
+
+    #[error("Failed to build HTTP client: {0}")]
+    HttpClient(#[source] reqwest::Error),
+
+    #[error("Failed to bind {addr}: {source}")]
+    Bind { addr: std::net::SocketAddr, source: std::io::Error },
 }
 
 pub type Result<T> = std::result::Result<T, LocalSendError>;
@@ -0,0 +1,114 @@
+//! Notifications about what the client is doing, for embedders that want to
+//! show progress or react to transfers without polling `sessions`.
+
+use std::fmt;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use tokio::sync::{broadcast, mpsc};
+
+use crate::models::device::DeviceInfo;
+use crate::redact::Redacted;
+use crate::transfer::session::SessionStatus;
+
+#[derive(Clone)]
+pub enum Event {
+    /// A peer we had not seen before announced itself or answered a probe.
+    PeerDiscovered { addr: SocketAddr, device: DeviceInfo },
+    /// An incoming transfer passed the accept policy. `files` counts the
+    /// files the sender will actually upload.
+    TransferAccepted { session_id: String, sender: DeviceInfo, files: usize },
+    /// An incoming transfer was turned away by the accept policy.
+    TransferRejected { sender: DeviceInfo },
+    /// A file of an incoming session is done. `path` is where it was written,
+    /// `None` for text, which is delivered separately.
+    FileReceived { session_id: String, file_id: String, path: Option<PathBuf> },
+    /// Clipboard text arrived and passed the text filter.
+    TextReceived { session_id: String, sender: DeviceInfo, text: String },
+    /// An incoming session completed or was cancelled by the sender.
+    SessionFinished { session_id: String, status: SessionStatus },
+}
+
+/// Like a derived `Debug`, except that clipboard text only shows its size:
+/// embedders tend to log events wholesale.
+impl fmt::Debug for Event {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Event::PeerDiscovered { addr, device } => {
+                f.debug_struct("PeerDiscovered").field("addr", addr).field("device", device).finish()
+            }
+            Event::TransferAccepted { session_id, sender, files } => f
+                .debug_struct("TransferAccepted")
+                .field("session_id", session_id)
+                .field("sender", sender)
+                .field("files", files)
+                .finish(),
+            Event::TransferRejected { sender } => f.debug_struct("TransferRejected").field("sender", sender).finish(),
+            Event::FileReceived { session_id, file_id, path } => f
+                .debug_struct("FileReceived")
+                .field("session_id", session_id)
+                .field("file_id", file_id)
+                .field("path", path)
+                .finish(),
+            Event::TextReceived { session_id, sender, text } => f
+                .debug_struct("TextReceived")
+                .field("session_id", session_id)
+                .field("sender", sender)
+                .field("text", &Redacted(text))
+                .finish(),
+            Event::SessionFinished { session_id, status } => f
+                .debug_struct("SessionFinished")
+                .field("session_id", session_id)
+                .field("status", status)
+                .finish(),
+        }
+    }
+}
+
+/// Receives [`Event`]s. Called inline on the networking tasks, so sinks must
+/// not block; hand the event to a channel if there is real work to do.
+pub trait EventSink: Send + Sync {
+    fn send(&self, event: &Event);
+}
+
+impl<F: Fn(&Event) + Send + Sync> EventSink for F {
+    fn send(&self, event: &Event) {
+        self(event)
+    }
+}
+
+impl EventSink for mpsc::UnboundedSender<Event> {
+    fn send(&self, event: &Event) {
+        // A closed receiver just means nobody is listening any more
+        let _ = mpsc::UnboundedSender::send(self, event.clone());
+    }
+}
+
+impl EventSink for broadcast::Sender<Event> {
+    fn send(&self, event: &Event) {
+        let _ = broadcast::Sender::send(self, event.clone());
+    }
+}
+
+/// The sinks a client was built with.
+#[derive(Clone, Default)]
+pub struct Events {
+    sinks: Arc<[Arc<dyn EventSink>]>,
+}
+
+impl Events {
+    pub fn new(sinks: Vec<Arc<dyn EventSink>>) -> Self {
+        Self { sinks: sinks.into() }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sinks.is_empty()
+    }
+
+    pub fn emit(&self, event: Event) {
+        for sink in self.sinks.iter() {
+            sink.send(&event);
+        }
+    }
+}
@@ -1,77 +1,137 @@
+pub mod builder;
+pub mod clock;
 pub mod discovery;
 pub mod error;
+pub mod events;
 pub mod models;
+pub mod net;
+pub mod oneshot;
+pub mod platform;
+pub mod redact;
 pub mod server;
 pub mod transfer;
 
+use crate::discovery::interfaces::{bind_discovery_socket_v6, Memberships, MembershipsV6};
 use crate::models::device::DeviceInfo;
+use crate::clock::Clock;
+use crate::events::Events;
+use crate::platform::Platform;
 use std::collections::HashMap;
-use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6};
 use tokio::net::UdpSocket;
 use tokio::task::JoinHandle;
+use tracing::{info, warn, error};
 use std::sync::Arc;
 use tokio::sync::Mutex;
+use transfer::accept::AcceptPolicy;
+use transfer::conflict::ConflictPolicy;
+use transfer::hooks::HookPipeline;
+use transfer::routing::RoutingTable;
 use transfer::session::Session;
+use transfer::upload::TextFilter;
 
+/// Known peers keyed by fingerprint.
+pub type Peers = Arc<Mutex<HashMap<String, (SocketAddr, DeviceInfo)>>>;
+
+/// Sessions in both directions keyed by session ID.
+pub type Sessions = Arc<Mutex<HashMap<String, Session>>>;
+
+/// A LocalSend node: discovery, the HTTP server and the sending side.
+/// Configured once through [`Client::builder`]; afterwards only runtime
+/// state (peers, sessions) changes.
 #[derive(Clone)]
 pub struct Client {
-    pub device: DeviceInfo,
-    pub socket: Arc<UdpSocket>,
-    pub multicast_addr: SocketAddrV4,
-    pub port: u16,
-    pub peers: Arc<Mutex<HashMap<String, (SocketAddr, DeviceInfo)>>>,
-    pub sessions: Arc<Mutex<HashMap<String, Session>>>, // Session ID to Session
-    pub http_client: reqwest::Client,
-    pub download_dir: String,
+    pub(crate) device: DeviceInfo,
+    pub(crate) socket: Arc<UdpSocket>,
+    pub(crate) multicast_addr: SocketAddrV4,
+    pub(crate) socket_v6: Option<Arc<UdpSocket>>,
+    pub(crate) multicast_addr_v6: SocketAddrV6,
+    pub(crate) port: u16,
+    /// Address the HTTP server listens on; unspecified means dual-stack on
+    /// every interface.
+    pub(crate) bind_addr: IpAddr,
+    /// Where the HTTP server actually listens while it runs.
+    pub(crate) http_addr: Arc<std::sync::Mutex<Option<SocketAddr>>>,
+    /// Join multicast groups and announce. Off, discovery is HTTP only.
+    pub(crate) multicast: bool,
+    pub(crate) peers: Peers,
+    pub(crate) sessions: Sessions,
+    pub(crate) http_client: reqwest::Client,
+    pub(crate) download_dir: String,
+    pub(crate) text_filter: Option<TextFilter>,
+    pub(crate) accept_policy: AcceptPolicy,
+    pub(crate) routes: Arc<RoutingTable>,
+    pub(crate) conflict_policy: ConflictPolicy,
+    /// Apply the sender's mtime/atime to received files.
+    pub(crate) preserve_timestamps: bool,
+    /// Side effects run on every received file, in order.
+    pub(crate) hooks: HookPipeline,
+    /// Media scanning, notifications, clipboard and default directories.
+    pub(crate) platform: Arc<dyn Platform>,
+    pub(crate) clock: Arc<dyn Clock>,
+    pub(crate) events: Events,
+    pub(crate) memberships: Memberships,
+    pub(crate) memberships_v6: MembershipsV6,
+    pub(crate) scan_lock: Arc<Mutex<()>>,
+}
+
+const MULTICAST_GROUP: Ipv4Addr = Ipv4Addr::new(224, 0, 0, 167);
+/// Link-local scope counterpart of `224.0.0.167`.
+const MULTICAST_GROUP_V6: Ipv6Addr = Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0, 0, 0x167);
+
+/// Binds the shared discovery socket. Group membership is managed per
+/// interface by [`Client::refresh_interfaces`], so this succeeds even before
+/// any network interface is up.
+async fn bind_discovery_socket(addr: SocketAddrV4) -> std::io::Result<UdpSocket> {
+    let socket = UdpSocket::bind(addr).await?;
+    socket.set_multicast_loop_v4(true)?;
+    socket.set_multicast_ttl_v4(255)?;
+    Ok(socket)
+}
+
+/// IPv6 discovery is best effort: devices with IPv6 disabled keep working
+/// on IPv4 alone.
+fn try_bind_discovery_socket_v6(port: u16) -> Option<Arc<UdpSocket>> {
+    match bind_discovery_socket_v6(port) {
+        Ok(socket) => Some(Arc::new(socket)),
+        Err(e) => {
+            warn!(error = %e, "IPv6 discovery disabled");
+            None
+        }
+    }
 }
 
 impl Client {
-    pub async fn default() -> crate::error::Result<Self> {
-        let device = DeviceInfo::default();
-        let socket = UdpSocket::bind("0.0.0.0:53317").await?;
-        socket.set_multicast_loop_v4(true)?;
-        socket.set_multicast_ttl_v4(255)?;
-        socket.join_multicast_v4(Ipv4Addr::new(224, 0, 0, 167), Ipv4Addr::new(0, 0, 0, 0))?;
-        let multicast_addr = SocketAddrV4::new(Ipv4Addr::new(224, 0, 0, 167), 53317);
-        let port = 53317;
-        let peers = Arc::new(Mutex::new(HashMap::new()));
-        let http_client = reqwest::Client::new();
-        let sessions = Arc::new(Mutex::new(HashMap::new()));
-        let download_dir = "/sdcard/Download/AirSend".to_string();
-
-        Ok(Self {
-            device,
-            socket: socket.into(),
-            multicast_addr,
-            port,
-            peers,
-            http_client,
-            sessions,
-            download_dir,
-        })
+    /// What we announce about ourselves.
+    pub fn device(&self) -> &DeviceInfo {
+        &self.device
+    }
+
+    pub fn port(&self) -> u16 {
+        self.port
     }
 
-    pub async fn with_config(info: DeviceInfo, port: u16, download_dir: String) -> crate::error::Result<Self>{
-        let socket = UdpSocket::bind(format!("0.0.0.0:{}", port.clone())).await?;
-        socket.set_multicast_loop_v4(true)?;
-        socket.set_multicast_ttl_v4(255)?;
-        socket.join_multicast_v4(Ipv4Addr::new(224, 0, 0, 167), Ipv4Addr::new(0, 0, 0, 0))?;
-        let multicast_addr = SocketAddrV4::new(Ipv4Addr::new(224, 0, 0, 167), port.clone());
-        let peers = Arc::new(Mutex::new(HashMap::new()));
-        let http_client = reqwest::Client::new();
-        let sessions = Arc::new(Mutex::new(HashMap::new()));
-
-        Ok(Self {
-            device: info,
-            socket: socket.into(),
-            multicast_addr,
-            port,
-            peers,
-            http_client,
-            sessions,
-            download_dir,
-        })
+    /// Address the HTTP server is listening on, `None` until it has started.
+    /// Without a bind address this is `[::]`, or `0.0.0.0` where IPv6 is
+    /// unavailable.
+    pub fn http_addr(&self) -> Option<SocketAddr> {
+        *self.http_addr.lock().unwrap_or_else(|e| e.into_inner())
+    }
 
+    pub fn download_dir(&self) -> &str {
+        &self.download_dir
+    }
+
+    pub fn peers(&self) -> &Peers {
+        &self.peers
+    }
+
+    pub fn sessions(&self) -> &Sessions {
+        &self.sessions
+    }
+
+    pub fn clock(&self) -> &Arc<dyn Clock> {
+        &self.clock
     }
 
     pub async fn start(&self) -> crate::error::Result<(JoinHandle<()>, JoinHandle<()>, JoinHandle<()>)> {
@@ -79,7 +139,7 @@ impl Client {
             let client = self.clone();
             tokio::spawn(async move {
                 if let Err(e) = client.start_http_server().await {
-                    eprintln!("HTTP server error: {}", e);
+                    error!(error = %e, "HTTP server stopped");
                 }
             })
         };
@@ -88,7 +148,7 @@ impl Client {
             let client = self.clone();
             tokio::spawn(async move {
                 if let Err(e) = client.listen_multicast().await {
-                    eprintln!("UDP listener error: {}", e);
+                    error!(error = %e, "UDP listener stopped");
                 }
             })
         };
@@ -97,10 +157,14 @@ impl Client {
             let client = self.clone();
             tokio::spawn(async move {
                 loop {
+                    // Pick up hotspot / Wi-Fi interfaces that appeared since the last round
+                    if client.refresh_interfaces().await {
+                        info!("network interfaces changed, re-announcing");
+                    }
                     if let Err(e) = client.announce(None).await {
-                        eprintln!("Announcement error: {}", e);
+                        warn!(error = %e, "announcement failed");
                     }
-                    tokio::time::sleep(std::time::Duration::from_secs(5)).await;
+                    client.clock.sleep(std::time::Duration::from_secs(5)).await;
                 }
             })
         };
@@ -9,6 +9,8 @@ pub enum DeviceType {
     Web,
     Headless,
     Server,
+    /// Anything newer apps send that we don't know about.
+    #[serde(other)]
     Unknown,
 }
 
@@ -22,7 +24,10 @@ pub struct DeviceInfo {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub device_type: Option<DeviceType>,
     pub fingerprint: String,
+    // Optional in `/info` responses from some clients
+    #[serde(default = "default_port")]
     pub port: u16,
+    #[serde(default = "default_protocol")]
     pub protocol: String,
     #[serde(default)]
     pub download: bool,
@@ -37,6 +42,14 @@ pub enum Protocol {
     Https,
 }
 
+fn default_port() -> u16 {
+    53317
+}
+
+fn default_protocol() -> String {
+    "https".to_string()
+}
+
 impl Default for DeviceInfo {
     fn default() -> Self {
         Self {
@@ -45,8 +58,8 @@ impl Default for DeviceInfo {
             device_model: None,
             device_type: Some(DeviceType::Headless),
             fingerprint: Uuid::new_v4().to_string(),
-            port: 53317,
-            protocol: "https".to_string(),
+            port: default_port(),
+            protocol: default_protocol(),
             download: true,
             announce: Some(true),
         }
@@ -1,5 +1,5 @@
 use crate::error::LocalSendError;
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, NaiveDateTime, Utc};
 use serde::{Deserialize, Serialize};
 use std::time::SystemTime;
 use std::path::Path;
@@ -27,6 +27,16 @@ pub struct FileMetadataExt {
     pub accessed: Option<String>,
 }
 
+impl FileMetadataExt {
+    pub fn modified_time(&self) -> Option<SystemTime> {
+        parse_datetime(self.modified.as_deref()?)
+    }
+
+    pub fn accessed_time(&self) -> Option<SystemTime> {
+        parse_datetime(self.accessed.as_deref()?)
+    }
+}
+
 impl FileMetadata {
     pub fn from_path(path: &Path) -> crate::error::Result<Self> {
         let metadata = path.metadata()?;
@@ -34,8 +44,18 @@ impl FileMetadata {
             return Err(LocalSendError::NotAFile);
         }
 
-        let id = path.to_str().unwrap().to_string();
-        let file_name = path.file_name().unwrap().to_str().unwrap().to_string();
+        // Peers only understand UTF-8 names; refuse rather than send a mangled one
+        let not_utf8 = || LocalSendError::InvalidPath {
+            name: path.to_string_lossy().to_string(),
+            reason: "not valid UTF-8".to_string(),
+        };
+        let id = path.to_str().ok_or_else(not_utf8)?.to_string();
+        let file_name = path
+            .file_name()
+            .ok_or_else(|| LocalSendError::InvalidPath { name: id.clone(), reason: "no file name".to_string() })?
+            .to_str()
+            .ok_or_else(not_utf8)?
+            .to_string();
         let size = metadata.len();
 
         let file_type = mime_guess::from_path(path)
@@ -46,8 +66,8 @@ impl FileMetadata {
         let sha256 = Some(sha256::try_digest(path)?);
 
         let metadata = Some(FileMetadataExt {
-            modified: metadata.modified().ok().map(|t| format_datetime(t)),
-            accessed: metadata.accessed().ok().map(|t| format_datetime(t)),
+            modified: metadata.modified().ok().map(format_datetime),
+            accessed: metadata.accessed().ok().map(format_datetime),
         });
 
         Ok(FileMetadata {
@@ -66,3 +86,33 @@ fn format_datetime(system_time: SystemTime) -> String {
     let datetime: DateTime<Utc> = system_time.into();
     datetime.to_rfc3339()
 }
+
+/// Parses the timestamps other LocalSend clients send. The reference app
+/// uses RFC 3339, but older or third-party clients have been seen sending
+/// RFC 2822, a bare `YYYY-MM-DD HH:MM:SS` (taken as UTC) or Unix epoch
+/// seconds / milliseconds.
+pub fn parse_datetime(value: &str) -> Option<SystemTime> {
+    let value = value.trim();
+    if let Ok(dt) = DateTime::parse_from_rfc3339(value) {
+        return Some(dt.into());
+    }
+    if let Ok(dt) = DateTime::parse_from_rfc2822(value) {
+        return Some(dt.into());
+    }
+    for format in ["%Y-%m-%dT%H:%M:%S%.f", "%Y-%m-%d %H:%M:%S%.f"] {
+        if let Ok(naive) = NaiveDateTime::parse_from_str(value, format) {
+            return Some(naive.and_utc().into());
+        }
+    }
+    if let Ok(epoch) = value.parse::<i64>() {
+        // From 10^11 on, seconds would be past the year 5000 and milliseconds
+        // are already 1973, so that is where the two are told apart
+        let dt = if epoch.unsigned_abs() >= 100_000_000_000 {
+            DateTime::from_timestamp_millis(epoch)
+        } else {
+            DateTime::from_timestamp(epoch, 0)
+        };
+        return dt.map(SystemTime::from);
+    }
+    None
+}
@@ -0,0 +1,129 @@
+use std::net::{IpAddr, Ipv6Addr, SocketAddr, SocketAddrV6};
+use std::sync::Arc;
+
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+
+/// Synthetic host suffix used to carry scoped IPv6 link-local addresses
+/// through reqwest, whose URL parser rejects zone IDs (`[fe80::1%wlan0]`).
+const LINK_LOCAL_SUFFIX: &str = ".link-local.localsend";
+
+/// Port LocalSend uses unless a peer says otherwise.
+pub const DEFAULT_PORT: u16 = 53317;
+
+/// How the HTTP client treats peers' certificates. LocalSend peers present
+/// self-signed certificates, so talking HTTPS to them in practice needs
+/// [`TlsConfig::self_signed`].
+#[derive(Clone, Default)]
+pub struct TlsConfig {
+    pub accept_invalid_certs: bool,
+    /// Extra roots to trust besides the system store.
+    pub root_certificates: Vec<reqwest::Certificate>,
+}
+
+impl TlsConfig {
+    pub fn self_signed() -> Self {
+        Self { accept_invalid_certs: true, ..Default::default() }
+    }
+
+    pub(crate) fn apply(self, mut builder: reqwest::ClientBuilder) -> reqwest::ClientBuilder {
+        for cert in self.root_certificates {
+            builder = builder.add_root_certificate(cert);
+        }
+        builder.danger_accept_invalid_certs(self.accept_invalid_certs)
+    }
+}
+
+/// Resolves a user-supplied `host[:port]` (IP, `[v6]:port` or hostname),
+/// defaulting to [`DEFAULT_PORT`].
+pub async fn resolve_addr(address: &str) -> crate::error::Result<SocketAddr> {
+    let address = address.trim();
+    if let Ok(addr) = address.parse::<SocketAddr>() {
+        return Ok(addr);
+    }
+    if let Ok(ip) = address.parse::<IpAddr>() {
+        return Ok(SocketAddr::new(ip, DEFAULT_PORT));
+    }
+    let has_port = address.rsplit_once(':').is_some_and(|(_, p)| p.parse::<u16>().is_ok());
+    let target = if has_port { address.to_string() } else { format!("{}:{}", address, DEFAULT_PORT) };
+    let mut addrs = tokio::net::lookup_host(&target).await?;
+    addrs.next().ok_or(crate::error::LocalSendError::PeerNotFound)
+}
+
+/// Folds IPv4-mapped IPv6 addresses (`::ffff:a.b.c.d`) seen on the dual-stack
+/// listener back to plain IPv4. Anything else, including the scope ID of a
+/// link-local address, is kept as is.
+pub fn canonical(addr: SocketAddr) -> SocketAddr {
+    match addr {
+        SocketAddr::V6(v6) => match v6.ip().to_ipv4_mapped() {
+            Some(v4) => SocketAddr::new(v4.into(), v6.port()),
+            None => addr,
+        },
+        SocketAddr::V4(_) => addr,
+    }
+}
+
+/// Formats `protocol://host:port` for a peer, bracketing IPv6 addresses and
+/// encoding link-local scope IDs so [`ScopedResolver`] can recover them.
+pub fn base_url(protocol: &str, addr: &SocketAddr) -> String {
+    match canonical(*addr) {
+        SocketAddr::V4(v4) => format!("{}://{}", protocol, v4),
+        SocketAddr::V6(v6) if is_link_local(v6.ip()) && v6.scope_id() != 0 => format!(
+            "{}://{}.s{}{}:{}",
+            protocol,
+            v6.ip().to_string().replace(':', "-"),
+            v6.scope_id(),
+            LINK_LOCAL_SUFFIX,
+            v6.port()
+        ),
+        SocketAddr::V6(v6) => format!("{}://[{}]:{}", protocol, v6.ip(), v6.port()),
+    }
+}
+
+pub fn is_link_local(ip: &Ipv6Addr) -> bool {
+    (ip.segments()[0] & 0xffc0) == 0xfe80
+}
+
+/// Recovers the scoped address from a host produced by [`base_url`]. The
+/// port is left at 0; reqwest fills it in from the URL.
+pub fn parse_link_local_host(host: &str) -> Option<SocketAddr> {
+    let host = host.strip_suffix(LINK_LOCAL_SUFFIX)?;
+    let (ip, scope) = host.rsplit_once(".s")?;
+    let ip: Ipv6Addr = ip.replace('-', ":").parse().ok()?;
+    let scope: u32 = scope.parse().ok()?;
+    Some(SocketAddr::V6(SocketAddrV6::new(ip, 0, 0, scope)))
+}
+
+/// DNS resolver that understands the hosts produced by [`base_url`] and
+/// defers to the system resolver for everything else.
+#[derive(Debug, Default)]
+pub struct ScopedResolver;
+
+impl Resolve for ScopedResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let host = name.as_str().to_string();
+        Box::pin(async move {
+            if let Some(addr) = parse_link_local_host(&host) {
+                let addrs: Addrs = Box::new(std::iter::once(addr));
+                return Ok(addrs);
+            }
+            let resolved: Vec<SocketAddr> = tokio::net::lookup_host((host.as_str(), 0)).await?.collect();
+            let addrs: Addrs = Box::new(resolved.into_iter());
+            Ok(addrs)
+        })
+    }
+}
+
+/// A reqwest builder able to reach every address [`base_url`] produces.
+/// Callers supplying their own client through
+/// [`ClientBuilder::http_client`](crate::builder::ClientBuilder::http_client)
+/// should start from this.
+///
+/// SNI is off: peers are addressed by IP, and the only host names we put in
+/// URLs are the synthetic link-local ones, which must not leave the machine.
+pub fn http_client_builder() -> reqwest::ClientBuilder {
+    reqwest::Client::builder().dns_resolver(Arc::new(ScopedResolver)).tls_sni(false)
+}
+
+pub fn is_ipv6(addr: &SocketAddr) -> bool {
+    matches!(canonical(*addr).ip(), IpAddr::V6(_))
+}
@@ -0,0 +1,208 @@
+//! One-shot transfers for scripts and CI: bring a [`Client`] up just long
+//! enough to find the peer, move one batch of files or text, and shut its
+//! background tasks down again.
+
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+
+use crate::discovery::remember_peer;
+use crate::discovery::scan::ScanOptions;
+use crate::error::{LocalSendError, Result};
+use crate::models::device::DeviceInfo;
+use crate::net::resolve_addr;
+use crate::transfer::session::SessionStatus;
+use crate::Client;
+
+/// How often discovery and session state are polled.
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Who to send to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Target {
+    /// The first peer that shows up.
+    Any,
+    /// A discovered peer, by fingerprint or alias (alias compared
+    /// case-insensitively).
+    Peer(String),
+    /// A `host[:port]` asked directly, skipping multicast discovery.
+    Address(String),
+}
+
+impl Target {
+    fn matches(&self, device: &DeviceInfo) -> bool {
+        match self {
+            Target::Any => true,
+            Target::Peer(key) => *key == device.fingerprint || key.eq_ignore_ascii_case(&device.alias),
+            Target::Address(_) => false,
+        }
+    }
+}
+
+/// What to send in one go.
+#[derive(Debug, Clone)]
+pub enum Payload {
+    Files(Vec<PathBuf>),
+    Text(String),
+}
+
+#[derive(Debug)]
+pub struct SendReport {
+    pub peer: DeviceInfo,
+    /// Items delivered (or already present on the receiver).
+    pub sent: Vec<String>,
+    /// Items that failed, with the reason. Sending carries on past failures.
+    pub failed: Vec<(String, LocalSendError)>,
+}
+
+impl SendReport {
+    pub fn is_success(&self) -> bool {
+        self.failed.is_empty()
+    }
+}
+
+#[derive(Debug)]
+pub struct ReceiveReport {
+    pub sender: DeviceInfo,
+    /// Where each received file ended up.
+    pub files: Vec<PathBuf>,
+    /// `text/plain` payloads, which are returned here instead of written.
+    pub texts: Vec<String>,
+}
+
+/// Aborts the client's server, listener and announcer when dropped.
+struct Running(Vec<JoinHandle<()>>);
+
+impl Running {
+    async fn start(client: &Client) -> Result<Self> {
+        let (server, udp, announce) = client.start().await?;
+        Ok(Self(vec![server, udp, announce]))
+    }
+}
+
+impl Drop for Running {
+    fn drop(&mut self) {
+        for handle in &self.0 {
+            handle.abort();
+        }
+    }
+}
+
+impl Client {
+    /// Waits up to `timeout` for `target` to become reachable and records it
+    /// in `peers`, returning its fingerprint and info. Discovered targets
+    /// need the client started; a subnet scan kicks in halfway through for
+    /// networks that drop multicast.
+    pub async fn find_peer(&self, target: &Target, timeout: Duration) -> Result<(String, DeviceInfo)> {
+        if let Target::Address(address) = target {
+            let addr = resolve_addr(address).await?;
+            let device = self.fetch_info(addr, timeout).await?;
+            remember_peer(&self.peers, &self.events, addr, device.clone()).await;
+            return Ok((device.fingerprint.clone(), device));
+        }
+
+        let start = Instant::now();
+        let mut scan: Option<JoinHandle<()>> = None;
+        let found = loop {
+            let found = self
+                .peers
+                .lock()
+                .await
+                .iter()
+                .find(|(_, (_, device))| device.fingerprint != self.device.fingerprint && target.matches(device))
+                .map(|(fingerprint, (_, device))| (fingerprint.clone(), device.clone()));
+            if found.is_some() || start.elapsed() >= timeout {
+                break found;
+            }
+            if scan.is_none() && start.elapsed() >= timeout / 2 {
+                let client = self.clone();
+                scan = Some(tokio::spawn(async move {
+                    let _ = client.scan_subnets(ScanOptions::default()).await;
+                }));
+            }
+            tokio::time::sleep(POLL_INTERVAL).await;
+        };
+        if let Some(scan) = scan {
+            scan.abort();
+        }
+        found.ok_or(LocalSendError::PeerNotFound)
+    }
+
+    /// Starts the client, finds `target` within `timeout`, sends `payload`
+    /// and stops again. Errors only when no transfer could be attempted;
+    /// per-item failures are in the report.
+    pub async fn send_once(&self, target: &Target, payload: Payload, timeout: Duration) -> Result<SendReport> {
+        let _running = match target {
+            Target::Address(_) => None,
+            _ => Some(Running::start(self).await?),
+        };
+        let (fingerprint, peer) = self.find_peer(target, timeout).await?;
+
+        let mut report = SendReport { peer, sent: Vec::new(), failed: Vec::new() };
+        match payload {
+            Payload::Files(paths) => {
+                for path in paths {
+                    let name = path.display().to_string();
+                    match self.send_file(fingerprint.clone(), path).await {
+                        Ok(()) => report.sent.push(name),
+                        // The receiver said no; the rest would be refused too
+                        Err(LocalSendError::Rejected) => {
+                            report.failed.push((name, LocalSendError::Rejected));
+                            break;
+                        }
+                        Err(e) => report.failed.push((name, e)),
+                    }
+                }
+            }
+            Payload::Text(text) => match self.send_text(fingerprint, &text).await {
+                Ok(()) => report.sent.push("text".to_string()),
+                Err(e) => report.failed.push(("text".to_string(), e)),
+            },
+        }
+        Ok(report)
+    }
+
+    /// Starts the client and accepts exactly one incoming session, returning
+    /// once every file in it has arrived. Text is captured rather than
+    /// handed to `text_filter` or the clipboard. `None` waits forever.
+    pub async fn receive_once(mut self, timeout: Option<Duration>) -> Result<ReceiveReport> {
+        let texts = Arc::new(Mutex::new(Vec::new()));
+        let captured = texts.clone();
+        self.text_filter = Some(Arc::new(move |text: &str| {
+            captured.lock().unwrap_or_else(|e| e.into_inner()).push(text.to_string());
+            false
+        }));
+        let _running = Running::start(&self).await?;
+
+        let start = Instant::now();
+        loop {
+            {
+                let sessions = self.sessions.lock().await;
+                let incoming = sessions
+                    .values()
+                    .filter(|s| s.sender.fingerprint != self.device.fingerprint);
+                for session in incoming {
+                    match session.status {
+                        SessionStatus::Cancelled => return Err(LocalSendError::Cancelled),
+                        SessionStatus::Completed => {
+                            let texts = std::mem::take(&mut *texts.lock().unwrap_or_else(|e| e.into_inner()));
+                            return Ok(ReceiveReport {
+                                sender: session.sender.clone(),
+                                files: session.received.values().flatten().cloned().collect(),
+                                texts,
+                            });
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            if timeout.is_some_and(|timeout| start.elapsed() >= timeout) {
+                return Err(LocalSendError::Timeout);
+            }
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    }
+}
@@ -0,0 +1,88 @@
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use super::Platform;
+
+/// App 侧 LocalServerSocket 的抽象命名空间地址 (不含开头的 `\0`)
+pub const APP_IPC_NAME: &str = "airsend_app_ipc";
+
+/// Rooted Android: system services are reached through `am` / `cmd`, and the
+/// companion App owns the clipboard.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Android;
+
+impl Platform for Android {
+    fn name(&self) -> &str {
+        "android"
+    }
+
+    fn media_scan(&self, path: &Path) -> std::io::Result<()> {
+        // 📷 触发 Android 媒体扫描器，让相册立即看到新文件
+        Command::new("am")
+            .args([
+                "broadcast",
+                "-a", "android.intent.action.MEDIA_SCANNER_SCAN_FILE",
+                "-d", &format!("file://{}", path.display()),
+            ])
+            .spawn()?;
+        Ok(())
+    }
+
+    fn notify(&self, title: &str, body: &str) -> std::io::Result<()> {
+        // 直接传参而非拼接 `sh -c`，文件名里的引号不会被解释
+        Command::new("cmd")
+            .args(["notification", "post", "-S", "bigtext", "-t", title, "airsend_rec", body])
+            .spawn()?;
+        Ok(())
+    }
+
+    fn set_clipboard(&self, text: &str) -> std::io::Result<()> {
+        // 守护进程没有 Context，写剪贴板只能交给 App：连接它的反向 IPC，写完即关闭
+        let mut stream = connect_app_ipc()?;
+        stream.write_all(text.as_bytes())?;
+        stream.shutdown(std::net::Shutdown::Write)
+    }
+
+    fn get_clipboard(&self) -> std::io::Result<Option<String>> {
+        // Android 10+ 只允许前台 App 读剪贴板；由 App 通过 SEND_TEXT 主动推送
+        Err(std::io::Error::new(ErrorKind::Unsupported, "clipboard is pushed by the App"))
+    }
+
+    fn download_dir(&self) -> PathBuf {
+        PathBuf::from("/sdcard/Download/AirSend")
+    }
+
+    fn pictures_dir(&self) -> PathBuf {
+        PathBuf::from("/sdcard/Pictures/AirSend")
+    }
+
+    fn state_dir(&self) -> PathBuf {
+        PathBuf::from("/data/local/tmp")
+    }
+
+    fn screenshot_dirs(&self) -> Vec<PathBuf> {
+        // AOSP 原生与国内 OEM (MIUI/HyperOS/ColorOS) 的魔改路径
+        vec![
+            PathBuf::from("/data/media/0/Pictures/Screenshots"),
+            PathBuf::from("/data/media/0/DCIM/Screenshots"),
+        ]
+    }
+}
+
+#[cfg(any(target_os = "android", target_os = "linux"))]
+fn connect_app_ipc() -> std::io::Result<std::os::unix::net::UnixStream> {
+    #[cfg(target_os = "android")]
+    use std::os::android::net::SocketAddrExt;
+    #[cfg(target_os = "linux")]
+    use std::os::linux::net::SocketAddrExt;
+    use std::os::unix::net::{SocketAddr, UnixStream};
+
+    let addr = SocketAddr::from_abstract_name(APP_IPC_NAME)?;
+    UnixStream::connect_addr(&addr)
+}
+
+#[cfg(not(any(target_os = "android", target_os = "linux")))]
+fn connect_app_ipc() -> std::io::Result<std::os::unix::net::UnixStream> {
+    Err(std::io::Error::new(ErrorKind::Unsupported, "abstract sockets need Linux"))
+}
@@ -0,0 +1,145 @@
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use super::Platform;
+
+/// How a [`Linux`] platform reaches the clipboard.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ClipboardBackend {
+    /// `wl-copy` / `wl-paste` (Wayland sessions).
+    Wayland,
+    /// `xclip` (X11 sessions).
+    Xclip,
+    /// No graphical session, e.g. a headless server: received text is kept
+    /// in a plain file so scripts can pick it up.
+    File(PathBuf),
+}
+
+/// Generic Linux desktop or server: XDG directories, `notify-send` and
+/// whichever clipboard tool the session has.
+#[derive(Debug, Clone)]
+pub struct Linux {
+    pub clipboard: ClipboardBackend,
+}
+
+impl Linux {
+    /// Picks the clipboard backend from the session environment.
+    pub fn detect() -> Self {
+        let clipboard = if std::env::var_os("WAYLAND_DISPLAY").is_some() && on_path("wl-copy") {
+            ClipboardBackend::Wayland
+        } else if std::env::var_os("DISPLAY").is_some() && on_path("xclip") {
+            ClipboardBackend::Xclip
+        } else {
+            ClipboardBackend::File(state_dir().join("clipboard.txt"))
+        };
+        Self { clipboard }
+    }
+
+    pub fn with_clipboard(clipboard: ClipboardBackend) -> Self {
+        Self { clipboard }
+    }
+}
+
+impl Platform for Linux {
+    fn name(&self) -> &str {
+        "linux"
+    }
+
+    fn media_scan(&self, _path: &Path) -> std::io::Result<()> {
+        // Desktop indexers (tracker, baloo) watch the XDG directories themselves
+        Ok(())
+    }
+
+    fn notify(&self, title: &str, body: &str) -> std::io::Result<()> {
+        if !on_path("notify-send") {
+            return Err(std::io::Error::new(ErrorKind::NotFound, "notify-send not installed"));
+        }
+        Command::new("notify-send")
+            .args(["--app-name", "AirSend", title, body])
+            .spawn()?;
+        Ok(())
+    }
+
+    fn set_clipboard(&self, text: &str) -> std::io::Result<()> {
+        match &self.clipboard {
+            ClipboardBackend::Wayland => pipe_to(&mut Command::new("wl-copy"), text),
+            ClipboardBackend::Xclip => pipe_to(Command::new("xclip").args(["-selection", "clipboard"]), text),
+            ClipboardBackend::File(path) => {
+                if let Some(dir) = path.parent() {
+                    std::fs::create_dir_all(dir)?;
+                }
+                std::fs::write(path, text)
+            }
+        }
+    }
+
+    fn get_clipboard(&self) -> std::io::Result<Option<String>> {
+        let output = match &self.clipboard {
+            ClipboardBackend::Wayland => Command::new("wl-paste").arg("--no-newline").output()?,
+            ClipboardBackend::Xclip => Command::new("xclip").args(["-selection", "clipboard", "-o"]).output()?,
+            ClipboardBackend::File(path) => {
+                return match std::fs::read_to_string(path) {
+                    Ok(text) if !text.is_empty() => Ok(Some(text)),
+                    Ok(_) => Ok(None),
+                    Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+                    Err(e) => Err(e),
+                };
+            }
+        };
+        // Both tools exit non-zero when the clipboard is empty
+        if !output.status.success() || output.stdout.is_empty() {
+            return Ok(None);
+        }
+        Ok(String::from_utf8(output.stdout).ok())
+    }
+
+    fn download_dir(&self) -> PathBuf {
+        dirs::download_dir().unwrap_or_else(home).join("AirSend")
+    }
+
+    fn pictures_dir(&self) -> PathBuf {
+        dirs::picture_dir().unwrap_or_else(home).join("AirSend")
+    }
+
+    fn state_dir(&self) -> PathBuf {
+        state_dir()
+    }
+
+    fn screenshot_dirs(&self) -> Vec<PathBuf> {
+        // GNOME and KDE both default to ~/Pictures/Screenshots
+        dirs::picture_dir()
+            .map(|dir| vec![dir.join("Screenshots")])
+            .unwrap_or_default()
+    }
+}
+
+fn home() -> PathBuf {
+    dirs::home_dir().unwrap_or_else(|| PathBuf::from("."))
+}
+
+/// `$XDG_STATE_HOME/airsend`, i.e. `~/.local/state/airsend` by default.
+fn state_dir() -> PathBuf {
+    dirs::state_dir()
+        .or_else(|| dirs::home_dir().map(|h| h.join(".local/state")))
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("airsend")
+}
+
+fn on_path(program: &str) -> bool {
+    std::env::var_os("PATH")
+        .is_some_and(|paths| std::env::split_paths(&paths).any(|dir| dir.join(program).is_file()))
+}
+
+fn pipe_to(command: &mut Command, text: &str) -> std::io::Result<()> {
+    let mut child = command.stdin(Stdio::piped()).stdout(Stdio::null()).spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(text.as_bytes())?;
+    }
+    let status = child.wait()?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(std::io::Error::other(format!("clipboard tool exited with {}", status)))
+    }
+}
@@ -0,0 +1,50 @@
+//! Host integration: everything the daemon does to the surrounding OS
+//! besides networking and writing files goes through [`Platform`], so the
+//! same code runs on rooted Android and on a plain Linux box, and tests can
+//! swap in a fake.
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+pub mod android;
+pub mod linux;
+
+pub use android::Android;
+pub use linux::Linux;
+
+pub trait Platform: Send + Sync {
+    fn name(&self) -> &str;
+
+    /// Makes a freshly written file visible to galleries / file indexers.
+    fn media_scan(&self, path: &Path) -> std::io::Result<()>;
+
+    /// Posts a user-visible notification.
+    fn notify(&self, title: &str, body: &str) -> std::io::Result<()>;
+
+    /// Puts received text on the local clipboard.
+    fn set_clipboard(&self, text: &str) -> std::io::Result<()>;
+
+    /// Reads the local clipboard; `None` when it is empty or not text.
+    fn get_clipboard(&self) -> std::io::Result<Option<String>>;
+
+    /// Where unrouted received files go.
+    fn download_dir(&self) -> PathBuf;
+
+    /// Where received photos and videos go by default.
+    fn pictures_dir(&self) -> PathBuf;
+
+    /// Writable directory for configuration and logs.
+    fn state_dir(&self) -> PathBuf;
+
+    /// Directories the OS drops new screenshots into.
+    fn screenshot_dirs(&self) -> Vec<PathBuf>;
+}
+
+/// The platform this binary was built for.
+pub fn native() -> Arc<dyn Platform> {
+    if cfg!(target_os = "android") {
+        Arc::new(Android)
+    } else {
+        Arc::new(Linux::detect())
+    }
+}
@@ -0,0 +1,30 @@
+//! Keeping secrets out of logs. Upload tokens, PINs and clipboard text pass
+//! through the same code that logs sessions and requests, so they are
+//! wrapped before they reach a `tracing` field or a `Debug` impl.
+
+use std::collections::HashMap;
+use std::fmt;
+
+/// Shows that a secret is there, and how big it is, but not what it is.
+pub struct Redacted<'a>(pub &'a str);
+
+impl fmt::Debug for Redacted<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<redacted, {} bytes>", self.0.len())
+    }
+}
+
+impl fmt::Display for Redacted<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self, f)
+    }
+}
+
+/// A file ID to token map with the tokens blanked out.
+pub struct RedactedTokens<'a>(pub &'a HashMap<String, String>);
+
+impl fmt::Debug for RedactedTokens<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.0.iter().map(|(id, token)| (id, Redacted(token)))).finish()
+    }
+}
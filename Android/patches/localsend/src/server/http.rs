@@ -2,20 +2,28 @@ use axum::{
     extract::DefaultBodyLimit, routing::{get, post}, Extension, Json, Router
 };
 use tower_http::limit::RequestBodyLimitLayer;
-use std::net::SocketAddr;
+use socket2::{Domain, Protocol, Socket, Type};
+use std::net::{Ipv6Addr, SocketAddr, SocketAddrV6};
 use tokio::net::TcpListener;
+use tracing::{info, warn};
 
-use crate::{discovery::http::register_device, transfer::upload::{register_prepare_upload, register_upload}, Client};
+use crate::{discovery::http::register_device, transfer::timestamps::PreserveTimestamps, transfer::upload::{register_cancel, register_prepare_upload, register_upload}, Client};
 
 impl Client {
     pub async fn start_http_server(&self) -> crate::error::Result<()> {
         let app = self.create_router();
-        let addr = SocketAddr::from(([0, 0, 0, 0], self.port));
+        let listener = if self.bind_addr.is_unspecified() {
+            bind_dual_stack(self.port)?
+        } else {
+            bind_exact(SocketAddr::new(self.bind_addr, self.port))?
+        };
+        let addr = listener.local_addr()?;
+        info!(%addr, "HTTP server listening");
 
-        let listener = TcpListener::bind(&addr).await?;
-        println!("HTTP server listening on {}", addr);
-
-        axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>()).await?;
+        *self.http_addr.lock().unwrap_or_else(|e| e.into_inner()) = Some(addr);
+        let served = axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>()).await;
+        *self.http_addr.lock().unwrap_or_else(|e| e.into_inner()) = None;
+        served?;
         Ok(())
     }
 
@@ -31,12 +39,58 @@ impl Client {
             }))
             .route("/api/localsend/v2/prepare-upload", post(register_prepare_upload))
             .route("/api/localsend/v2/upload", post(register_upload))
+            .route("/api/localsend/v2/cancel", post(register_cancel))
             .layer(DefaultBodyLimit::disable())
             .layer(RequestBodyLimitLayer::new(1024 * 1024 * 1024))
             .layer(Extension(self.device.clone()))
             .layer(Extension(self.sessions.clone()))
             .layer(Extension(self.download_dir.clone()))
+            .layer(Extension(self.text_filter.clone()))
+            .layer(Extension(self.routes.clone()))
+            .layer(Extension(self.conflict_policy))
+            .layer(Extension(PreserveTimestamps(self.preserve_timestamps)))
+            .layer(Extension(self.hooks.clone()))
+            .layer(Extension(self.platform.clone()))
+            .layer(Extension(self.clock.clone()))
+            .layer(Extension(self.accept_policy.clone()))
+            .layer(Extension(self.events.clone()))
             .with_state(peers)
 
     }
 }
+
+/// Listens on `[::]` accepting both IPv6 and IPv4-mapped connections, falling
+/// back to `0.0.0.0` where IPv6 is unavailable.
+fn bind_dual_stack(port: u16) -> std::io::Result<TcpListener> {
+    let dual = || -> std::io::Result<Socket> {
+        let socket = Socket::new(Domain::IPV6, Type::STREAM, Some(Protocol::TCP))?;
+        socket.set_only_v6(false)?;
+        socket.set_reuse_address(true)?;
+        socket.bind(&SocketAddr::V6(SocketAddrV6::new(Ipv6Addr::UNSPECIFIED, port, 0, 0)).into())?;
+        Ok(socket)
+    };
+    let socket = match dual() {
+        Ok(socket) => socket,
+        Err(e) => {
+            warn!(error = %e, "IPv6 listener unavailable, falling back to IPv4");
+            let socket = Socket::new(Domain::IPV4, Type::STREAM, Some(Protocol::TCP))?;
+            socket.set_reuse_address(true)?;
+            socket.bind(&SocketAddr::from(([0, 0, 0, 0], port)).into())?;
+            socket
+        }
+    };
+    listen(socket)
+}
+
+fn bind_exact(addr: SocketAddr) -> std::io::Result<TcpListener> {
+    let socket = Socket::new(Domain::for_address(addr), Type::STREAM, Some(Protocol::TCP))?;
+    socket.set_reuse_address(true)?;
+    socket.bind(&addr.into())?;
+    listen(socket)
+}
+
+fn listen(socket: Socket) -> std::io::Result<TcpListener> {
+    socket.listen(1024)?;
+    socket.set_nonblocking(true)?;
+    TcpListener::from_std(socket.into())
+}
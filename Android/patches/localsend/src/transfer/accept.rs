@@ -0,0 +1,45 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::error::{LocalSendError, Result};
+use crate::models::{device::DeviceInfo, file::FileMetadata};
+
+/// Decides an incoming transfer from the sender and the files it offers.
+pub type AcceptFilter = Arc<dyn Fn(&DeviceInfo, &HashMap<String, FileMetadata>) -> bool + Send + Sync>;
+
+/// Which incoming `prepare-upload` requests the server accepts. Refusals
+/// answer 403, a missing or wrong PIN 401, as the LocalSend protocol expects.
+#[derive(Clone, Default)]
+pub enum AcceptPolicy {
+    /// Accept everything without asking, as a headless daemon does.
+    #[default]
+    All,
+    /// Only senders whose fingerprint or alias (case-insensitive) is listed.
+    Senders(Vec<String>),
+    /// Senders must pass `?pin=` matching this PIN.
+    Pin(String),
+    Custom(AcceptFilter),
+}
+
+impl AcceptPolicy {
+    pub fn check(&self, sender: &DeviceInfo, files: &HashMap<String, FileMetadata>, pin: Option<&str>) -> Result<()> {
+        let accepted = match self {
+            AcceptPolicy::All => true,
+            AcceptPolicy::Senders(allowed) => allowed
+                .iter()
+                .any(|a| *a == sender.fingerprint || a.eq_ignore_ascii_case(&sender.alias)),
+            AcceptPolicy::Pin(expected) => {
+                if pin != Some(expected.as_str()) {
+                    return Err(LocalSendError::InvalidPin);
+                }
+                true
+            }
+            AcceptPolicy::Custom(filter) => filter(sender, files),
+        };
+        if accepted {
+            Ok(())
+        } else {
+            Err(LocalSendError::Rejected)
+        }
+    }
+}
@@ -0,0 +1,222 @@
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use serde::{Deserialize, Serialize};
+use tokio::fs::OpenOptions;
+use tokio::io::AsyncWriteExt;
+use uuid::Uuid;
+
+use crate::models::file::FileMetadata;
+
+/// What to do when a received file's name is already taken.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictPolicy {
+    /// Keep both: the new file becomes `name (N).ext`.
+    #[default]
+    Rename,
+    /// Atomically replace the existing file.
+    Overwrite,
+    /// Skip if the existing file has the same size and SHA-256, otherwise
+    /// rename. Makes re-sending a folder cheap.
+    SkipIdentical,
+    /// Replace only if the sender's copy is newer, skip otherwise. Falls back
+    /// to renaming when the sender didn't say when its copy was modified.
+    KeepNewest,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WriteOutcome {
+    Written(PathBuf),
+    /// An existing file was kept; the path is that file.
+    Skipped(PathBuf),
+}
+
+/// Upper bound on `name (N).ext` attempts before giving up.
+const MAX_RENAME_ATTEMPTS: u32 = 10_000;
+
+/// Decides from metadata alone whether a file can be left out of a
+/// prepare-upload response, so the sender never uploads it.
+pub async fn can_skip(target: &Path, file: &FileMetadata, policy: ConflictPolicy) -> bool {
+    match policy {
+        ConflictPolicy::SkipIdentical => match &file.sha256 {
+            Some(sha256) => is_identical(target, file.size, sha256).await,
+            None => false,
+        },
+        ConflictPolicy::KeepNewest => match (incoming_modified(file), existing_modified(target).await) {
+            (Some(incoming), Some(existing)) => incoming <= existing,
+            _ => false,
+        },
+        ConflictPolicy::Rename | ConflictPolicy::Overwrite => false,
+    }
+}
+
+/// Writes `body` as `dir/name` according to `policy`.
+///
+/// New files are created with `O_CREAT | O_EXCL` and replacements go through
+/// a temporary file renamed into place, so two concurrent uploads with the
+/// same name can never clobber each other or leave a half-written file.
+pub async fn write_with_policy(
+    dir: &Path,
+    name: &str,
+    body: &[u8],
+    policy: ConflictPolicy,
+    file: &FileMetadata,
+) -> std::io::Result<WriteOutcome> {
+    let target = dir.join(name);
+    match policy {
+        ConflictPolicy::Rename => create_renamed(dir, name, body).await.map(WriteOutcome::Written),
+        ConflictPolicy::Overwrite => {
+            replace(&target, body).await?;
+            Ok(WriteOutcome::Written(target))
+        }
+        ConflictPolicy::SkipIdentical => {
+            // Hash what actually arrived rather than trusting the sender's claim
+            let digest = sha256::digest(body);
+            if is_identical(&target, body.len() as u64, &digest).await {
+                return Ok(WriteOutcome::Skipped(target));
+            }
+            create_renamed(dir, name, body).await.map(WriteOutcome::Written)
+        }
+        ConflictPolicy::KeepNewest => match (incoming_modified(file), existing_modified(&target).await) {
+            (_, None) => create_renamed(dir, name, body).await.map(WriteOutcome::Written),
+            (Some(incoming), Some(existing)) if incoming > existing => {
+                replace(&target, body).await?;
+                Ok(WriteOutcome::Written(target))
+            }
+            (Some(_), Some(_)) => Ok(WriteOutcome::Skipped(target)),
+            (None, Some(_)) => create_renamed(dir, name, body).await.map(WriteOutcome::Written),
+        },
+    }
+}
+
+async fn is_identical(path: &Path, size: u64, sha256: &str) -> bool {
+    match tokio::fs::metadata(path).await {
+        Ok(meta) if meta.is_file() && meta.len() == size => {}
+        _ => return false,
+    }
+    let path = path.to_path_buf();
+    match tokio::task::spawn_blocking(move || sha256::try_digest(path.as_path())).await {
+        Ok(Ok(existing)) => existing.eq_ignore_ascii_case(sha256),
+        _ => false,
+    }
+}
+
+fn incoming_modified(file: &FileMetadata) -> Option<SystemTime> {
+    file.metadata.as_ref()?.modified_time()
+}
+
+async fn existing_modified(path: &Path) -> Option<SystemTime> {
+    tokio::fs::metadata(path).await.ok()?.modified().ok()
+}
+
+pub(crate) fn numbered(name: &str, counter: u32) -> String {
+    let path = Path::new(name);
+    // 提取文件名本体和扩展名 (例如: "photo.png" -> stem: "photo", ext: "png")
+    let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or(name);
+    match path.extension().and_then(|e| e.to_str()) {
+        Some(ext) => format!("{} ({}).{}", stem, counter, ext),
+        None => format!("{} ({})", stem, counter),
+    }
+}
+
+/// Creates `name`, or the first free `name (N).ext`, exclusively.
+async fn create_renamed(dir: &Path, name: &str, body: &[u8]) -> std::io::Result<PathBuf> {
+    for counter in 0..MAX_RENAME_ATTEMPTS {
+        let candidate = match counter {
+            0 => dir.join(name),
+            n => dir.join(numbered(name, n)),
+        };
+        match OpenOptions::new().write(true).create_new(true).open(&candidate).await {
+            Ok(mut out) => {
+                if let Err(e) = write_all(&mut out, body).await {
+                    let _ = tokio::fs::remove_file(&candidate).await;
+                    return Err(e);
+                }
+                return Ok(candidate);
+            }
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    Err(std::io::Error::new(ErrorKind::AlreadyExists, format!("no free name for {}", name)))
+}
+
+/// Moves an existing file to `dir/name`, or the first free `name (N).ext`,
+/// without ever replacing what is there. A hard link claims the name
+/// atomically; where links don't work (another filesystem, FAT/sdcardfs) the
+/// file is copied into an exclusively created one instead.
+pub(crate) fn place_renamed(source: &Path, dir: &Path, name: &str) -> std::io::Result<PathBuf> {
+    for counter in 0..MAX_RENAME_ATTEMPTS {
+        let candidate = match counter {
+            0 => dir.join(name),
+            n => dir.join(numbered(name, n)),
+        };
+        match std::fs::hard_link(source, &candidate) {
+            Ok(()) => {}
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => continue,
+            Err(_) => match copy_new(source, &candidate) {
+                Ok(()) => {}
+                Err(e) if e.kind() == ErrorKind::AlreadyExists => continue,
+                Err(e) => return Err(e),
+            },
+        }
+        std::fs::remove_file(source)?;
+        return Ok(candidate);
+    }
+    Err(std::io::Error::new(ErrorKind::AlreadyExists, format!("no free name for {}", name)))
+}
+
+/// Renames the directory `source` to `parent/name`, or the first free
+/// `name (N)`. The name is claimed with `mkdir` first, which fails if it
+/// exists; renaming onto that empty directory then replaces nothing else.
+pub(crate) fn place_dir_renamed(source: &Path, parent: &Path, name: &str) -> std::io::Result<PathBuf> {
+    for counter in 0..MAX_RENAME_ATTEMPTS {
+        let candidate = match counter {
+            0 => parent.join(name),
+            n => parent.join(format!("{} ({})", name, n)),
+        };
+        match std::fs::create_dir(&candidate) {
+            Ok(()) => {}
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e),
+        }
+        if let Err(e) = std::fs::rename(source, &candidate) {
+            let _ = std::fs::remove_dir(&candidate);
+            return Err(e);
+        }
+        return Ok(candidate);
+    }
+    Err(std::io::Error::new(ErrorKind::AlreadyExists, format!("no free name for {}", name)))
+}
+
+fn copy_new(source: &Path, target: &Path) -> std::io::Result<()> {
+    let mut out = std::fs::File::options().write(true).create_new(true).open(target)?;
+    let copied = std::io::copy(&mut std::fs::File::open(source)?, &mut out).and_then(|_| out.sync_all());
+    if copied.is_err() {
+        let _ = std::fs::remove_file(target);
+    }
+    copied
+}
+
+/// Writes to a hidden temporary file next to `target` and renames it over.
+async fn replace(target: &Path, body: &[u8]) -> std::io::Result<()> {
+    let dir = target.parent().unwrap_or(Path::new("."));
+    let temp = dir.join(format!(".airsend-{}.part", Uuid::new_v4()));
+    let mut out = OpenOptions::new().write(true).create_new(true).open(&temp).await?;
+    if let Err(e) = write_all(&mut out, body).await {
+        let _ = tokio::fs::remove_file(&temp).await;
+        return Err(e);
+    }
+    if let Err(e) = tokio::fs::rename(&temp, target).await {
+        let _ = tokio::fs::remove_file(&temp).await;
+        return Err(e);
+    }
+    Ok(())
+}
+
+async fn write_all(out: &mut tokio::fs::File, body: &[u8]) -> std::io::Result<()> {
+    out.write_all(body).await?;
+    out.sync_all().await
+}
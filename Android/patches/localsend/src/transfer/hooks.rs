@@ -0,0 +1,435 @@
+use std::fs::File;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn, Span};
+
+use crate::models::{device::DeviceInfo, file::FileMetadata};
+use crate::platform::Platform;
+use crate::transfer::conflict::{place_dir_renamed, place_renamed};
+use crate::transfer::paths::{resolve_within, sanitize_relative_path};
+use crate::transfer::routing::mime_matches;
+
+/// Refuse to expand archives past this many bytes (zip bombs).
+pub const MAX_UNZIP_BYTES: u64 = 4 * 1024 * 1024 * 1024;
+/// How long a script hook may run before it is killed.
+pub const DEFAULT_SCRIPT_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// A file that has just been written to disk, as seen by the hooks.
+#[derive(Debug, Clone)]
+pub struct ReceivedFile {
+    /// Current location; hooks that move the file update it for later hooks.
+    pub path: PathBuf,
+    pub file: FileMetadata,
+    pub sender: DeviceInfo,
+}
+
+/// A side effect run after a file has been received. Hooks run in order on
+/// a blocking thread, after the HTTP response has been sent.
+pub trait PostReceiveHook: Send + Sync {
+    fn name(&self) -> &str;
+
+    /// Whether this hook applies to `received`. Defaults to every file.
+    fn applies_to(&self, _received: &ReceivedFile) -> bool {
+        true
+    }
+
+    fn run(&self, received: &mut ReceivedFile) -> std::io::Result<()>;
+}
+
+/// Ordered list of hooks. A failing hook is logged and does not stop the
+/// ones after it.
+#[derive(Clone, Default)]
+pub struct HookPipeline {
+    hooks: Vec<Arc<dyn PostReceiveHook>>,
+}
+
+impl HookPipeline {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, hook: impl PostReceiveHook + 'static) -> &mut Self {
+        self.hooks.push(Arc::new(hook));
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hooks.is_empty()
+    }
+
+    /// Builds the configured steps; system integration goes through `platform`.
+    pub fn from_config(steps: &[HookStep], platform: Arc<dyn Platform>) -> Self {
+        let mut pipeline = Self::new();
+        for step in steps {
+            pipeline.hooks.push(step.build(&platform));
+        }
+        pipeline
+    }
+
+    /// Runs every applicable hook and returns the file as the last hook left it.
+    pub fn run_blocking(&self, mut received: ReceivedFile) -> ReceivedFile {
+        for hook in &self.hooks {
+            if !hook.applies_to(&received) {
+                continue;
+            }
+            if let Err(e) = hook.run(&mut received) {
+                warn!(hook = hook.name(), path = %received.path.display(), error = %e, "⚠️ 接收后处理失败");
+            }
+        }
+        received
+    }
+
+    pub async fn run(&self, received: ReceivedFile) -> ReceivedFile {
+        let pipeline = self.clone();
+        let fallback = received.clone();
+        let span = Span::current();
+        tokio::task::spawn_blocking(move || span.in_scope(|| pipeline.run_blocking(received)))
+            .await
+            .unwrap_or(fallback)
+    }
+}
+
+/// One configured step of the pipeline. `mime` and `extensions` narrow which
+/// files it applies to, with the same semantics as routing rules.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HookStep {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mime: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub extensions: Vec<String>,
+    #[serde(flatten)]
+    pub hook: HookConfig,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum HookConfig {
+    /// Ask the platform's media indexer to pick the file up.
+    MediaScan,
+    /// Post a system notification.
+    Notify {
+        #[serde(default = "default_notify_title")]
+        title: String,
+    },
+    /// Run a program with `AIRSEND_PATH`, `AIRSEND_NAME`, `AIRSEND_MIME`,
+    /// `AIRSEND_SIZE`, `AIRSEND_SENDER` and `AIRSEND_SENDER_FINGERPRINT` set.
+    /// It is killed after `timeout_secs`.
+    Script {
+        command: String,
+        #[serde(default)]
+        args: Vec<String>,
+        #[serde(default = "default_script_timeout_secs")]
+        timeout_secs: u64,
+    },
+    /// Move the file into another directory.
+    Move { destination: String },
+    /// Extract a zip archive next to it, into a folder named after it.
+    Unzip {
+        #[serde(default)]
+        delete_archive: bool,
+    },
+}
+
+fn default_notify_title() -> String {
+    "AirSend".to_string()
+}
+
+fn default_script_timeout_secs() -> u64 {
+    DEFAULT_SCRIPT_TIMEOUT.as_secs()
+}
+
+impl HookStep {
+    fn build(&self, platform: &Arc<dyn Platform>) -> Arc<dyn PostReceiveHook> {
+        let filter = Filter {
+            mime: self.mime.clone(),
+            extensions: self.extensions.clone(),
+        };
+        match &self.hook {
+            HookConfig::MediaScan => Arc::new(Filtered(filter, MediaScan { platform: platform.clone() })),
+            HookConfig::Notify { title } => Arc::new(Filtered(
+                filter,
+                Notify {
+                    title: title.clone(),
+                    platform: platform.clone(),
+                },
+            )),
+            HookConfig::Script { command, args, timeout_secs } => Arc::new(Filtered(
+                filter,
+                Script {
+                    command: command.clone(),
+                    args: args.clone(),
+                    timeout: Duration::from_secs(*timeout_secs),
+                },
+            )),
+            HookConfig::Move { destination } => Arc::new(Filtered(
+                filter,
+                Move {
+                    destination: PathBuf::from(destination),
+                },
+            )),
+            HookConfig::Unzip { delete_archive } => Arc::new(Filtered(
+                filter,
+                Unzip {
+                    delete_archive: *delete_archive,
+                },
+            )),
+        }
+    }
+}
+
+/// The pipeline used when nothing is configured: photos and videos show up
+/// in the gallery right away.
+pub fn default_steps() -> Vec<HookStep> {
+    ["image/*", "video/*"]
+        .into_iter()
+        .map(|mime| HookStep {
+            mime: Some(mime.to_string()),
+            extensions: Vec::new(),
+            hook: HookConfig::MediaScan,
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, Default)]
+struct Filter {
+    mime: Option<String>,
+    extensions: Vec<String>,
+}
+
+impl Filter {
+    fn matches(&self, file: &FileMetadata) -> bool {
+        if let Some(mime) = &self.mime {
+            if !mime_matches(mime, &file.file_type) {
+                return false;
+            }
+        }
+        if self.extensions.is_empty() {
+            return true;
+        }
+        let ext = Path::new(&file.file_name)
+            .extension()
+            .and_then(|e| e.to_str())
+            .unwrap_or_default();
+        self.extensions
+            .iter()
+            .any(|e| e.trim_start_matches('.').eq_ignore_ascii_case(ext))
+    }
+}
+
+struct Filtered<H>(Filter, H);
+
+impl<H: PostReceiveHook> PostReceiveHook for Filtered<H> {
+    fn name(&self) -> &str {
+        self.1.name()
+    }
+
+    fn applies_to(&self, received: &ReceivedFile) -> bool {
+        self.0.matches(&received.file) && self.1.applies_to(received)
+    }
+
+    fn run(&self, received: &mut ReceivedFile) -> std::io::Result<()> {
+        self.1.run(received)
+    }
+}
+
+pub struct MediaScan {
+    pub platform: Arc<dyn Platform>,
+}
+
+impl PostReceiveHook for MediaScan {
+    fn name(&self) -> &str {
+        "media_scan"
+    }
+
+    fn run(&self, received: &mut ReceivedFile) -> std::io::Result<()> {
+        self.platform.media_scan(&received.path)?;
+        info!(path = %received.path.display(), "📸 媒体已落盘，并触发系统相册刷新");
+        Ok(())
+    }
+}
+
+pub struct Notify {
+    pub title: String,
+    pub platform: Arc<dyn Platform>,
+}
+
+impl PostReceiveHook for Notify {
+    fn name(&self) -> &str {
+        "notify"
+    }
+
+    fn run(&self, received: &mut ReceivedFile) -> std::io::Result<()> {
+        let name = received
+            .path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_else(|| "新文件".to_string());
+        self.platform
+            .notify(&self.title, &format!("已收到来自 {} 的文件: {}", received.sender.alias, name))
+    }
+}
+
+pub struct Script {
+    pub command: String,
+    pub args: Vec<String>,
+    pub timeout: Duration,
+}
+
+impl PostReceiveHook for Script {
+    fn name(&self) -> &str {
+        "script"
+    }
+
+    fn run(&self, received: &mut ReceivedFile) -> std::io::Result<()> {
+        let name = received
+            .path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let mut child = Command::new(&self.command)
+            .args(&self.args)
+            .env("AIRSEND_PATH", &received.path)
+            .env("AIRSEND_NAME", name)
+            .env("AIRSEND_MIME", &received.file.file_type)
+            .env("AIRSEND_SIZE", received.file.size.to_string())
+            .env("AIRSEND_SENDER", &received.sender.alias)
+            .env("AIRSEND_SENDER_FINGERPRINT", &received.sender.fingerprint)
+            .spawn()?;
+
+        // 挂住的脚本不能永远占着 hook 线程：超时即杀掉
+        let deadline = Instant::now() + self.timeout;
+        let status = loop {
+            if let Some(status) = child.try_wait()? {
+                break status;
+            }
+            if Instant::now() >= deadline {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(std::io::Error::new(
+                    ErrorKind::TimedOut,
+                    format!("{} killed after {:?}", self.command, self.timeout),
+                ));
+            }
+            std::thread::sleep(Duration::from_millis(50));
+        };
+        if status.success() {
+            Ok(())
+        } else {
+            Err(std::io::Error::other(format!("{} exited with {}", self.command, status)))
+        }
+    }
+}
+
+pub struct Move {
+    pub destination: PathBuf,
+}
+
+impl PostReceiveHook for Move {
+    fn name(&self) -> &str {
+        "move"
+    }
+
+    fn run(&self, received: &mut ReceivedFile) -> std::io::Result<()> {
+        let name = received
+            .path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .ok_or_else(|| std::io::Error::new(ErrorKind::InvalidInput, "no file name"))?;
+        std::fs::create_dir_all(&self.destination)?;
+        // 与接收落盘相同的不覆盖保证：名字被占用就换 `name (N)`，从不替换已有文件
+        received.path = place_renamed(&received.path, &self.destination, &name)?;
+        Ok(())
+    }
+}
+
+pub struct Unzip {
+    pub delete_archive: bool,
+}
+
+impl PostReceiveHook for Unzip {
+    fn name(&self) -> &str {
+        "unzip"
+    }
+
+    fn applies_to(&self, received: &ReceivedFile) -> bool {
+        received.file.file_type == "application/zip"
+            || received
+                .path
+                .extension()
+                .is_some_and(|e| e.eq_ignore_ascii_case("zip"))
+    }
+
+    fn run(&self, received: &mut ReceivedFile) -> std::io::Result<()> {
+        let stem = received
+            .path
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_else(|| "archive".to_string());
+        let parent = received.path.parent().unwrap_or(Path::new("."));
+
+        // 先解压到旁边的临时目录，成功后整体改名到位：重复接收或中途失败都不会留下半棵树
+        let staging = parent.join(format!(".airsend-{}.unzip", uuid::Uuid::new_v4()));
+        std::fs::create_dir(&staging)?;
+        let root = match extract(&received.path, &staging).and_then(|()| place_dir_renamed(&staging, parent, &stem)) {
+            Ok(root) => root,
+            Err(e) => {
+                let _ = std::fs::remove_dir_all(&staging);
+                return Err(e);
+            }
+        };
+
+        if self.delete_archive {
+            std::fs::remove_file(&received.path)?;
+        }
+        received.path = root;
+        Ok(())
+    }
+}
+
+/// Extracts `archive` into the fresh directory `root`.
+fn extract(archive: &Path, root: &Path) -> std::io::Result<()> {
+    let mut archive = zip::ZipArchive::new(File::open(archive)?).map_err(std::io::Error::other)?;
+    let mut total = 0u64;
+    for i in 0..archive.len() {
+        let mut entry = archive.by_index(i).map_err(std::io::Error::other)?;
+        // 压缩包里的路径与 file_name 一样不可信，走同一套清洗
+        let relative = match sanitize_relative_path(entry.name()) {
+            Ok(relative) => relative,
+            Err(e) => {
+                warn!(error = %e, "🚫 跳过压缩包中的非法路径");
+                continue;
+            }
+        };
+        let target = resolve_within(root, &relative).map_err(std::io::Error::other)?;
+        if entry.is_dir() {
+            std::fs::create_dir_all(&target)?;
+            continue;
+        }
+        if entry.is_symlink() {
+            continue;
+        }
+        if let Some(dir) = target.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let mut out = match File::options().write(true).create_new(true).open(&target) {
+            Ok(out) => out,
+            // 同名条目只保留第一个
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => {
+                warn!(entry = %relative.display(), "⚠️ 跳过压缩包中的重复条目");
+                continue;
+            }
+            Err(e) => return Err(e),
+        };
+        let mut limited = std::io::Read::take(&mut entry, MAX_UNZIP_BYTES - total + 1);
+        total += std::io::copy(&mut limited, &mut out)?;
+        if total > MAX_UNZIP_BYTES {
+            return Err(std::io::Error::other("archive expands past the size limit"));
+        }
+    }
+    Ok(())
+}
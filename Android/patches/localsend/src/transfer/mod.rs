@@ -1,3 +1,9 @@
+pub mod accept;
+pub mod conflict;
 pub mod download;
+pub mod hooks;
+pub mod paths;
+pub mod routing;
 pub mod session;
+pub mod timestamps;
 pub mod upload;
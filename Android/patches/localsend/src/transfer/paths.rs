@@ -0,0 +1,222 @@
+use std::path::{Component, Path, PathBuf};
+
+use chrono::{DateTime, Local};
+use unicode_normalization::UnicodeNormalization;
+
+use crate::error::{LocalSendError, Result};
+use crate::models::{device::DeviceInfo, file::FileMetadata};
+use crate::transfer::routing::RoutingTable;
+
+/// Longest file name most filesystems accept, in bytes.
+pub const MAX_COMPONENT_BYTES: usize = 255;
+/// Folder transfers deeper than this are refused outright.
+pub const MAX_DEPTH: usize = 32;
+/// Keeps the joined absolute path comfortably under `PATH_MAX`.
+pub const MAX_PATH_BYTES: usize = 1024;
+
+/// Device names Windows refuses regardless of extension. Received files are
+/// often re-shared to Windows machines or land on exFAT SD cards.
+const RESERVED_NAMES: &[&str] = &[
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+fn invalid(name: &str, reason: &str) -> LocalSendError {
+    LocalSendError::InvalidPath {
+        name: name.to_string(),
+        reason: reason.to_string(),
+    }
+}
+
+/// Turns a sender-supplied `file_name` such as `album/day1/img.jpg` into a
+/// relative path that can be joined under the destination directory.
+///
+/// The name is NFC-normalised first so visually identical names from macOS
+/// (NFD) and Android (NFC) collide instead of coexisting. Both `/` and `\`
+/// separate components, empty and `.` components are dropped. Absolute
+/// paths, drive prefixes, `..`, NUL bytes and over-deep or over-long names
+/// are rejected; every remaining component goes through
+/// [`sanitize_component`].
+pub fn sanitize_relative_path(file_name: &str) -> Result<PathBuf> {
+    if file_name.contains('\0') {
+        return Err(invalid(file_name, "contains NUL"));
+    }
+
+    let normalized: String = file_name.nfc().collect();
+    if normalized.starts_with(['/', '\\']) {
+        return Err(invalid(file_name, "absolute path"));
+    }
+    if has_drive_prefix(&normalized) {
+        return Err(invalid(file_name, "drive prefix"));
+    }
+
+    let mut path = PathBuf::new();
+    let mut depth = 0;
+    for component in normalized.split(['/', '\\']) {
+        match component.trim() {
+            "" | "." => continue,
+            ".." => return Err(invalid(file_name, "parent directory reference")),
+            _ => {}
+        }
+        depth += 1;
+        if depth > MAX_DEPTH {
+            return Err(invalid(file_name, "too deep"));
+        }
+        path.push(sanitize_component(component)?);
+    }
+
+    if path.as_os_str().is_empty() {
+        return Err(invalid(file_name, "empty"));
+    }
+    if path.as_os_str().len() > MAX_PATH_BYTES {
+        return Err(invalid(file_name, "path too long"));
+    }
+    Ok(path)
+}
+
+/// Cleans a single path component:
+///
+/// - control characters are dropped, and characters FAT/exFAT/NTFS reject
+///   (`<>:"|?*`) become `_`
+/// - surrounding whitespace and trailing dots are trimmed
+/// - Windows device names (`CON`, `com1.txt`, ...) get a `_` prefix
+/// - names over [`MAX_COMPONENT_BYTES`] are shortened, keeping the extension
+pub fn sanitize_component(component: &str) -> Result<String> {
+    if component.contains(['/', '\\', '\0']) {
+        return Err(invalid(component, "separator or NUL in component"));
+    }
+
+    let cleaned: String = component
+        .nfc()
+        .filter(|c| !c.is_control())
+        .map(|c| if matches!(c, '<' | '>' | ':' | '"' | '|' | '?' | '*') { '_' } else { c })
+        .collect();
+    let mut name = cleaned.trim().trim_end_matches('.').trim_end().to_string();
+
+    match name.as_str() {
+        "" => return Err(invalid(component, "empty component")),
+        "." | ".." => return Err(invalid(component, "parent directory reference")),
+        _ => {}
+    }
+
+    let stem = name.split('.').next().unwrap_or_default();
+    if RESERVED_NAMES.iter().any(|r| r.eq_ignore_ascii_case(stem)) {
+        name.insert(0, '_');
+    }
+
+    Ok(truncate_component(&name))
+}
+
+/// Shortens `name` to [`MAX_COMPONENT_BYTES`] on a char boundary, keeping a
+/// reasonable extension intact.
+fn truncate_component(name: &str) -> String {
+    if name.len() <= MAX_COMPONENT_BYTES {
+        return name.to_string();
+    }
+    let (stem, ext) = match name.rfind('.') {
+        Some(i) if i > 0 && name.len() - i <= 16 => (&name[..i], &name[i..]),
+        _ => (name, ""),
+    };
+    let mut budget = MAX_COMPONENT_BYTES - ext.len();
+    while !stem.is_char_boundary(budget) {
+        budget -= 1;
+    }
+    format!("{}{}", &stem[..budget], ext)
+}
+
+fn has_drive_prefix(name: &str) -> bool {
+    let bytes = name.as_bytes();
+    bytes.len() >= 2 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':'
+}
+
+/// Joins `relative` under `root`, refusing anything that would end up
+/// outside it. Purely lexical; see [`ensure_within`] for the on-disk check.
+pub fn resolve_within(root: &Path, relative: &Path) -> Result<PathBuf> {
+    let mut resolved = root.to_path_buf();
+    for component in relative.components() {
+        match component {
+            Component::Normal(part) => resolved.push(part),
+            Component::CurDir => {}
+            _ => return Err(invalid(&relative.to_string_lossy(), "escapes destination")),
+        }
+    }
+    if !resolved.starts_with(root) {
+        return Err(invalid(&relative.to_string_lossy(), "escapes destination"));
+    }
+    Ok(resolved)
+}
+
+/// Verifies that an existing directory still resolves inside `root` once
+/// symlinks are followed, so a link planted in the destination can't
+/// redirect writes elsewhere (the daemon runs as root).
+pub async fn ensure_within(root: &Path, dir: &Path) -> Result<()> {
+    let root = tokio::fs::canonicalize(root).await?;
+    let dir = tokio::fs::canonicalize(dir).await?;
+    if dir.starts_with(&root) {
+        Ok(())
+    } else {
+        Err(invalid(&dir.to_string_lossy(), "escapes destination"))
+    }
+}
+
+/// Creates `dir`, which [`resolve_within`] placed under `root`, one
+/// component at a time. An existing component must be a real directory, so
+/// a symlink planted below `root` can't make the daemon create directories
+/// elsewhere before [`ensure_within`] gets to look. `root` itself comes from
+/// the routing table and is created as configured.
+pub async fn create_dir_within(root: &Path, dir: &Path) -> Result<()> {
+    let relative = dir
+        .strip_prefix(root)
+        .map_err(|_| invalid(&dir.to_string_lossy(), "escapes destination"))?;
+    tokio::fs::create_dir_all(root).await?;
+
+    let mut current = root.to_path_buf();
+    for component in relative.components() {
+        let Component::Normal(part) = component else {
+            return Err(invalid(&relative.to_string_lossy(), "escapes destination"));
+        };
+        current.push(part);
+        if let Err(e) = tokio::fs::create_dir(&current).await {
+            if e.kind() != std::io::ErrorKind::AlreadyExists {
+                return Err(e.into());
+            }
+        }
+        // symlink_metadata doesn't follow links: a planted link is not a dir
+        if !tokio::fs::symlink_metadata(&current).await?.is_dir() {
+            return Err(invalid(&current.to_string_lossy(), "not a directory"));
+        }
+    }
+    Ok(())
+}
+
+/// Where an incoming file will be written: the routed destination root,
+/// the (possibly nested) directory under it, and the sanitised file name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReceiveTarget {
+    pub root: PathBuf,
+    pub dir: PathBuf,
+    pub name: String,
+}
+
+impl ReceiveTarget {
+    pub fn plan(
+        file: &FileMetadata,
+        sender: &DeviceInfo,
+        download_dir: &str,
+        routes: &RoutingTable,
+        now: DateTime<Local>,
+    ) -> Result<Self> {
+        let root = routes.destination(file, sender, download_dir, now);
+        let relative = sanitize_relative_path(&file.file_name)?;
+        let dir = resolve_within(&root, relative.parent().unwrap_or(Path::new("")))?;
+        let name = relative
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .ok_or_else(|| invalid(&file.file_name, "empty"))?;
+        Ok(Self { root, dir, name })
+    }
+
+    pub fn path(&self) -> PathBuf {
+        self.dir.join(&self.name)
+    }
+}
@@ -0,0 +1,167 @@
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+
+use crate::models::{device::DeviceInfo, file::FileMetadata};
+
+/// One routing rule. Every condition that is set must match; unset
+/// conditions match anything. `destination` may be absolute or relative to
+/// the download directory and may contain placeholders, see
+/// [`render_template`].
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RouteRule {
+    /// Exact MIME type or a `type/*` wildcard.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mime: Option<String>,
+    /// File extensions without the dot, case-insensitive.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub extensions: Vec<String>,
+    /// Sender fingerprint or alias (alias compared case-insensitively).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sender: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_size: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_size: Option<u64>,
+    pub destination: String,
+}
+
+/// `image/jpeg` or a `image/*` wildcard against a file's MIME type, ignoring
+/// case; senders are not consistent about it. Also used by hook filters.
+pub(crate) fn mime_matches(pattern: &str, file_type: &str) -> bool {
+    match pattern.strip_suffix("/*") {
+        Some(top) => file_type.split('/').next().is_some_and(|t| t.eq_ignore_ascii_case(top)),
+        None => file_type.eq_ignore_ascii_case(pattern),
+    }
+}
+
+impl RouteRule {
+    pub fn matches(&self, file: &FileMetadata, sender: &DeviceInfo) -> bool {
+        if let Some(mime) = &self.mime {
+            if !mime_matches(mime, &file.file_type) {
+                return false;
+            }
+        }
+
+        if !self.extensions.is_empty() {
+            let ext = extension(&file.file_name);
+            if !self.extensions.iter().any(|e| e.trim_start_matches('.').eq_ignore_ascii_case(&ext)) {
+                return false;
+            }
+        }
+
+        if let Some(wanted) = &self.sender {
+            if *wanted != sender.fingerprint && !wanted.eq_ignore_ascii_case(&sender.alias) {
+                return false;
+            }
+        }
+
+        self.min_size.is_none_or(|min| file.size >= min) && self.max_size.is_none_or(|max| file.size <= max)
+    }
+}
+
+/// Ordered routing table; the first matching rule wins and unmatched files
+/// go to the download directory.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct RoutingTable {
+    pub rules: Vec<RouteRule>,
+}
+
+impl Default for RoutingTable {
+    /// Photos and videos land where the Android gallery looks for them.
+    fn default() -> Self {
+        Self::media_to("/sdcard/Pictures/AirSend")
+    }
+}
+
+impl RoutingTable {
+    /// Sends `image/*` and `video/*` to `dir`, everything else to the
+    /// download directory.
+    pub fn media_to(dir: impl AsRef<Path>) -> Self {
+        let destination = dir.as_ref().to_string_lossy().to_string();
+        let media = |mime: &str| RouteRule {
+            mime: Some(mime.to_string()),
+            destination: destination.clone(),
+            ..Default::default()
+        };
+        Self { rules: vec![media("image/*"), media("video/*")] }
+    }
+
+    /// Where `file` goes; `now` fills date placeholders.
+    pub fn destination(&self, file: &FileMetadata, sender: &DeviceInfo, download_dir: &str, now: DateTime<Local>) -> PathBuf {
+        let Some(rule) = self.rules.iter().find(|r| r.matches(file, sender)) else {
+            return PathBuf::from(download_dir);
+        };
+        let rendered = render_template(&rule.destination, file, sender, now);
+        let path = Path::new(&rendered);
+        if path.is_absolute() {
+            path.to_path_buf()
+        } else {
+            Path::new(download_dir).join(path)
+        }
+    }
+}
+
+/// Expands placeholders in a destination template:
+///
+/// - `{sender}` / `{fingerprint}`: sender alias / fingerprint
+/// - `{ext}`: lower-case file extension, `{mime}`: top-level MIME type
+/// - any run of `yyyy`, `mm`, `dd` with separators, e.g. `{yyyy-mm}`
+///
+/// Substituted values are reduced to a single safe path component, so a
+/// sender calling itself `../..` cannot escape the destination.
+pub fn render_template(template: &str, file: &FileMetadata, sender: &DeviceInfo, now: DateTime<Local>) -> String {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        // No closing brace: the rest is literal text, pushed below
+        let Some(len) = rest[start..].find('}') else {
+            break;
+        };
+        out.push_str(&rest[..start]);
+        let key = &rest[start + 1..start + len];
+        let value = match key {
+            "sender" => sender.alias.clone(),
+            "fingerprint" => sender.fingerprint.clone(),
+            "ext" => extension(&file.file_name),
+            "mime" => file.file_type.split('/').next().unwrap_or_default().to_string(),
+            _ if is_date_pattern(key) => {
+                let format = key.replace("yyyy", "%Y").replace("mm", "%m").replace("dd", "%d");
+                now.format(&format).to_string()
+            }
+            // Unknown placeholder: keep it verbatim so typos are visible
+            _ => format!("{{{}}}", key),
+        };
+        out.push_str(&path_component(&value));
+        rest = &rest[start + len + 1..];
+    }
+    out.push_str(rest);
+    out
+}
+
+fn is_date_pattern(key: &str) -> bool {
+    let stripped = key.replace("yyyy", "").replace("mm", "").replace("dd", "");
+    stripped.len() < key.len() && stripped.chars().all(|c| matches!(c, '-' | '_' | '.'))
+}
+
+fn extension(file_name: &str) -> String {
+    Path::new(file_name)
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or_default()
+        .to_ascii_lowercase()
+}
+
+fn path_component(value: &str) -> String {
+    let cleaned: String = value
+        .chars()
+        .map(|c| if c == '/' || c == '\\' || c.is_control() { '_' } else { c })
+        .collect();
+    match cleaned.trim() {
+        "" | "." | ".." => "_".to_string(),
+        trimmed => trimmed.to_string(),
+    }
+}
@@ -1,4 +1,4 @@
-use std::{collections::HashMap, net::SocketAddr};
+use std::{collections::{HashMap, HashSet}, net::SocketAddr, path::PathBuf};
 
 use serde::{Deserialize, Serialize};
 
@@ -13,9 +13,30 @@ pub struct Session {
     pub sender: DeviceInfo,
     pub status: SessionStatus,
     pub addr: SocketAddr,
+    /// Files that have arrived, with where they ended up (`None` for text
+    /// handed to the clipboard). The session completes once every file with
+    /// a token is in here.
+    #[serde(default)]
+    pub received: HashMap<String, Option<PathBuf>>,
+    /// Files being written right now. Their token is taken until the write
+    /// succeeds or fails, so a replayed upload cannot write a second copy.
+    #[serde(default)]
+    pub in_progress: HashSet<String>,
 }
 
-#[derive(PartialEq, Deserialize, Serialize)]
+impl Session {
+    /// Files can still finish writing after the sender cancelled; that does
+    /// not revive the session.
+    pub fn mark_received(&mut self, file_id: &str, path: Option<PathBuf>) {
+        self.in_progress.remove(file_id);
+        self.received.insert(file_id.to_string(), path);
+        if self.status == SessionStatus::Active && self.file_tokens.keys().all(|id| self.received.contains_key(id)) {
+            self.status = SessionStatus::Completed;
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
 pub enum SessionStatus {
     Pending,
     Active,
@@ -0,0 +1,35 @@
+use std::fs::{File, FileTimes};
+use std::path::Path;
+
+use crate::models::file::FileMetadataExt;
+
+/// Router extension carrying [`Client::preserve_timestamps`](crate::Client).
+#[derive(Debug, Clone, Copy)]
+pub struct PreserveTimestamps(pub bool);
+
+/// Applies the sender's modification and access times to a received file so
+/// galleries sort photos by when they were taken rather than when they
+/// arrived. Missing or unparseable values leave that timestamp untouched.
+///
+/// Returns whether anything was applied.
+pub async fn apply_timestamps(path: &Path, metadata: &FileMetadataExt) -> std::io::Result<bool> {
+    let mut times = FileTimes::new();
+    let mut changed = false;
+    if let Some(modified) = metadata.modified_time() {
+        times = times.set_modified(modified);
+        changed = true;
+    }
+    if let Some(accessed) = metadata.accessed_time() {
+        times = times.set_accessed(accessed);
+        changed = true;
+    }
+    if !changed {
+        return Ok(false);
+    }
+
+    let path = path.to_path_buf();
+    tokio::task::spawn_blocking(move || File::options().write(true).open(path)?.set_times(times))
+        .await
+        .map_err(std::io::Error::other)??;
+    Ok(true)
+}
@@ -1,29 +1,55 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
 use std::net::SocketAddr;
 use std::path::PathBuf;
 use std::sync::Arc;
 
 use axum::body::Bytes;
+use axum::extract::rejection::JsonRejection;
 use axum::extract::{ConnectInfo, Query};
 use axum::Extension;
-use axum::{response::IntoResponse, Json};
+use axum::response::{IntoResponse, Response};
+use axum::Json;
 use axum::http::StatusCode;
 
-use native_dialog::MessageDialog;
 use serde::{Deserialize, Serialize};
-use tokio::sync::Mutex;
+use tracing::{debug, error, field, info, warn, Instrument, Span};
 use uuid::Uuid;
-use crate::error::{LocalSendError, Result};
+use crate::clock::Clock;
+use crate::error::{body_text, LocalSendError, Result};
+use crate::events::{Event, Events};
+use crate::net::{base_url, canonical};
+use crate::platform::Platform;
+use crate::redact::{Redacted, RedactedTokens};
+use crate::transfer::accept::AcceptPolicy;
+use crate::transfer::conflict::{can_skip, write_with_policy, ConflictPolicy, WriteOutcome};
+use crate::transfer::hooks::{HookPipeline, ReceivedFile};
+use crate::transfer::paths::{create_dir_within, ensure_within, ReceiveTarget};
+use crate::transfer::routing::RoutingTable;
 use crate::transfer::session::{Session, SessionStatus};
-use crate::{models::{device::DeviceInfo, file::FileMetadata}, Client};
+use crate::transfer::timestamps::{apply_timestamps, PreserveTimestamps};
+use crate::{models::{device::DeviceInfo, file::FileMetadata}, Client, Sessions};
 
-#[derive(Debug, Serialize, Deserialize)]
+/// Decides whether an intercepted `text/plain` payload is forwarded to the App.
+/// Returning `false` drops the text silently (e.g. a clipboard echo).
+pub type TextFilter = Arc<dyn Fn(&str) -> bool + Send + Sync>;
+
+#[derive(Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct PrepareUploadResponse {
     pub session_id: String,
     pub files: HashMap<String, String>,
 }
 
+impl fmt::Debug for PrepareUploadResponse {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PrepareUploadResponse")
+            .field("session_id", &self.session_id)
+            .field("files", &RedactedTokens(&self.files))
+            .finish()
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct PrepareUploadRequest {
@@ -32,17 +58,14 @@ pub struct PrepareUploadRequest {
 }
 
 impl Client {
+    #[tracing::instrument(skip_all, fields(peer = %peer, session_id = field::Empty))]
     pub async fn prepare_upload(&self, peer: String, files: HashMap<String, FileMetadata>) -> Result<PrepareUploadResponse> {
-        if !self.peers.lock().await.contains_key(&peer) {
-            return Err(LocalSendError::PeerNotFound);
-        }
-
-        let peer = self.peers.lock().await.get(&peer).unwrap().clone();
-        println!("Peer: {:?}", peer);
+        let peer = self.peers.lock().await.get(&peer).cloned().ok_or(LocalSendError::PeerNotFound)?;
+        debug!(addr = %peer.0, alias = %peer.1.alias, files = files.len(), "preparing upload");
 
         let response = self
             .http_client
-            .post(&format!("{}://{}/api/localsend/v2/prepare-upload", peer.1.protocol, peer.0.clone()))
+            .post(format!("{}/api/localsend/v2/prepare-upload", base_url(&peer.1.protocol, &peer.0)))
             .json(&PrepareUploadRequest {
                 info: self.device.clone(),
                 files: files.clone(),
@@ -50,9 +73,22 @@ impl Client {
             .send()
             .await?;
 
-        println!("Response: {:?}", response);
+        debug!(status = %response.status(), "prepare-upload answered");
+
+        if !response.status().is_success() {
+            return Err(LocalSendError::from_response(peer.0, response).await);
+        }
+
+        // 204: the receiver already has everything, nothing to upload
+        if response.status() == reqwest::StatusCode::NO_CONTENT {
+            return Ok(PrepareUploadResponse {
+                session_id: String::new(),
+                files: HashMap::new(),
+            });
+        }
 
         let response: PrepareUploadResponse = response.json().await?;
+        Span::current().record("session_id", field::display(&response.session_id));
 
         let session = Session {
             session_id: response.session_id.clone(),
@@ -62,6 +98,8 @@ impl Client {
             sender: self.device.clone(),
             status: SessionStatus::Active,
             addr: peer.0,
+            received: HashMap::new(),
+            in_progress: HashSet::new(),
         };
 
         self.sessions.lock().await.insert(response.session_id.clone(), session);
@@ -69,35 +107,59 @@ impl Client {
         Ok(response)
     }
 
+    #[tracing::instrument(skip_all, fields(session_id = %session_id, file_id = %file_id))]
     pub async fn upload(&self, session_id: String, file_id: String, token: String, body: Bytes) -> Result<()> {
-        let sessions = self.sessions.lock().await;
-        let session = sessions.get(&session_id).unwrap();
+        // Only hold the lock to validate; other transfers must not wait on this request
+        let base = {
+            let sessions = self.sessions.lock().await;
+            let session = sessions.get(&session_id).ok_or_else(|| LocalSendError::SessionNotFound(session_id.clone()))?;
 
-        if session.status != SessionStatus::Active {
-            return Err(LocalSendError::SessionInactive);
-        }
+            if session.status != SessionStatus::Active {
+                return Err(LocalSendError::SessionInactive);
+            }
 
-        if session.file_tokens.get(&file_id) != Some(&token) {
-            return Err(LocalSendError::InvalidToken);
-        }
+            if session.file_tokens.get(&file_id) != Some(&token) {
+                return Err(LocalSendError::InvalidToken);
+            }
 
+            base_url(&session.receiver.protocol, &session.addr)
+        };
+
+        debug!(bytes = body.len(), "uploading");
         let request = self
             .http_client
-            .post(&format!("{}://{}/api/localsend/v2/upload?sessionId={}&fileId={}&token={}", session.receiver.protocol, session.addr, session_id, file_id, token))
+            .post(format!("{}/api/localsend/v2/upload?sessionId={}&fileId={}&token={}", base, session_id, file_id, token))
             //.post(&format!("https://webhook.site/2f23a529-b687-4375-ad5f-54906ab26ac7?session_id={}&file_id={}&token={}", session_id, file_id, token))
             .body(body);
 
-        println!("Uploading file: {:?}", request);
-        let response = request.send().await?;
+        // The URL carries the upload token; keep it out of errors that end up in logs
+        let response = request.send().await.map_err(reqwest::Error::without_url)?;
 
         if response.status() != 200 {
-            println!("Upload failed: {:?}", response);
-            return Err(LocalSendError::UploadFailed);
+            warn!(status = %response.status(), "upload refused");
+            return Err(LocalSendError::UploadFailed {
+                session_id,
+                file_id,
+                status: response.status().as_u16(),
+                body: body_text(response).await,
+            });
+        }
+
+        // The sending side's session completes once every file went through
+        if let Some(session) = self.sessions.lock().await.get_mut(&session_id) {
+            session.mark_received(&file_id, None);
         }
 
         Ok(())
     }
 
+    /// Drops the sending side's record of a session started by `send_file`
+    /// or `send_text`; nothing refers to it once the call returns.
+    async fn finish_outgoing(&self, session_id: &str) {
+        self.sessions.lock().await.remove(session_id);
+    }
+
+    #[tracing::instrument(skip_all, fields(peer = %peer, path = %file_path.display()))]
     pub async fn send_file(&self, peer: String, file_path: PathBuf) -> Result<()> {
         // Generate file metadata
         let file_metadata = FileMetadata::from_path(&file_path)?;
@@ -107,219 +169,344 @@ impl Client {
         files.insert(file_metadata.id.clone(), file_metadata.clone());
 
         // Prepare upload
-        let prepare_response = self.prepare_upload(peer, files).await?;
-
-        // Get file token
-        let token = prepare_response.files.get(&file_metadata.id)
-            .ok_or(LocalSendError::InvalidToken)?;
-
-        // Read file contents
-        let file_contents = tokio::fs::read(&file_path).await?;
-        let bytes = Bytes::from(file_contents);
+        let response = self.prepare_upload(peer, files).await?;
+
+        // Get file token; none at all means the receiver skipped it
+        let result = match response.files.get(&file_metadata.id) {
+            None if response.files.is_empty() => Ok(()),
+            None => Err(LocalSendError::InvalidToken),
+            // Read file contents and upload
+            Some(token) => match tokio::fs::read(&file_path).await {
+                Ok(contents) => {
+                    self.upload(response.session_id.clone(), file_metadata.id, token.clone(), Bytes::from(contents)).await
+                }
+                Err(e) => Err(e.into()),
+            },
+        };
+        self.finish_outgoing(&response.session_id).await;
+        result
+    }
 
-        // Upload file
-        self.upload(
-            prepare_response.session_id,
-            file_metadata.id,
-            token.clone(),
-            bytes
-        ).await?;
+    /// Sends `text` the way LocalSend clients share clipboard content: a
+    /// single `text/plain` file the receiver shows instead of saving.
+    #[tracing::instrument(skip_all, fields(peer = %peer, text = %Redacted(text)))]
+    pub async fn send_text(&self, peer: String, text: &str) -> Result<()> {
+        let file_id = format!("sync_{}", Uuid::new_v4());
+        let mut files = HashMap::new();
+        files.insert(file_id.clone(), FileMetadata {
+            id: file_id.clone(),
+            file_name: "clipboard.txt".to_string(),
+            size: text.len() as u64,
+            file_type: "text/plain".to_string(),
+            sha256: None,
+            preview: None,
+            metadata: None,
+        });
 
-        Ok(())
+        let response = self.prepare_upload(peer, files).await?;
+        let result = match response.files.get(&file_id) {
+            Some(token) => {
+                self.upload(response.session_id.clone(), file_id, token.clone(), Bytes::copy_from_slice(text.as_bytes())).await
+            }
+            None => Ok(()),
+        };
+        self.finish_outgoing(&response.session_id).await;
+        result
     }
 
+    #[tracing::instrument(skip_all, fields(session_id = %session_id))]
     pub async fn cancel_upload(&self, session_id: String) -> Result<()> {
-        let sessions = self.sessions.lock().await;
-        let session = sessions.get(&session_id).unwrap();
+        let base = {
+            let sessions = self.sessions.lock().await;
+            let session = sessions.get(&session_id).ok_or_else(|| LocalSendError::SessionNotFound(session_id.clone()))?;
+            base_url(&session.receiver.protocol, &session.addr)
+        };
 
         let request = self
             .http_client
-            .post(&format!("{}://{}/api/localsend/v2/cancel?sessionId={}", session.receiver.protocol, session.addr, session_id))
+            .post(format!("{}/api/localsend/v2/cancel?sessionId={}", base, session_id))
             .send()
             .await?;
 
         if request.status() != 200 {
-            return Err(LocalSendError::CancelFailed);
+            return Err(LocalSendError::CancelFailed {
+                session_id,
+                status: request.status().as_u16(),
+                body: body_text(request).await,
+            });
         }
 
         Ok(())
     }
 }
 
+#[allow(clippy::too_many_arguments)] // axum extractors
+#[tracing::instrument(skip_all, fields(peer = field::Empty, session_id = field::Empty))]
 pub async fn register_prepare_upload(
+    Query(params): Query<PrepareUploadParams>,
     Extension(client): Extension<DeviceInfo>,
-    Extension(sessions): Extension<Arc<Mutex<HashMap<String, Session>>>>,
+    Extension(sessions): Extension<Sessions>,
+    Extension(download_dir): Extension<String>,
+    Extension(routes): Extension<Arc<RoutingTable>>,
+    Extension(conflict_policy): Extension<ConflictPolicy>,
+    Extension(clock): Extension<Arc<dyn Clock>>,
+    Extension(accept_policy): Extension<AcceptPolicy>,
+    Extension(events): Extension<Events>,
     ConnectInfo(addr): ConnectInfo<SocketAddr>,
-    Json(req): Json<PrepareUploadRequest>,
-) -> impl IntoResponse {
-    println!("Received upload request from alias: {}", req.info.alias);
+    req: std::result::Result<Json<PrepareUploadRequest>, JsonRejection>,
+) -> Result<Response> {
+    let Json(req) = req.map_err(|e| LocalSendError::InvalidBody(e.body_text()))?;
+    Span::current().record("peer", field::display(&req.info.fingerprint));
+    info!(alias = %req.info.alias, %addr, files = req.files.len(), "incoming upload request");
+
+    // 🚀 守护进程模式缺省直接同意接收，无需弹窗；嵌入方可通过 AcceptPolicy 收紧
+    match accept_policy.check(&req.info, &req.files, params.pin.as_deref()) {
+        Ok(()) => {}
+        Err(LocalSendError::InvalidPin) => {
+            warn!(alias = %req.info.alias, pin_given = params.pin.is_some(), "🔒 PIN 缺失或错误，拒绝");
+            return Err(LocalSendError::InvalidPin);
+        }
+        Err(e) => {
+            info!(alias = %req.info.alias, "🚫 接收策略拒绝了该传输");
+            events.emit(Event::TransferRejected { sender: req.info });
+            return Err(e);
+        }
+    }
 
-    // 🚀 修复点：对于守护进程模式，直接自动同意接收，无需弹窗
-    let result = true;
+    let session_id = Uuid::new_v4().to_string();
+    Span::current().record("session_id", field::display(&session_id));
+
+    // 目标位置已有相同/更新的文件时不发 token，发送方就不会上传这一项
+    let mut file_tokens: HashMap<String, String> = HashMap::new();
+    for (id, file) in &req.files {
+        if file.file_type != "text/plain" {
+            if let Ok(target) = ReceiveTarget::plan(file, &req.info, &download_dir, &routes, clock.now()) {
+                if can_skip(&target.path(), file, conflict_policy).await {
+                    info!(file_id = %id, path = %target.path().display(), "⏭️ 跳过已存在的文件");
+                    continue;
+                }
+            }
+        }
+        file_tokens.insert(id.clone(), Uuid::new_v4().to_string());
+    }
 
-    if result {
-        let session_id = Uuid::new_v4().to_string();
+    // 协议约定：没有需要传输的文件时返回 204
+    if file_tokens.is_empty() && !req.files.is_empty() {
+        return Ok(StatusCode::NO_CONTENT.into_response());
+    }
 
-        let file_tokens: HashMap<String, String> = req.files.iter()
-                                                      .map(|(id, _)| (id.clone(), Uuid::new_v4().to_string())) // Replace with actual token logic
-                                                      .collect();
+    let session = Session {
+        session_id: session_id.clone(),
+        files: req.files.clone(),
+        file_tokens: file_tokens.clone(),
+        receiver: client.clone(),
+        sender: req.info.clone(),
+        status: SessionStatus::Active,
+        addr: canonical(addr),
+        received: HashMap::new(),
+        in_progress: HashSet::new(),
+    };
 
-        let session = Session {
-            session_id: session_id.clone(),
-            files: req.files.clone(),
-            file_tokens: file_tokens.clone(),
-            receiver: client.clone(),
-            sender: req.info.clone(),
-            status: SessionStatus::Active,
-            addr,
-        };
+    sessions.lock().await.insert(session_id.clone(), session);
+    events.emit(Event::TransferAccepted {
+        session_id: session_id.clone(),
+        sender: req.info.clone(),
+        files: file_tokens.len(),
+    });
+
+    Ok(Json(PrepareUploadResponse {
+        session_id,
+        files: file_tokens,
+    })
+    .into_response())
+}
 
-        sessions.lock().await.insert(session_id.clone(), session);
+#[derive(Deserialize)]
+pub struct PrepareUploadParams {
+    pin: Option<String>,
+}
 
-        return (StatusCode::OK,
-                Json(PrepareUploadResponse {
-                    session_id,
-                    files: file_tokens,
-                })).into_response();
-    } else {
-        return StatusCode::FORBIDDEN.into_response();
+/// Records an arrived file and tells the event sinks, including when it was
+/// the session's last one. The session may have been cancelled while the
+/// file was being written; it then stays cancelled.
+async fn finish_file(sessions: &Sessions, session_id: &str, file_id: &str, path: Option<PathBuf>, events: &Events) {
+    let completed = {
+        let mut sessions = sessions.lock().await;
+        let Some(session) = sessions.get_mut(session_id) else { return };
+        let was_active = session.status == SessionStatus::Active;
+        session.mark_received(file_id, path.clone());
+        was_active && session.status == SessionStatus::Completed
+    };
+    events.emit(Event::FileReceived {
+        session_id: session_id.to_string(),
+        file_id: file_id.to_string(),
+        path,
+    });
+    if completed {
+        events.emit(Event::SessionFinished { session_id: session_id.to_string(), status: SessionStatus::Completed });
     }
 }
 
+#[allow(clippy::too_many_arguments)] // axum extractors
+#[tracing::instrument(skip_all, fields(session_id = %params.session_id, file_id = %params.file_id, peer = field::Empty))]
 pub async fn register_upload(
     Query(params): Query<UploadParams>,
-    Extension(sessions): Extension<Arc<Mutex<HashMap<String, Session>>>>,
+    Extension(sessions): Extension<Sessions>,
     Extension(download_dir): Extension<String>,
+    Extension(text_filter): Extension<Option<TextFilter>>,
+    Extension(routes): Extension<Arc<RoutingTable>>,
+    Extension(conflict_policy): Extension<ConflictPolicy>,
+    Extension(PreserveTimestamps(preserve_timestamps)): Extension<PreserveTimestamps>,
+    Extension(hooks): Extension<HookPipeline>,
+    Extension(platform): Extension<Arc<dyn Platform>>,
+    Extension(clock): Extension<Arc<dyn Clock>>,
+    Extension(events): Extension<Events>,
     body: Bytes,
-) -> impl IntoResponse {
+) -> Result<StatusCode> {
     // Extract query parameters
     let session_id = &params.session_id;
     let file_id = &params.file_id;
     let token = &params.token;
 
-    // Get session and validate
-    let mut sessions_lock = sessions.lock().await;
-    let session = match sessions_lock.get_mut(session_id) {
-        Some(session) => session,
-        None => return StatusCode::BAD_REQUEST.into_response(),
-    };
+    // 🔓 只在校验时持有会话锁：落盘、剪贴板、过滤器都在锁外进行，多个上传/取消可并行
+    let (file_metadata, sender, _claim) = {
+        let mut sessions_lock = sessions.lock().await;
+        let session = sessions_lock
+            .get_mut(session_id)
+            .ok_or_else(|| LocalSendError::SessionNotFound(session_id.clone()))?;
 
+        if session.status != SessionStatus::Active {
+            return Err(LocalSendError::SessionInactive);
+        }
 
-    if session.status != SessionStatus::Active {
-        return StatusCode::BAD_REQUEST.into_response()
-    }
+        // Validate token
+        if session.file_tokens.get(file_id) != Some(&token.to_string()) {
+            return Err(LocalSendError::InvalidToken);
+        }
 
-    // Validate token
-    if session.file_tokens.get(file_id) != Some(&token.to_string()) {
-        return StatusCode::FORBIDDEN.into_response();
-    }
+        // Get file metadata
+        let file_metadata = session.files.get(file_id).cloned().ok_or(LocalSendError::InvalidToken)?;
 
-    // Get file metadata
-    let file_metadata = match session.files.get(file_id) {
-        Some(metadata) => metadata,
-        None => return (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            "File not found".to_string(),
-        )
-            .into_response(),
+        // 🔒 在锁内占用令牌：并发或重放的同一上传只有一个能写盘
+        if session.received.contains_key(file_id) || !session.in_progress.insert(file_id.clone()) {
+            return Err(LocalSendError::DuplicateUpload(file_id.clone()));
+        }
+        let claim = Claim { sessions: sessions.clone(), session_id: session_id.clone(), file_id: file_id.clone() };
+        (file_metadata, session.sender.clone(), claim)
     };
+    Span::current().record("peer", field::display(&sender.fingerprint));
 
     // ==========================================
     // 🚀 核心拦截逻辑：发现是纯文本，直接截胡并推给 App
     // ==========================================
     if file_metadata.file_type == "text/plain" {
         let text_content = String::from_utf8_lossy(&body).to_string();
-        println!("📥 拦截到纯文本/剪贴板数据，长度: {}", text_content.len());
-        
-        // 异步推给 Android App 的 LocalServerSocket
-        tokio::spawn(async move {
-            use tokio::net::UnixStream;
-            use tokio::io::AsyncWriteExt;
-            match UnixStream::connect("\0airsend_app_ipc").await {
-                Ok(mut stream) => {
-                    let _ = stream.write_all(text_content.as_bytes()).await;
-                    let _ = stream.shutdown().await;
-                    println!("✅ 成功将文本推送到 Android App IPC 总线");
-                }
-                Err(e) => println!("❌ 无法连接到 App IPC (请确保App在前台运行且已启动 Reverse IPC): {}", e),
+        info!(len = text_content.len(), "📥 拦截到纯文本/剪贴板数据");
+
+        if let Some(filter) = &text_filter {
+            if !filter(&text_content) {
+                debug!("🔁 文本被过滤器拦截 (回声/重复)，不再推送给 App");
+                finish_file(&sessions, session_id, file_id, None, &events).await;
+                return Ok(StatusCode::OK);
             }
+        }
+
+        events.emit(Event::TextReceived {
+            session_id: session_id.clone(),
+            sender,
+            text: text_content.clone(),
         });
 
+        // 交给平台层写入本机剪贴板 (Android 上即推给 App 的 LocalServerSocket)
+        let span = Span::current();
+        tokio::task::spawn_blocking(move || span.in_scope(|| match platform.set_clipboard(&text_content) {
+            Ok(()) => info!(platform = platform.name(), "✅ 成功将文本写入剪贴板"),
+            Err(e) => error!(platform = platform.name(), error = %e, "❌ 无法写入剪贴板 (Android 上请确保 App 已启动 Reverse IPC)"),
+        }));
+
         // 截胡成功，直接返回 200 OK，不要再去创建文件写磁盘了
-        return StatusCode::OK.into_response();
+        finish_file(&sessions, session_id, file_id, None, &events).await;
+        return Ok(StatusCode::OK);
     }
     // ==========================================
 
     // ==========================================
-    // 🧠 智能分流落盘路径
+    // 🧠 智能分流落盘路径：按路由表 (MIME/扩展名/发送方/大小) 选目录
+    // 📁 文件夹传输：file_name 可能是 `album/day1/img.jpg`，在目标目录下按需重建层级
+    // 🛡️ 守护进程以 root 运行：`../../data/adb/...` 之类的文件名必须在这里拦死
     // ==========================================
-    let actual_dir = if file_metadata.file_type.starts_with("image/") || file_metadata.file_type.starts_with("video/") {
-        "/sdcard/Pictures/AirSend".to_string()
-    } else {
-        download_dir.clone()
-    };
+    let target = ReceiveTarget::plan(&file_metadata, &sender, &download_dir, &routes, clock.now())
+        .inspect_err(|e| warn!(error = %e, "🚫 拒绝非法文件名"))?;
 
-    // Create directory if it doesn't exist
-    if let Err(e) = tokio::fs::create_dir_all(&actual_dir).await {
-        return (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            format!("Failed to create directory: {}", e),
-        )
-            .into_response();
-    }
+    // 目录里被预埋的符号链接同样可能把写入导向别处：逐级创建，遇到链接即拒绝
+    create_dir_within(&target.root, &target.dir)
+        .await
+        .inspect_err(|e| warn!(error = %e, "🚫 目标目录越界"))?;
+    ensure_within(&target.root, &target.dir)
+        .await
+        .inspect_err(|e| warn!(error = %e, "🚫 目标目录越界"))?;
 
     // ==========================================
-    // 🛡️ 核心：同名文件冲突解决策略 (Auto-rename)
+    // 🛡️ 核心：同名文件冲突解决策略 (重命名/覆盖/跳过相同/保留较新)
     // ==========================================
-    let mut final_file_name = file_metadata.file_name.clone();
-    let mut file_path = format!("{}/{}", actual_dir, final_file_name);
-    let mut counter = 1;
-
-    // 循环检测底层文件系统中该路径是否已被占用
-    while std::path::Path::new(&file_path).exists() {
-        let path = std::path::Path::new(&file_metadata.file_name);
-
-        // 提取文件名本体和扩展名 (例如: "photo.png" -> stem: "photo", ext: "png")
-        let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or(&file_metadata.file_name);
-        let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("");
-
-        // 组装新的带序号的文件名
-        if ext.is_empty() {
-            final_file_name = format!("{} ({})", stem, counter);
-        } else {
-            final_file_name = format!("{} ({}).{}", stem, counter, ext);
+    let written = match write_with_policy(&target.dir, &target.name, &body, conflict_policy, &file_metadata).await? {
+        WriteOutcome::Written(path) => path,
+        WriteOutcome::Skipped(path) => {
+            info!(path = %path.display(), "⏭️ 已存在相同/更新的文件，跳过写入");
+            finish_file(&sessions, session_id, file_id, Some(path), &events).await;
+            return Ok(StatusCode::OK);
         }
+    };
 
-        // 更新路径用于下一轮 exists() 探测
-        file_path = format!("{}/{}", actual_dir, final_file_name);
-        counter += 1;
+    // 🕰️ 沿用发送方的修改/访问时间，否则相册按"接收时间"排序会全乱
+    if preserve_timestamps {
+        if let Some(metadata) = &file_metadata.metadata {
+            if let Err(e) = apply_timestamps(&written, metadata).await {
+                warn!(path = %written.display(), error = %e, "⚠️ 无法还原文件时间戳");
+            }
+        }
     }
-    // ==========================================
 
-    // Write file (此时的 file_path 一定是安全的、未被占用的绝对路径)
-    if let Err(e) = tokio::fs::write(&file_path, body.clone()).await {
-        return (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            format!("Failed to write file: {}", e),
-        )
-            .into_response();
-    }
+    info!(path = %written.display(), bytes = body.len(), "📦 文件接收完成");
+    finish_file(&sessions, session_id, file_id, Some(written.clone()), &events).await;
 
     // ==========================================
-    // 📷 触发 Android 媒体扫描器
+    // 🪝 接收后处理流水线 (媒体扫描/通知/脚本/移动/解压)，不阻塞 HTTP 响应
     // ==========================================
-    if file_metadata.file_type.starts_with("image/") || file_metadata.file_type.starts_with("video/") {
-        let _ = std::process::Command::new("am")
-            .args(&[
-                "broadcast",
-                "-a", "android.intent.action.MEDIA_SCANNER_SCAN_FILE",
-                "-d", &format!("file://{}", file_path)
-            ])
-            .spawn();
-        println!("📸 媒体已落盘至 {}，并触发系统相册刷新", file_path);
+    if !hooks.is_empty() {
+        let received = ReceivedFile {
+            path: written,
+            file: file_metadata,
+            sender,
+        };
+        tokio::spawn(async move {
+            hooks.run(received).await;
+        }.in_current_span());
     }
 
-    StatusCode::OK.into_response()
+    Ok(StatusCode::OK)
+}
+
+/// A file whose upload is being written. Dropping it hands the token back,
+/// whether the write failed or the sender hung up mid-request, so the file
+/// can be sent again; once received it stays refused.
+struct Claim {
+    sessions: Sessions,
+    session_id: String,
+    file_id: String,
+}
+
+impl Drop for Claim {
+    fn drop(&mut self) {
+        let sessions = self.sessions.clone();
+        let session_id = std::mem::take(&mut self.session_id);
+        let file_id = std::mem::take(&mut self.file_id);
+        tokio::spawn(async move {
+            if let Some(session) = sessions.lock().await.get_mut(&session_id) {
+                session.in_progress.remove(&file_id);
+            }
+        });
+    }
 }
 
 // Query parameters struct
@@ -331,17 +518,20 @@ pub struct UploadParams {
     token: String,
 }
 
+#[tracing::instrument(skip_all, fields(session_id = %params.session_id))]
 pub async fn register_cancel(
     Query(params): Query<CancelParams>,
-    Extension(sessions): Extension<Arc<Mutex<HashMap<String, Session>>>>,
-) -> impl IntoResponse {
+    Extension(sessions): Extension<Sessions>,
+    Extension(events): Extension<Events>,
+) -> Result<StatusCode> {
     let mut sessions_lock = sessions.lock().await;
-    let session = match sessions_lock.get_mut(&params.session_id) {
-        Some(session) => session,
-        None => return StatusCode::BAD_REQUEST.into_response(),
-    };
+    let session = sessions_lock
+        .get_mut(&params.session_id)
+        .ok_or_else(|| LocalSendError::SessionNotFound(params.session_id.clone()))?;
     session.status = SessionStatus::Cancelled;
-    StatusCode::OK.into_response()
+    info!("🛑 发送方取消了传输");
+    events.emit(Event::SessionFinished { session_id: params.session_id, status: SessionStatus::Cancelled });
+    Ok(StatusCode::OK)
 }
 
 // Cancel parameters struct
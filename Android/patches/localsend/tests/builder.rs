@@ -0,0 +1,62 @@
+mod common;
+
+use std::net::Ipv4Addr;
+
+use common::free_port;
+use localsend::builder::ClientBuilder;
+use localsend::error::BuildError;
+use localsend::models::device::DeviceInfo;
+use localsend::net::TlsConfig;
+use localsend::transfer::accept::AcceptPolicy;
+use localsend::Client;
+
+async fn build(configure: impl FnOnce(ClientBuilder) -> ClientBuilder) -> Result<Client, BuildError> {
+    let builder = Client::builder().port(free_port()).bind_addr(Ipv4Addr::LOCALHOST.into()).multicast(false);
+    configure(builder).build().await
+}
+
+#[tokio::test]
+async fn applies_settings() {
+    let dir = tempfile::tempdir().unwrap();
+    let device = DeviceInfo { alias: "Built".to_string(), ..Default::default() };
+    let client = build(|b| b.device(device).storage_root(dir.path())).await.unwrap();
+
+    assert_eq!(client.device().alias, "Built");
+    assert_eq!(client.device().port, client.port());
+    assert_eq!(client.download_dir(), dir.path().join("Download/AirSend").to_string_lossy());
+}
+
+#[tokio::test]
+async fn rejects_invalid_settings() {
+    let blank = DeviceInfo { alias: " ".to_string(), ..Default::default() };
+    assert!(matches!(build(|b| b.device(blank)).await, Err(BuildError::EmptyAlias)));
+    assert!(matches!(build(|b| b.port(0)).await, Err(BuildError::ZeroPort)));
+
+    let gopher = DeviceInfo { protocol: "gopher".to_string(), ..Default::default() };
+    assert!(matches!(build(|b| b.device(gopher)).await, Err(BuildError::UnknownProtocol(p)) if p == "gopher"));
+
+    let unicast = Ipv4Addr::new(192, 168, 1, 1);
+    assert!(matches!(build(|b| b.multicast_group(unicast)).await, Err(BuildError::NotMulticast(ip)) if ip == unicast));
+
+    let pin = AcceptPolicy::Pin(String::new());
+    assert!(matches!(build(|b| b.accept_policy(pin)).await, Err(BuildError::EmptyPin)));
+
+    let file = tempfile::NamedTempFile::new().unwrap();
+    assert!(matches!(build(|b| b.download_dir(file.path())).await, Err(BuildError::NotADirectory(_))));
+}
+
+#[tokio::test]
+async fn custom_http_client_excludes_http_options() {
+    let http = localsend::net::http_client_builder().build().unwrap();
+    let result = build(|b| b.http_client(http).tls(TlsConfig::self_signed())).await;
+    assert!(matches!(result, Err(BuildError::HttpClientConflict)));
+
+    assert!(build(|b| b.tls(TlsConfig::self_signed()).configure_http(|h| h.no_proxy())).await.is_ok());
+}
+
+#[tokio::test]
+async fn reports_taken_port() {
+    let first = build(|b| b).await.unwrap();
+    let second = build(|b| b.port(first.port())).await;
+    assert!(matches!(second, Err(BuildError::Bind { .. })));
+}
@@ -0,0 +1,154 @@
+#![allow(dead_code)] // each test binary uses its own subset
+
+use std::net::{Ipv4Addr, SocketAddr, TcpListener};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use localsend::builder::ClientBuilder;
+use localsend::models::device::DeviceInfo;
+use localsend::platform::Platform;
+use localsend::transfer::hooks::HookPipeline;
+use localsend::transfer::routing::RoutingTable;
+use localsend::Client;
+use tempfile::TempDir;
+
+/// Records system calls instead of shelling out to `am` / `cmd` / `wl-copy`.
+#[derive(Default)]
+pub struct FakePlatform {
+    pub calls: Mutex<Vec<String>>,
+}
+
+impl FakePlatform {
+    pub fn calls(&self) -> Vec<String> {
+        self.calls.lock().unwrap().clone()
+    }
+}
+
+impl Platform for FakePlatform {
+    fn name(&self) -> &str {
+        "fake"
+    }
+
+    fn media_scan(&self, path: &Path) -> std::io::Result<()> {
+        self.calls.lock().unwrap().push(format!("scan {}", path.display()));
+        Ok(())
+    }
+
+    fn notify(&self, title: &str, body: &str) -> std::io::Result<()> {
+        self.calls.lock().unwrap().push(format!("notify {}: {}", title, body));
+        Ok(())
+    }
+
+    fn set_clipboard(&self, text: &str) -> std::io::Result<()> {
+        self.calls.lock().unwrap().push(format!("clipboard {}", text));
+        Ok(())
+    }
+
+    fn get_clipboard(&self) -> std::io::Result<Option<String>> {
+        Ok(None)
+    }
+
+    fn download_dir(&self) -> PathBuf {
+        PathBuf::from("/tmp")
+    }
+
+    fn pictures_dir(&self) -> PathBuf {
+        PathBuf::from("/tmp")
+    }
+
+    fn state_dir(&self) -> PathBuf {
+        PathBuf::from("/tmp")
+    }
+
+    fn screenshot_dirs(&self) -> Vec<PathBuf> {
+        Vec::new()
+    }
+}
+
+pub fn fake() -> (Arc<FakePlatform>, Arc<dyn Platform>) {
+    let platform = Arc::new(FakePlatform::default());
+    (platform.clone(), platform)
+}
+
+/// Polls `condition` for up to two seconds, for effects the server runs in
+/// the background after replying (clipboard pushes, hooks).
+pub async fn eventually(condition: impl Fn() -> bool) -> bool {
+    for _ in 0..100 {
+        if condition() {
+            return true;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    false
+}
+
+/// A port nothing is listening on right now.
+pub fn free_port() -> u16 {
+    TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port()
+}
+
+/// An in-process peer: a `Client` on its own port and temp download dir,
+/// wired to a [`FakePlatform`] so nothing leaves the test.
+pub struct Node {
+    pub client: Client,
+    pub platform: Arc<FakePlatform>,
+    pub dir: TempDir,
+}
+
+impl Node {
+    /// A client on a free loopback port with no multicast, no routes and
+    /// no hooks.
+    pub async fn new(alias: &str) -> Self {
+        Self::with(alias, |builder| builder).await
+    }
+
+    /// Like [`Node::new`], letting the test adjust the builder first.
+    pub async fn with(alias: &str, configure: impl FnOnce(ClientBuilder) -> ClientBuilder) -> Self {
+        let dir = tempfile::tempdir().unwrap();
+        let device = DeviceInfo { alias: alias.to_string(), protocol: "http".to_string(), ..Default::default() };
+        let (platform, shared) = fake();
+        let builder = Client::builder()
+            .device(device)
+            .port(free_port())
+            .bind_addr(Ipv4Addr::LOCALHOST.into())
+            .multicast(false)
+            .download_dir(dir.path())
+            .routes(RoutingTable { rules: Vec::new() })
+            .hooks(HookPipeline::new())
+            .platform(shared);
+        let client = configure(builder).build().await.unwrap();
+        Self { client, platform, dir }
+    }
+
+    /// Starts the HTTP server and waits until it answers.
+    pub async fn serve(self) -> Self {
+        let client = self.client.clone();
+        tokio::spawn(async move { client.start_http_server().await.unwrap() });
+        for _ in 0..100 {
+            if tokio::net::TcpStream::connect(self.addr()).await.is_ok() {
+                return self;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        panic!("server on {} did not come up", self.addr());
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        SocketAddr::from(([127, 0, 0, 1], self.client.port()))
+    }
+
+    pub fn fingerprint(&self) -> String {
+        self.client.device().fingerprint.clone()
+    }
+
+    /// Files in the download directory, sorted.
+    pub fn received(&self) -> Vec<String> {
+        let mut names: Vec<String> = std::fs::read_dir(self.dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+            .collect();
+        names.sort();
+        names
+    }
+}
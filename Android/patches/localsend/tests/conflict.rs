@@ -0,0 +1,140 @@
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+use chrono::{DateTime, Utc};
+use localsend::models::file::{FileMetadata, FileMetadataExt};
+use localsend::transfer::conflict::{can_skip, write_with_policy, ConflictPolicy, WriteOutcome};
+
+fn metadata(name: &str, body: &[u8], modified: Option<SystemTime>) -> FileMetadata {
+    FileMetadata {
+        id: "f1".to_string(),
+        file_name: name.to_string(),
+        size: body.len() as u64,
+        file_type: "text/plain".to_string(),
+        sha256: Some(sha256::digest(body)),
+        preview: None,
+        metadata: modified.map(|t| FileMetadataExt {
+            modified: Some(DateTime::<Utc>::from(t).to_rfc3339()),
+            accessed: None,
+        }),
+    }
+}
+
+fn existing(dir: &Path, name: &str, body: &[u8], modified: SystemTime) {
+    let path = dir.join(name);
+    std::fs::write(&path, body).unwrap();
+    std::fs::File::options().write(true).open(&path).unwrap().set_modified(modified).unwrap();
+}
+
+fn names(dir: &Path) -> Vec<String> {
+    let mut names: Vec<String> =
+        std::fs::read_dir(dir).unwrap().map(|e| e.unwrap().file_name().to_string_lossy().to_string()).collect();
+    names.sort();
+    names
+}
+
+fn hours_ago(hours: u64) -> SystemTime {
+    SystemTime::now() - Duration::from_secs(hours * 3600)
+}
+
+async fn write(dir: &Path, name: &str, body: &[u8], policy: ConflictPolicy, modified: Option<SystemTime>) -> WriteOutcome {
+    write_with_policy(dir, name, body, policy, &metadata(name, body, modified)).await.unwrap()
+}
+
+#[tokio::test]
+async fn rename_numbers_past_existing_copies() {
+    let dir = tempfile::tempdir().unwrap();
+    for name in ["a.txt", "a (1).txt", "a (2).txt", "a (4).txt"] {
+        std::fs::write(dir.path().join(name), name).unwrap();
+    }
+
+    let outcome = write(dir.path(), "a.txt", b"new", ConflictPolicy::Rename, None).await;
+    assert_eq!(outcome, WriteOutcome::Written(dir.path().join("a (3).txt")));
+    let outcome = write(dir.path(), "a.txt", b"newer", ConflictPolicy::Rename, None).await;
+    assert_eq!(outcome, WriteOutcome::Written(dir.path().join("a (5).txt")));
+    assert_eq!(std::fs::read(dir.path().join("a.txt")).unwrap(), b"a.txt");
+}
+
+#[tokio::test]
+async fn rename_handles_names_without_or_with_several_dots() {
+    let dir = tempfile::tempdir().unwrap();
+    for name in ["README", "backup.tar.gz", "a (1).txt"] {
+        write(dir.path(), name, b"1", ConflictPolicy::Rename, None).await;
+        write(dir.path(), name, b"2", ConflictPolicy::Rename, None).await;
+    }
+    assert_eq!(
+        names(dir.path()),
+        ["README", "README (1)", "a (1) (1).txt", "a (1).txt", "backup.tar (1).gz", "backup.tar.gz"]
+    );
+}
+
+#[tokio::test]
+async fn overwrite_replaces_in_place() {
+    let dir = tempfile::tempdir().unwrap();
+    existing(dir.path(), "a.txt", b"old contents", hours_ago(1));
+
+    let outcome = write(dir.path(), "a.txt", b"new", ConflictPolicy::Overwrite, None).await;
+    assert_eq!(outcome, WriteOutcome::Written(dir.path().join("a.txt")));
+    assert_eq!(std::fs::read(dir.path().join("a.txt")).unwrap(), b"new");
+    // No temporary file left behind
+    assert_eq!(names(dir.path()), ["a.txt"]);
+}
+
+#[tokio::test]
+async fn overwrite_creates_missing_files() {
+    let dir = tempfile::tempdir().unwrap();
+    write(dir.path(), "a.txt", b"new", ConflictPolicy::Overwrite, None).await;
+    assert_eq!(names(dir.path()), ["a.txt"]);
+    assert!(!can_skip(&dir.path().join("a.txt"), &metadata("a.txt", b"new", None), ConflictPolicy::Overwrite).await);
+}
+
+#[tokio::test]
+async fn skip_identical_compares_contents() {
+    let dir = tempfile::tempdir().unwrap();
+    existing(dir.path(), "a.txt", b"same", hours_ago(1));
+    let target = dir.path().join("a.txt");
+
+    assert!(can_skip(&target, &metadata("a.txt", b"same", None), ConflictPolicy::SkipIdentical).await);
+    assert!(!can_skip(&target, &metadata("a.txt", b"diff", None), ConflictPolicy::SkipIdentical).await);
+    let mut unhashed = metadata("a.txt", b"same", None);
+    unhashed.sha256 = None;
+    assert!(!can_skip(&target, &unhashed, ConflictPolicy::SkipIdentical).await);
+
+    assert_eq!(write(dir.path(), "a.txt", b"same", ConflictPolicy::SkipIdentical, None).await, WriteOutcome::Skipped(target));
+    let outcome = write(dir.path(), "a.txt", b"diff", ConflictPolicy::SkipIdentical, None).await;
+    assert_eq!(outcome, WriteOutcome::Written(dir.path().join("a (1).txt")));
+}
+
+#[tokio::test]
+async fn keep_newest_replaces_only_with_newer_copies() {
+    let dir = tempfile::tempdir().unwrap();
+    existing(dir.path(), "a.txt", b"existing", hours_ago(2));
+    let target = dir.path().join("a.txt");
+
+    // Older than what we have: skipped, before and after upload
+    assert!(can_skip(&target, &metadata("a.txt", b"older", Some(hours_ago(3))), ConflictPolicy::KeepNewest).await);
+    let outcome = write(dir.path(), "a.txt", b"older", ConflictPolicy::KeepNewest, Some(hours_ago(3))).await;
+    assert_eq!(outcome, WriteOutcome::Skipped(target.clone()));
+    assert_eq!(std::fs::read(&target).unwrap(), b"existing");
+
+    // Newer: replaced in place
+    assert!(!can_skip(&target, &metadata("a.txt", b"newer", Some(hours_ago(1))), ConflictPolicy::KeepNewest).await);
+    let outcome = write(dir.path(), "a.txt", b"newer", ConflictPolicy::KeepNewest, Some(hours_ago(1))).await;
+    assert_eq!(outcome, WriteOutcome::Written(target.clone()));
+    assert_eq!(std::fs::read(&target).unwrap(), b"newer");
+    assert_eq!(names(dir.path()), ["a.txt"]);
+}
+
+#[tokio::test]
+async fn keep_newest_without_a_timestamp_keeps_both() {
+    let dir = tempfile::tempdir().unwrap();
+    existing(dir.path(), "a.txt", b"existing", hours_ago(2));
+
+    assert!(!can_skip(&dir.path().join("a.txt"), &metadata("a.txt", b"x", None), ConflictPolicy::KeepNewest).await);
+    let outcome = write(dir.path(), "a.txt", b"undated", ConflictPolicy::KeepNewest, None).await;
+    assert_eq!(outcome, WriteOutcome::Written(dir.path().join("a (1).txt")));
+
+    // Nothing there yet: plain create
+    let outcome = write(dir.path(), "b.txt", b"new", ConflictPolicy::KeepNewest, Some(hours_ago(5))).await;
+    assert_eq!(outcome, WriteOutcome::Written(dir.path().join("b.txt")));
+}
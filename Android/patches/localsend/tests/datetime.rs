@@ -0,0 +1,70 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use localsend::models::file::{parse_datetime, FileMetadataExt};
+
+fn at(secs: u64, millis: u64) -> Option<SystemTime> {
+    Some(UNIX_EPOCH + Duration::from_secs(secs) + Duration::from_millis(millis))
+}
+
+/// 2024-03-07T12:34:56Z
+const T: u64 = 1_709_814_896;
+
+#[test]
+fn supported_formats() {
+    let cases = [
+        // RFC 3339, as the reference app sends it
+        ("2024-03-07T12:34:56Z", at(T, 0)),
+        ("2024-03-07T12:34:56.789Z", at(T, 789)),
+        ("2024-03-07T14:34:56+02:00", at(T, 0)),
+        ("2024-03-07T07:34:56.5-05:00", at(T, 500)),
+        // RFC 2822
+        ("Thu, 07 Mar 2024 12:34:56 +0000", at(T, 0)),
+        ("Thu, 07 Mar 2024 13:34:56 +0100", at(T, 0)),
+        // Naive, taken as UTC
+        ("2024-03-07T12:34:56", at(T, 0)),
+        ("2024-03-07 12:34:56", at(T, 0)),
+        ("2024-03-07 12:34:56.250", at(T, 250)),
+        // Epoch seconds and milliseconds
+        ("1709814896", at(T, 0)),
+        ("1709814896789", at(T, 789)),
+        ("0", at(0, 0)),
+        // Surrounding whitespace is ignored
+        ("  2024-03-07T12:34:56Z\n", at(T, 0)),
+    ];
+    for (input, expected) in cases {
+        assert_eq!(parse_datetime(input), expected, "{:?}", input);
+    }
+}
+
+#[test]
+fn seconds_and_milliseconds_boundary() {
+    // The largest value still read as seconds lands in the year 5138
+    assert_eq!(parse_datetime("99999999999"), at(99_999_999_999, 0));
+    // One more and it is milliseconds, early 1973
+    assert_eq!(parse_datetime("100000000000"), at(100_000_000, 0));
+    assert_eq!(parse_datetime("100000000001"), at(100_000_000, 1));
+}
+
+#[test]
+fn negative_epochs_predate_1970() {
+    assert_eq!(parse_datetime("-86400"), Some(UNIX_EPOCH - Duration::from_secs(86_400)));
+    assert_eq!(parse_datetime("-100000000000"), Some(UNIX_EPOCH - Duration::from_secs(100_000_000)));
+}
+
+#[test]
+fn garbage_is_rejected() {
+    for input in ["", "yesterday", "2024-13-01T00:00:00Z", "2024-03-07", "12:34:56", "1.5", "0x10", "99999999999999999999"] {
+        assert_eq!(parse_datetime(input), None, "{:?}", input);
+    }
+}
+
+#[test]
+fn metadata_accessors_parse_both_fields() {
+    let ext = FileMetadataExt { modified: Some("1709814896".to_string()), accessed: Some("2024-03-07T12:34:57Z".to_string()) };
+    assert_eq!(ext.modified_time(), at(T, 0));
+    assert_eq!(ext.accessed_time(), at(T + 1, 0));
+
+    let empty = FileMetadataExt { modified: None, accessed: Some("nonsense".to_string()) };
+    assert_eq!(empty.modified_time(), None);
+    assert_eq!(empty.accessed_time(), None);
+}
@@ -0,0 +1,95 @@
+mod common;
+
+use std::net::{Ipv4Addr, SocketAddr};
+use std::time::{Duration, Instant};
+
+use common::Node;
+use localsend::discovery::interfaces::NetInterface;
+use localsend::discovery::scan::{scan_targets, ScanOptions};
+use localsend::models::device::DeviceInfo;
+
+fn interface(name: &str, addr: [u8; 4]) -> NetInterface {
+    NetInterface { name: name.to_string(), ..with_prefix(addr, 24) }
+}
+
+fn with_prefix(addr: [u8; 4], prefix: u32) -> NetInterface {
+    NetInterface {
+        name: "wlan0".to_string(),
+        index: 2,
+        addr: Ipv4Addr::from(addr),
+        netmask: Ipv4Addr::from(u32::MAX.checked_shl(32 - prefix).unwrap_or(0)),
+    }
+}
+
+#[test]
+fn lan_interfaces_are_eligible() {
+    for name in ["wlan0", "eth0", "en0", "br-lan", "ap0", "swlan0"] {
+        assert!(interface(name, [192, 168, 1, 20]).is_eligible(), "{}", name);
+    }
+}
+
+#[test]
+fn tunnels_and_modems_are_excluded() {
+    for name in ["tun0", "ppp0", "rmnet_data0", "ccmni1", "dummy0", "ipsec0", "wg0"] {
+        assert!(!interface(name, [10, 8, 0, 2]).is_eligible(), "{}", name);
+    }
+}
+
+#[test]
+fn loopback_and_unspecified_addresses_are_excluded() {
+    assert!(!interface("lo", [127, 0, 0, 1]).is_eligible());
+    assert!(!interface("wlan0", [127, 0, 0, 1]).is_eligible());
+    assert!(!interface("wlan0", [0, 0, 0, 0]).is_eligible());
+}
+
+#[test]
+fn scan_covers_a_24_except_ourselves() {
+    let targets = scan_targets(&with_prefix([192, 168, 1, 20], 24));
+    assert_eq!(targets.len(), 253);
+    assert_eq!(targets.first(), Some(&Ipv4Addr::new(192, 168, 1, 1)));
+    assert_eq!(targets.last(), Some(&Ipv4Addr::new(192, 168, 1, 254)));
+    assert!(!targets.contains(&Ipv4Addr::new(192, 168, 1, 20)));
+}
+
+#[test]
+fn wide_subnets_are_narrowed_to_our_24() {
+    for prefix in [22, 16, 8] {
+        let targets = scan_targets(&with_prefix([10, 0, 5, 9], prefix));
+        assert_eq!(targets.len(), 253, "/{}", prefix);
+        assert!(targets.iter().all(|ip| ip.octets()[..3] == [10, 0, 5]), "/{}", prefix);
+    }
+    // A /23 is still small enough to scan whole
+    assert_eq!(scan_targets(&with_prefix([10, 0, 5, 9], 23)).len(), 509);
+}
+
+#[test]
+fn point_to_point_and_host_routes() {
+    assert_eq!(scan_targets(&with_prefix([10, 0, 0, 1], 31)), vec![Ipv4Addr::new(10, 0, 0, 0)]);
+    assert_eq!(scan_targets(&with_prefix([10, 0, 0, 0], 31)), vec![Ipv4Addr::new(10, 0, 0, 1)]);
+    assert!(scan_targets(&with_prefix([10, 0, 0, 1], 32)).is_empty());
+    assert_eq!(scan_targets(&with_prefix([10, 0, 0, 1], 30)), vec![Ipv4Addr::new(10, 0, 0, 2)]);
+}
+
+#[tokio::test]
+async fn https_scanner_falls_back_to_http_peers() {
+    let plain = Node::new("Plain").await.serve().await;
+    let scanner = Node::with("Scanner", |builder| {
+        builder.device(DeviceInfo { alias: "Scanner".to_string(), protocol: "https".to_string(), ..Default::default() })
+    })
+    .await;
+
+    let found = scanner.client.scan_hosts(vec![plain.addr()], ScanOptions::default()).await.unwrap();
+    assert_eq!(found, 1);
+    assert!(scanner.client.peers().lock().await.contains_key(&plain.fingerprint()));
+}
+
+#[tokio::test]
+async fn refused_hosts_are_skipped_quickly() {
+    let scanner = Node::new("Scanner").await;
+    let closed = SocketAddr::from(([127, 0, 0, 1], common::free_port()));
+    let options = ScanOptions { concurrency: 1, timeout: Duration::from_secs(5) };
+
+    let started = Instant::now();
+    assert_eq!(scanner.client.scan_hosts(vec![closed], options).await.unwrap(), 0);
+    assert!(started.elapsed() < Duration::from_secs(1));
+}
@@ -0,0 +1,186 @@
+//! Property tests feeding the server and the path/template helpers hostile
+//! input. Whatever a peer sends, the server must answer with a client error
+//! rather than a 5xx or a crashed task, and nothing may land outside the
+//! download directory.
+
+mod common;
+
+use std::path::{Component, Path};
+
+use common::Node;
+use localsend::models::device::DeviceInfo;
+use localsend::models::file::FileMetadata;
+use localsend::transfer::paths::sanitize_relative_path;
+use localsend::transfer::routing::{RouteRule, RoutingTable};
+use proptest::prelude::*;
+use serde_json::{json, Value};
+use tokio::runtime::Runtime;
+
+fn runtime() -> Runtime {
+    tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap()
+}
+
+/// A receiver plus a raw HTTP client to throw requests at it.
+struct Target {
+    rt: Runtime,
+    node: Node,
+    http: reqwest::Client,
+}
+
+impl Target {
+    fn new() -> Self {
+        let rt = runtime();
+        let node = rt.block_on(async { Node::new("Fuzzed").await.serve().await });
+        Self { rt, node, http: reqwest::Client::new() }
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("http://{}/api/localsend/v2/{}", self.node.addr(), path)
+    }
+
+    fn post(&self, path: &str, query: &[(&str, &str)], body: Vec<u8>) -> (u16, Vec<u8>) {
+        self.rt.block_on(async {
+            let request = self.http.post(self.url(path)).query(query).header("content-type", "application/json");
+            let response = request.body(body).send().await.unwrap();
+            (response.status().as_u16(), response.bytes().await.unwrap().to_vec())
+        })
+    }
+
+    fn prepare(&self, files: Value) -> (u16, Value) {
+        let body = json!({ "info": { "alias": "Fuzzer", "version": "2.1", "fingerprint": "fuzz" }, "files": files });
+        let (status, body) = self.post("prepare-upload", &[], body.to_string().into_bytes());
+        (status, serde_json::from_slice(&body).unwrap_or(Value::Null))
+    }
+
+    /// Every file under the download dir, which must be all that exists
+    /// of what the peer sent.
+    fn assert_contained(&self) {
+        fn walk(dir: &Path, root: &Path) {
+            for entry in std::fs::read_dir(dir).unwrap() {
+                let path = entry.unwrap().path();
+                assert!(path.starts_with(root));
+                if path.is_dir() && !path.is_symlink() {
+                    walk(&path, root);
+                }
+            }
+        }
+        walk(self.node.dir.path(), self.node.dir.path());
+    }
+}
+
+fn client_error(status: u16) -> bool {
+    (400..500).contains(&status)
+}
+
+/// File names a hostile sender might try.
+fn file_name() -> impl Strategy<Value = String> {
+    prop_oneof![
+        "[a-zA-Z0-9 ._-]{1,20}",
+        "(\\.\\./){1,4}[a-z]{1,8}",
+        "/[a-z]{1,8}(/[a-z]{1,8}){0,3}",
+        "[a-z]{1,4}(\\\\\\.\\.){1,3}\\\\[a-z]{1,4}",
+        "[A-Za-z]:[a-z\\\\]{0,10}",
+        ".{0,300}",
+    ]
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(64))]
+
+    #[test]
+    fn sanitized_paths_stay_relative(name in ".{0,200}") {
+        if let Ok(path) = sanitize_relative_path(&name) {
+            prop_assert!(path.components().all(|c| matches!(c, Component::Normal(_))), "{:?}", path);
+        }
+    }
+
+    #[test]
+    fn peer_controlled_placeholders_stay_inside(alias in ".{0,40}", fingerprint in ".{0,40}", file_name in file_name()) {
+        let sender = DeviceInfo { alias, fingerprint, ..Default::default() };
+        let file = FileMetadata {
+            id: "f".to_string(),
+            file_name,
+            size: 0,
+            file_type: "application/octet-stream".to_string(),
+            sha256: None,
+            preview: None,
+            metadata: None,
+        };
+        let routes = RoutingTable {
+            rules: vec![RouteRule { destination: "{sender}/{fingerprint}/{ext}".to_string(), ..Default::default() }],
+        };
+        let destination = routes.destination(&file, &sender, "/srv/download", chrono::Local::now());
+        prop_assert!(destination.starts_with("/srv/download"), "{:?}", destination);
+        prop_assert!(destination.components().all(|c| !matches!(c, Component::ParentDir)), "{:?}", destination);
+    }
+
+    #[test]
+    fn parses_any_file_metadata(raw in ".{0,200}") {
+        if let Ok(file) = serde_json::from_str::<FileMetadata>(&raw) {
+            if let Some(ext) = file.metadata {
+                let _ = (ext.modified_time(), ext.accessed_time());
+            }
+        }
+    }
+}
+
+#[test]
+fn server_survives_arbitrary_bodies() {
+    let target = Target::new();
+    proptest!(ProptestConfig::with_cases(64), |(body in proptest::collection::vec(any::<u8>(), 0..512), endpoint in prop_oneof!["register", "prepare-upload"])| {
+        let (status, _) = target.post(&endpoint, &[], body);
+        prop_assert!(status == 200 || client_error(status), "{} answered {}", endpoint, status);
+    });
+    // Still serving afterwards
+    let (status, _) = target.prepare(json!({}));
+    assert_eq!(status, 200);
+}
+
+#[test]
+fn server_survives_odd_json() {
+    let target = Target::new();
+    let odd = prop_oneof![
+        Just(Value::Null),
+        any::<i64>().prop_map(Value::from),
+        ".{0,20}".prop_map(Value::from),
+        proptest::collection::vec(any::<bool>(), 0..3).prop_map(|v| json!(v)),
+    ];
+    proptest!(ProptestConfig::with_cases(64), |(info in odd.clone(), files in odd)| {
+        let body = json!({ "info": info, "files": files }).to_string().into_bytes();
+        let (status, _) = target.post("prepare-upload", &[], body);
+        prop_assert!(status == 200 || client_error(status), "answered {}", status);
+    });
+}
+
+#[test]
+fn hostile_file_names_stay_inside() {
+    let target = Target::new();
+    proptest!(ProptestConfig::with_cases(64), |(name in file_name(), size in 0u64..64)| {
+        let (status, prepared) = target.prepare(json!({
+            "f": { "id": "f", "fileName": name, "size": size, "fileType": "application/octet-stream" }
+        }));
+        prop_assert!(matches!(status, 200 | 204) || client_error(status), "prepare answered {}", status);
+        if status == 200 {
+            let session = prepared["sessionId"].as_str().unwrap();
+            let token = prepared["files"]["f"].as_str().unwrap();
+            let query = [("sessionId", session), ("fileId", "f"), ("token", token)];
+            let (status, _) = target.post("upload", &query, vec![b'x'; size as usize]);
+            prop_assert!(status == 200 || client_error(status), "upload of {:?} answered {}", name, status);
+        }
+        target.assert_contained();
+    });
+}
+
+#[test]
+fn upload_rejects_unknown_parameters() {
+    let target = Target::new();
+    proptest!(ProptestConfig::with_cases(64), |(session in ".{0,40}", file in ".{0,40}", token in ".{0,40}", drop in 0usize..4)| {
+        let mut query = vec![("sessionId", session.as_str()), ("fileId", file.as_str()), ("token", token.as_str())];
+        query.truncate(3 - drop.min(3));
+        let (status, _) = target.post("upload", &query, b"data".to_vec());
+        prop_assert!(client_error(status), "answered {}", status);
+        let (status, _) = target.post("cancel", &query[..query.len().min(1)], Vec::new());
+        prop_assert!(client_error(status), "cancel answered {}", status);
+    });
+    target.assert_contained();
+}
@@ -0,0 +1,341 @@
+mod common;
+
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use common::fake;
+use localsend::models::{device::DeviceInfo, file::FileMetadata};
+use localsend::transfer::hooks::{
+    self, HookConfig, HookPipeline, HookStep, Move, PostReceiveHook, ReceivedFile, Script, Unzip,
+};
+
+fn received(path: &Path, file_type: &str) -> ReceivedFile {
+    let file_name = path.file_name().unwrap().to_string_lossy().to_string();
+    ReceivedFile {
+        path: path.to_path_buf(),
+        file: FileMetadata {
+            id: file_name.clone(),
+            file_name,
+            size: 0,
+            file_type: file_type.to_string(),
+            sha256: None,
+            preview: None,
+            metadata: None,
+        },
+        sender: DeviceInfo {
+            alias: "MacBook".to_string(),
+            fingerprint: "abc123".to_string(),
+            ..Default::default()
+        },
+    }
+}
+
+/// Records every call instead of touching the system.
+struct Recorder {
+    name: &'static str,
+    log: Arc<Mutex<Vec<(String, PathBuf)>>>,
+    fail: bool,
+}
+
+impl PostReceiveHook for Recorder {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn run(&self, received: &mut ReceivedFile) -> std::io::Result<()> {
+        self.log.lock().unwrap().push((self.name.to_string(), received.path.clone()));
+        if self.fail {
+            Err(std::io::Error::other("boom"))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[test]
+fn runs_hooks_in_order_past_failures() {
+    let log = Arc::new(Mutex::new(Vec::new()));
+    let mut pipeline = HookPipeline::new();
+    for (name, fail) in [("first", false), ("broken", true), ("last", false)] {
+        pipeline.push(Recorder { name, log: log.clone(), fail });
+    }
+
+    pipeline.run_blocking(received(Path::new("/tmp/a.jpg"), "image/jpeg"));
+    let names: Vec<_> = log.lock().unwrap().iter().map(|(n, _)| n.clone()).collect();
+    assert_eq!(names, ["first", "broken", "last"]);
+}
+
+#[test]
+fn later_hooks_see_moved_path() {
+    let dir = tempfile::tempdir().unwrap();
+    let source = dir.path().join("a.txt");
+    std::fs::write(&source, "hi").unwrap();
+    let elsewhere = dir.path().join("sorted");
+
+    let log = Arc::new(Mutex::new(Vec::new()));
+    let mut pipeline = HookPipeline::new();
+    pipeline
+        .push(Move { destination: elsewhere.clone() })
+        .push(Recorder { name: "after", log: log.clone(), fail: false });
+
+    let out = pipeline.run_blocking(received(&source, "text/markdown"));
+    assert_eq!(out.path, elsewhere.join("a.txt"));
+    assert!(!source.exists());
+    assert_eq!(log.lock().unwrap()[0].1, elsewhere.join("a.txt"));
+}
+
+#[test]
+fn move_keeps_existing_files() {
+    let dir = tempfile::tempdir().unwrap();
+    let elsewhere = dir.path().join("sorted");
+    std::fs::create_dir(&elsewhere).unwrap();
+    std::fs::write(elsewhere.join("a.txt"), "old").unwrap();
+    let source = dir.path().join("a.txt");
+    std::fs::write(&source, "new").unwrap();
+
+    let mut file = received(&source, "text/plain");
+    Move { destination: elsewhere.clone() }.run(&mut file).unwrap();
+    assert_eq!(file.path, elsewhere.join("a (1).txt"));
+    assert_eq!(std::fs::read_to_string(elsewhere.join("a.txt")).unwrap(), "old");
+}
+
+#[test]
+fn config_filters_by_mime_and_extension() {
+    let dir = tempfile::tempdir().unwrap();
+    let steps: Vec<HookStep> = serde_json::from_value(serde_json::json!([
+        { "type": "move", "mime": "image/*", "destination": dir.path().join("pics") },
+        { "type": "move", "extensions": ["pdf"], "destination": dir.path().join("docs") },
+    ]))
+    .unwrap();
+    assert_eq!(
+        steps[0].hook,
+        HookConfig::Move { destination: dir.path().join("pics").to_string_lossy().to_string() }
+    );
+    let pipeline = HookPipeline::from_config(&steps, fake().1);
+
+    let photo = dir.path().join("p.jpg");
+    let doc = dir.path().join("d.PDF");
+    let other = dir.path().join("x.bin");
+    for path in [&photo, &doc, &other] {
+        std::fs::write(path, "x").unwrap();
+    }
+
+    assert_eq!(pipeline.run_blocking(received(&photo, "image/jpeg")).path, dir.path().join("pics/p.jpg"));
+    assert_eq!(pipeline.run_blocking(received(&doc, "application/pdf")).path, dir.path().join("docs/d.PDF"));
+    assert_eq!(pipeline.run_blocking(received(&other, "application/octet-stream")).path, other);
+}
+
+#[test]
+fn default_steps_scan_only_media() {
+    let (calls, platform) = fake();
+    let pipeline = HookPipeline::from_config(&hooks::default_steps(), platform);
+    pipeline.run_blocking(received(Path::new("/x/a.jpg"), "image/jpeg"));
+    pipeline.run_blocking(received(Path::new("/x/b.mp4"), "video/mp4"));
+    pipeline.run_blocking(received(Path::new("/x/c.pdf"), "application/pdf"));
+    assert_eq!(*calls.calls.lock().unwrap(), ["scan /x/a.jpg", "scan /x/b.mp4"]);
+}
+
+#[test]
+fn mime_filters_ignore_case_like_routing() {
+    let (calls, platform) = fake();
+    let pipeline = HookPipeline::from_config(&hooks::default_steps(), platform);
+    pipeline.run_blocking(received(Path::new("/x/a.jpg"), "Image/JPEG"));
+    pipeline.run_blocking(received(Path::new("/x/b.mp4"), "VIDEO/mp4"));
+    pipeline.run_blocking(received(Path::new("/x/c.pdf"), "Application/PDF"));
+    assert_eq!(*calls.calls.lock().unwrap(), ["scan /x/a.jpg", "scan /x/b.mp4"]);
+
+    let dir = tempfile::tempdir().unwrap();
+    let steps: Vec<HookStep> = serde_json::from_value(serde_json::json!([
+        { "type": "move", "mime": "application/pdf", "destination": dir.path().join("docs") },
+    ]))
+    .unwrap();
+    let doc = dir.path().join("d.pdf");
+    std::fs::write(&doc, "x").unwrap();
+    let pipeline = HookPipeline::from_config(&steps, fake().1);
+    assert_eq!(pipeline.run_blocking(received(&doc, "Application/PDF")).path, dir.path().join("docs/d.pdf"));
+}
+
+#[test]
+fn notify_goes_through_platform() {
+    let (calls, platform) = fake();
+    let steps = [HookStep {
+        mime: None,
+        extensions: Vec::new(),
+        hook: HookConfig::Notify { title: "AirSend".to_string() },
+    }];
+    HookPipeline::from_config(&steps, platform).run_blocking(received(Path::new("/x/a.txt"), "text/plain"));
+    assert_eq!(*calls.calls.lock().unwrap(), ["notify AirSend: 已收到来自 MacBook 的文件: a.txt"]);
+}
+
+#[test]
+fn script_gets_file_details_in_env() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("note.md");
+    std::fs::write(&path, "x").unwrap();
+    let out = dir.path().join("env.txt");
+
+    let script = Script {
+        command: "sh".to_string(),
+        args: vec![
+            "-c".to_string(),
+            format!("echo \"$AIRSEND_NAME|$AIRSEND_MIME|$AIRSEND_SENDER|$AIRSEND_SENDER_FINGERPRINT\" > {}", out.display()),
+        ],
+        timeout: hooks::DEFAULT_SCRIPT_TIMEOUT,
+    };
+    script.run(&mut received(&path, "text/markdown")).unwrap();
+    assert_eq!(std::fs::read_to_string(&out).unwrap().trim(), "note.md|text/markdown|MacBook|abc123");
+
+    let failing = Script { command: "false".to_string(), args: Vec::new(), timeout: hooks::DEFAULT_SCRIPT_TIMEOUT };
+    assert!(failing.run(&mut received(&path, "text/markdown")).is_err());
+}
+
+#[test]
+fn unzip_extracts_and_refuses_traversal() {
+    let dir = tempfile::tempdir().unwrap();
+    let archive = dir.path().join("album.zip");
+    {
+        let mut zip = zip::ZipWriter::new(std::fs::File::create(&archive).unwrap());
+        let options = zip::write::SimpleFileOptions::default();
+        zip.start_file("day1/a.jpg", options).unwrap();
+        zip.write_all(b"jpeg").unwrap();
+        zip.start_file("../../escape.sh", options).unwrap();
+        zip.write_all(b"rm -rf /").unwrap();
+        zip.finish().unwrap();
+    }
+
+    let mut file = received(&archive, "application/zip");
+    let unzip = Unzip { delete_archive: true };
+    assert!(unzip.applies_to(&file));
+    unzip.run(&mut file).unwrap();
+
+    assert_eq!(file.path, dir.path().join("album"));
+    assert_eq!(std::fs::read(dir.path().join("album/day1/a.jpg")).unwrap(), b"jpeg");
+    assert!(!archive.exists());
+    assert!(!dir.path().parent().unwrap().join("escape.sh").exists());
+    assert!(!dir.path().join("escape.sh").exists());
+}
+
+#[test]
+fn hung_script_is_killed_after_its_timeout() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("a.txt");
+    std::fs::write(&path, "x").unwrap();
+    let marker = dir.path().join("finished");
+
+    let script = Script {
+        command: "sh".to_string(),
+        args: vec!["-c".to_string(), format!("sleep 5; touch {}", marker.display())],
+        timeout: Duration::from_millis(200),
+    };
+    let started = Instant::now();
+    let err = script.run(&mut received(&path, "text/plain")).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::TimedOut);
+    assert!(started.elapsed() < Duration::from_secs(2));
+    std::thread::sleep(Duration::from_millis(300));
+    assert!(!marker.exists());
+}
+
+#[test]
+fn script_timeout_is_configurable() {
+    let steps: Vec<HookStep> = serde_json::from_value(serde_json::json!([
+        { "type": "script", "command": "true" },
+        { "type": "script", "command": "true", "timeout_secs": 5 },
+    ]))
+    .unwrap();
+    assert_eq!(
+        steps[0].hook,
+        HookConfig::Script { command: "true".to_string(), args: Vec::new(), timeout_secs: 60 }
+    );
+    assert_eq!(
+        steps[1].hook,
+        HookConfig::Script { command: "true".to_string(), args: Vec::new(), timeout_secs: 5 }
+    );
+}
+
+#[test]
+fn move_numbers_past_several_existing_files() {
+    let dir = tempfile::tempdir().unwrap();
+    let elsewhere = dir.path().join("sorted");
+    std::fs::create_dir(&elsewhere).unwrap();
+    for name in ["a.txt", "a (1).txt"] {
+        std::fs::write(elsewhere.join(name), name).unwrap();
+    }
+    let source = dir.path().join("a.txt");
+    std::fs::write(&source, "new").unwrap();
+
+    let mut file = received(&source, "text/plain");
+    Move { destination: elsewhere.clone() }.run(&mut file).unwrap();
+    assert_eq!(file.path, elsewhere.join("a (2).txt"));
+    assert_eq!(std::fs::read_to_string(&file.path).unwrap(), "new");
+    assert_eq!(std::fs::read_to_string(elsewhere.join("a (1).txt")).unwrap(), "a (1).txt");
+    assert!(!source.exists());
+}
+
+fn write_zip(path: &Path, entries: &[(&str, &[u8])]) {
+    let mut zip = zip::ZipWriter::new(std::fs::File::create(path).unwrap());
+    let options = zip::write::SimpleFileOptions::default().compression_method(zip::CompressionMethod::Stored);
+    for (name, contents) in entries {
+        zip.start_file(*name, options).unwrap();
+        zip.write_all(contents).unwrap();
+    }
+    zip.finish().unwrap();
+}
+
+fn dir_names(dir: &Path) -> Vec<String> {
+    let mut names: Vec<String> =
+        std::fs::read_dir(dir).unwrap().map(|e| e.unwrap().file_name().to_string_lossy().to_string()).collect();
+    names.sort();
+    names
+}
+
+#[test]
+fn unzip_twice_keeps_both_trees() {
+    let dir = tempfile::tempdir().unwrap();
+    let archive = dir.path().join("album.zip");
+    write_zip(&archive, &[("a.jpg", b"first")]);
+    let unzip = Unzip { delete_archive: false };
+
+    let mut first = received(&archive, "application/zip");
+    unzip.run(&mut first).unwrap();
+    write_zip(&archive, &[("a.jpg", b"second")]);
+    let mut second = received(&archive, "application/zip");
+    unzip.run(&mut second).unwrap();
+
+    assert_eq!(first.path, dir.path().join("album"));
+    assert_eq!(second.path, dir.path().join("album (1)"));
+    assert_eq!(std::fs::read(dir.path().join("album/a.jpg")).unwrap(), b"first");
+    assert_eq!(std::fs::read(dir.path().join("album (1)/a.jpg")).unwrap(), b"second");
+    assert_eq!(dir_names(dir.path()), ["album", "album (1)", "album.zip"]);
+}
+
+#[test]
+fn unzip_keeps_the_first_of_duplicate_entries() {
+    let dir = tempfile::tempdir().unwrap();
+    let archive = dir.path().join("dupes.zip");
+    {
+        // ZipWriter refuses duplicate names, so the second one differs only in spelling
+        write_zip(&archive, &[("x/a.txt", b"first"), ("x//a.txt", b"second"), ("b.txt", b"b")]);
+    }
+    let mut file = received(&archive, "application/zip");
+    Unzip { delete_archive: false }.run(&mut file).unwrap();
+    assert_eq!(std::fs::read(dir.path().join("dupes/x/a.txt")).unwrap(), b"first");
+    assert_eq!(std::fs::read(dir.path().join("dupes/b.txt")).unwrap(), b"b");
+}
+
+#[test]
+fn failed_unzip_leaves_nothing_behind() {
+    let dir = tempfile::tempdir().unwrap();
+    let archive = dir.path().join("broken.zip");
+    write_zip(&archive, &[("a.txt", b"fine"), ("b.txt", b"CORRUPTED-PAYLOAD")]);
+    let mut bytes = std::fs::read(&archive).unwrap();
+    let at = bytes.windows(9).position(|w| w == b"CORRUPTED").unwrap();
+    bytes[at] = b'X';
+    std::fs::write(&archive, bytes).unwrap();
+
+    let mut file = received(&archive, "application/zip");
+    assert!(Unzip { delete_archive: true }.run(&mut file).is_err());
+    assert_eq!(file.path, archive);
+    assert_eq!(dir_names(dir.path()), ["broken.zip"]);
+}
@@ -0,0 +1,133 @@
+//! What the crate logs: transfers are traceable by session, file and peer,
+//! while upload tokens, PINs and clipboard text never reach the output.
+
+mod common;
+
+use std::io::Write;
+use std::path::Path;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
+
+use common::Node;
+use localsend::events::Event;
+use localsend::models::device::DeviceInfo;
+use localsend::oneshot::Target;
+use localsend::transfer::accept::AcceptPolicy;
+use localsend::transfer::upload::PrepareUploadResponse;
+
+const TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Everything logged by this test binary, at every level.
+fn captured() -> &'static Arc<Mutex<Vec<u8>>> {
+    static LOG: OnceLock<Arc<Mutex<Vec<u8>>>> = OnceLock::new();
+    LOG.get_or_init(|| {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let writer = log.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_max_level(tracing::Level::TRACE)
+            .with_ansi(false)
+            .with_writer(move || Capture(writer.clone()))
+            .finish();
+        tracing::subscriber::set_global_default(subscriber).unwrap();
+        log
+    })
+}
+
+fn log_text() -> String {
+    String::from_utf8_lossy(&captured().lock().unwrap()).to_string()
+}
+
+struct Capture(Arc<Mutex<Vec<u8>>>);
+
+impl Write for Capture {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+async fn connect(from: &Node, to: &Node) -> String {
+    let (fingerprint, _) = from.client.find_peer(&Target::Address(to.addr().to_string()), TIMEOUT).await.unwrap();
+    fingerprint
+}
+
+fn write_file(dir: &Path, name: &str, contents: &[u8]) -> std::path::PathBuf {
+    let path = dir.join(name);
+    std::fs::write(&path, contents).unwrap();
+    path
+}
+
+#[tokio::test]
+async fn transfers_are_logged_without_secrets() {
+    captured();
+    let a = Node::new("Sender").await.serve().await;
+    let b = Node::new("Receiver").await.serve().await;
+    let peer = connect(&a, &b).await;
+
+    a.client.send_file(peer.clone(), write_file(a.dir.path(), "report.pdf", b"pdf")).await.unwrap();
+    a.client.send_text(peer, "correct horse battery staple").await.unwrap();
+    assert!(common::eventually(|| !b.platform.calls().is_empty()).await);
+
+    let sessions = b.client.sessions().lock().await;
+    let tokens: Vec<String> = sessions.values().flat_map(|s| s.file_tokens.values().cloned()).collect();
+    let log = log_text();
+    assert_eq!(tokens.len(), 2);
+    for session in sessions.values() {
+        assert!(log.contains(&format!("session_id={}", session.session_id)), "{} not logged", session.session_id);
+        for file_id in session.file_tokens.keys() {
+            assert!(log.contains(&format!("file_id={}", file_id)), "{} not logged", file_id);
+        }
+    }
+    assert!(log.contains(&format!("peer={}", a.fingerprint())));
+    assert!(!log.contains("correct horse battery staple"), "clipboard text leaked:\n{}", log);
+    for token in tokens {
+        assert!(!log.contains(&token), "token leaked:\n{}", log);
+    }
+}
+
+#[tokio::test]
+async fn wrong_pin_is_not_logged() {
+    captured();
+    let b = Node::with("Locked", |builder| builder.accept_policy(AcceptPolicy::Pin("8642".into())))
+        .await
+        .serve()
+        .await;
+
+    let body = serde_json::json!({
+        "info": { "alias": "Guesser", "version": "2.1", "fingerprint": "guesser" },
+        "files": {}
+    });
+    let response = reqwest::Client::new()
+        .post(format!("http://{}/api/localsend/v2/prepare-upload?pin=975310", b.addr()))
+        .json(&body)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 401);
+
+    let log = log_text();
+    assert!(log.contains("pin_given=true"));
+    assert!(!log.contains("975310") && !log.contains("8642"), "PIN leaked:\n{}", log);
+}
+
+#[test]
+fn debug_output_hides_secrets() {
+    let response = PrepareUploadResponse {
+        session_id: "s1".to_string(),
+        files: [("f1".to_string(), "tok-secret".to_string())].into_iter().collect(),
+    };
+    let shown = format!("{:?}", response);
+    assert!(shown.contains("s1") && shown.contains("f1") && !shown.contains("tok-secret"), "{}", shown);
+
+    let event = Event::TextReceived {
+        session_id: "s1".to_string(),
+        sender: DeviceInfo::default(),
+        text: "my bank password".to_string(),
+    };
+    let shown = format!("{:?}", event);
+    assert!(shown.contains("<redacted, 16 bytes>") && !shown.contains("password"), "{}", shown);
+}
@@ -0,0 +1,406 @@
+//! Two in-process clients talking over localhost, covering the protocol end
+//! to end without any network beyond the loopback interface.
+
+mod common;
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::body::Bytes;
+use chrono::{Local, TimeZone};
+use common::Node;
+use localsend::clock::ManualClock;
+use localsend::error::LocalSendError;
+use localsend::events::Event;
+use localsend::models::file::FileMetadata;
+use localsend::oneshot::Target;
+use localsend::transfer::accept::AcceptPolicy;
+use localsend::transfer::conflict::ConflictPolicy;
+use localsend::transfer::routing::{RouteRule, RoutingTable};
+use localsend::transfer::session::SessionStatus;
+
+const TIMEOUT: Duration = Duration::from_secs(2);
+
+async fn pair() -> (Node, Node) {
+    let sender = Node::new("Sender").await.serve().await;
+    let receiver = Node::new("Receiver").await.serve().await;
+    (sender, receiver)
+}
+
+/// Makes `to` known to `from` the way a favourite or `--address` would.
+async fn connect(from: &Node, to: &Node) -> String {
+    let (fingerprint, _) = from.client.find_peer(&Target::Address(to.addr().to_string()), TIMEOUT).await.unwrap();
+    fingerprint
+}
+
+fn write_file(dir: &Path, name: &str, contents: &[u8]) -> PathBuf {
+    let path = dir.join(name);
+    std::fs::write(&path, contents).unwrap();
+    path
+}
+
+async fn status(node: &Node, session_id: &str) -> SessionStatus {
+    let sessions = node.client.sessions().lock().await;
+    sessions.get(session_id).expect("node has the session").status
+}
+
+#[tokio::test]
+async fn reports_the_address_it_listens_on() {
+    let node = Node::new("Node").await;
+    assert_eq!(node.client.http_addr(), None);
+    let node = node.serve().await;
+    assert_eq!(node.client.http_addr(), Some(node.addr()));
+}
+
+#[tokio::test]
+async fn register_and_info_discover_each_other() {
+    let (a, b) = pair().await;
+
+    a.client.announce_http(Some(b.addr()), "http").await.unwrap();
+    let peers = b.client.peers().lock().await;
+    let (addr, device) = peers.get(&a.fingerprint()).expect("receiver recorded the announcement");
+    assert_eq!(device.alias, "Sender");
+    assert_eq!(addr.port(), a.client.port());
+    drop(peers);
+
+    let info = a.client.fetch_info(b.addr(), TIMEOUT).await.unwrap();
+    assert_eq!(info.alias, "Receiver");
+    assert_eq!(info.fingerprint, b.fingerprint());
+    assert_eq!(info.protocol, "http");
+}
+
+#[tokio::test]
+async fn find_peer_by_address_records_peer() {
+    let (a, b) = pair().await;
+    assert_eq!(connect(&a, &b).await, b.fingerprint());
+    assert!(a.client.peers().lock().await.contains_key(&b.fingerprint()));
+
+    let nobody = format!("127.0.0.1:{}", common::free_port());
+    assert!(a.client.find_peer(&Target::Address(nobody), Duration::from_millis(300)).await.is_err());
+}
+
+#[tokio::test]
+async fn uploads_file_end_to_end() {
+    let (a, b) = pair().await;
+    let peer = connect(&a, &b).await;
+    let source = write_file(a.dir.path(), "report.bin", b"\x00\x01binary payload");
+
+    a.client.send_file(peer, source).await.unwrap();
+
+    assert_eq!(b.received(), ["report.bin"]);
+    assert_eq!(std::fs::read(b.dir.path().join("report.bin")).unwrap(), b"\x00\x01binary payload");
+    let sessions = b.client.sessions().lock().await;
+    let session = sessions.values().next().unwrap();
+    assert_eq!(session.status, SessionStatus::Completed);
+    assert_eq!(session.sender.alias, "Sender");
+}
+
+#[tokio::test]
+async fn sender_forgets_finished_sends() {
+    let (a, b) = pair().await;
+    let peer = connect(&a, &b).await;
+
+    a.client.send_file(peer.clone(), write_file(a.dir.path(), "a.bin", b"a")).await.unwrap();
+    a.client.send_text(peer.clone(), "hello").await.unwrap();
+    // A failed upload is forgotten as well
+    let source = write_file(a.dir.path(), "b.bin", b"b");
+    std::fs::remove_dir_all(b.dir.path()).unwrap();
+    std::fs::write(b.dir.path(), b"").unwrap();
+    assert!(a.client.send_file(peer, source).await.is_err());
+    std::fs::remove_file(b.dir.path()).unwrap();
+
+    assert!(a.client.sessions().lock().await.is_empty());
+    assert_eq!(b.client.sessions().lock().await.len(), 3);
+}
+
+#[tokio::test]
+async fn session_completes_after_last_file() {
+    let (a, b) = pair().await;
+    let peer = connect(&a, &b).await;
+    let first = FileMetadata::from_path(&write_file(a.dir.path(), "one.bin", b"1")).unwrap();
+    let second = FileMetadata::from_path(&write_file(a.dir.path(), "two.bin", b"2")).unwrap();
+    let files = HashMap::from([(first.id.clone(), first.clone()), (second.id.clone(), second.clone())]);
+
+    let response = a.client.prepare_upload(peer, files).await.unwrap();
+    assert_eq!(response.files.len(), 2);
+
+    let token = response.files[&first.id].clone();
+    a.client.upload(response.session_id.clone(), first.id, token, Bytes::from_static(b"1")).await.unwrap();
+    assert_eq!(status(&b, &response.session_id).await, SessionStatus::Active);
+    assert_eq!(status(&a, &response.session_id).await, SessionStatus::Active);
+
+    let token = response.files[&second.id].clone();
+    a.client.upload(response.session_id.clone(), second.id, token, Bytes::from_static(b"2")).await.unwrap();
+    assert_eq!(status(&b, &response.session_id).await, SessionStatus::Completed);
+    assert_eq!(status(&a, &response.session_id).await, SessionStatus::Completed);
+    assert_eq!(b.received(), ["one.bin", "two.bin"]);
+}
+
+#[tokio::test]
+async fn concurrent_duplicate_uploads_write_one_file() {
+    let (a, b) = pair().await;
+    let peer = connect(&a, &b).await;
+    // Rename would keep both copies as `x.bin` and `x (1).bin` if both got through
+    let file = FileMetadata::from_path(&write_file(a.dir.path(), "x.bin", &[7; 256 * 1024])).unwrap();
+    let response = a.client.prepare_upload(peer, HashMap::from([(file.id.clone(), file.clone())])).await.unwrap();
+    let token = response.files[&file.id].clone();
+    let body = Bytes::from(vec![7; 256 * 1024]);
+
+    let (first, second) = tokio::join!(
+        a.client.upload(response.session_id.clone(), file.id.clone(), token.clone(), body.clone()),
+        a.client.upload(response.session_id.clone(), file.id.clone(), token.clone(), body.clone()),
+    );
+    let conflicts = [&first, &second]
+        .into_iter()
+        .filter(|r| matches!(r, Err(LocalSendError::UploadFailed { status: 409, .. })))
+        .count();
+    assert!(first.is_ok() != second.is_ok(), "{:?} / {:?}", first, second);
+    assert_eq!(conflicts, 1);
+    assert_eq!(b.received(), ["x.bin"]);
+
+    // Replaying it after the fact is refused as well
+    let replay = a.client.upload(response.session_id, file.id, token, body).await;
+    assert!(replay.is_err());
+    assert_eq!(b.received(), ["x.bin"]);
+}
+
+#[tokio::test]
+async fn failed_upload_can_be_retried() {
+    let (a, b) = pair().await;
+    let peer = connect(&a, &b).await;
+    let download_dir = b.dir.path().to_path_buf();
+    std::fs::remove_dir_all(&download_dir).unwrap();
+    std::fs::write(&download_dir, b"").unwrap();
+
+    let file = FileMetadata::from_path(&write_file(a.dir.path(), "x.bin", b"data")).unwrap();
+    let response = a.client.prepare_upload(peer, HashMap::from([(file.id.clone(), file.clone())])).await.unwrap();
+    let token = response.files[&file.id].clone();
+    let upload = || a.client.upload(response.session_id.clone(), file.id.clone(), token.clone(), Bytes::from_static(b"data"));
+    assert!(matches!(upload().await, Err(LocalSendError::UploadFailed { status: 500, .. })));
+
+    std::fs::remove_file(&download_dir).unwrap();
+    std::fs::create_dir(&download_dir).unwrap();
+    // The token comes back once the failed handler has let go of it
+    let mut retried = upload().await;
+    for _ in 0..50 {
+        if !matches!(retried, Err(LocalSendError::UploadFailed { status: 409, .. })) {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        retried = upload().await;
+    }
+    retried.unwrap();
+    assert_eq!(b.received(), ["x.bin"]);
+    assert_eq!(status(&b, &response.session_id).await, SessionStatus::Completed);
+}
+
+#[tokio::test]
+async fn server_errors_do_not_leak_local_details() {
+    let (a, b) = pair().await;
+    let peer = connect(&a, &b).await;
+    // The download directory turns into a file, so writing fails with an OS error
+    let download_dir = b.dir.path().to_path_buf();
+    std::fs::remove_dir_all(&download_dir).unwrap();
+    std::fs::write(&download_dir, b"").unwrap();
+
+    let file = FileMetadata::from_path(&write_file(a.dir.path(), "x.bin", b"data")).unwrap();
+    let response = a.client.prepare_upload(peer, HashMap::from([(file.id.clone(), file.clone())])).await.unwrap();
+    let token = response.files[&file.id].clone();
+    let result = a.client.upload(response.session_id, file.id, token, Bytes::from_static(b"data")).await;
+    std::fs::remove_file(&download_dir).unwrap();
+    match result {
+        Err(LocalSendError::UploadFailed { status: 500, body, .. }) => {
+            assert!(body.contains("Internal server error"), "{}", body);
+            assert!(!body.contains(&*download_dir.to_string_lossy()), "{}", body);
+        }
+        other => panic!("unexpected {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn cancel_stops_session() {
+    let (a, b) = pair().await;
+    let peer = connect(&a, &b).await;
+    let file = FileMetadata::from_path(&write_file(a.dir.path(), "big.bin", b"data")).unwrap();
+    let response = a.client.prepare_upload(peer, HashMap::from([(file.id.clone(), file.clone())])).await.unwrap();
+
+    a.client.cancel_upload(response.session_id.clone()).await.unwrap();
+    assert_eq!(status(&b, &response.session_id).await, SessionStatus::Cancelled);
+
+    let token = response.files[&file.id].clone();
+    let result = a.client.upload(response.session_id, file.id, token, Bytes::from_static(b"data")).await;
+    match result {
+        Err(e @ LocalSendError::UploadFailed { status: 400, .. }) => {
+            assert!(e.to_string().contains("Session inactive"), "{}", e);
+            assert!(!e.is_retryable());
+        }
+        other => panic!("unexpected {:?}", other),
+    }
+    assert!(b.received().is_empty());
+}
+
+#[tokio::test]
+async fn text_goes_to_clipboard_not_disk() {
+    let (a, b) = pair().await;
+    let peer = connect(&a, &b).await;
+
+    a.client.send_text(peer, "hello from the loopback").await.unwrap();
+
+    assert!(common::eventually(|| !b.platform.calls().is_empty()).await);
+    assert_eq!(b.platform.calls(), ["clipboard hello from the loopback"]);
+    assert!(b.received().is_empty());
+}
+
+#[tokio::test]
+async fn text_filter_can_drop_text() {
+    let a = Node::new("Sender").await.serve().await;
+    let b = Node::with("Receiver", |builder| builder.text_filter(Arc::new(|text: &str| text != "echo")))
+        .await
+        .serve()
+        .await;
+    let peer = connect(&a, &b).await;
+
+    a.client.send_text(peer.clone(), "echo").await.unwrap();
+    a.client.send_text(peer, "fresh").await.unwrap();
+
+    assert!(common::eventually(|| !b.platform.calls().is_empty()).await);
+    assert_eq!(b.platform.calls(), ["clipboard fresh"]);
+    let sessions = b.client.sessions().lock().await;
+    assert!(sessions.values().all(|s| s.status == SessionStatus::Completed));
+}
+
+#[tokio::test]
+async fn name_conflicts_are_renamed() {
+    let (a, b) = pair().await;
+    let peer = connect(&a, &b).await;
+    let first = write_file(a.dir.path(), "photo.bin", b"first");
+
+    a.client.send_file(peer.clone(), first.clone()).await.unwrap();
+    std::fs::write(&first, b"second").unwrap();
+    a.client.send_file(peer, first).await.unwrap();
+
+    assert_eq!(b.received(), ["photo (1).bin", "photo.bin"]);
+    assert_eq!(std::fs::read(b.dir.path().join("photo.bin")).unwrap(), b"first");
+    assert_eq!(std::fs::read(b.dir.path().join("photo (1).bin")).unwrap(), b"second");
+}
+
+#[tokio::test]
+async fn identical_files_are_skipped() {
+    let a = Node::new("Sender").await.serve().await;
+    let b = Node::with("Receiver", |builder| builder.conflict_policy(ConflictPolicy::SkipIdentical))
+        .await
+        .serve()
+        .await;
+    let peer = connect(&a, &b).await;
+    let source = write_file(a.dir.path(), "same.bin", b"unchanged");
+
+    a.client.send_file(peer.clone(), source.clone()).await.unwrap();
+    a.client.send_file(peer, source).await.unwrap();
+
+    assert_eq!(b.received(), ["same.bin"]);
+    // The second prepare-upload answered 204, so no session was opened
+    assert_eq!(b.client.sessions().lock().await.len(), 1);
+}
+
+#[tokio::test]
+async fn routes_use_injected_clock() {
+    let clock = Arc::new(ManualClock::new(Local.with_ymd_and_hms(2024, 2, 29, 23, 59, 0).unwrap()));
+    let routes = RoutingTable {
+        rules: vec![RouteRule { extensions: vec!["bin".into()], destination: "{yyyy-mm}".into(), ..Default::default() }],
+    };
+    let a = Node::new("Sender").await.serve().await;
+    let b = Node::with("Receiver", |builder| builder.clock(clock.clone()).routes(routes)).await.serve().await;
+    let peer = connect(&a, &b).await;
+
+    a.client.send_file(peer.clone(), write_file(a.dir.path(), "leap.bin", b"1")).await.unwrap();
+    clock.advance(Duration::from_secs(60));
+    a.client.send_file(peer, write_file(a.dir.path(), "march.bin", b"2")).await.unwrap();
+
+    assert!(b.dir.path().join("2024-02/leap.bin").exists());
+    assert!(b.dir.path().join("2024-03/march.bin").exists());
+}
+
+#[tokio::test]
+async fn remembered_peers_are_announced_once() {
+    let (events, mut rx) = tokio::sync::mpsc::unbounded_channel();
+    let a = Node::with("Sender", |builder| builder.event_sink(events)).await;
+    let b = Node::new("Receiver").await;
+    let addr = "127.0.0.1:53317".parse().unwrap();
+
+    a.client.remember_peer(addr, b.client.device().clone()).await;
+    a.client.remember_peer(addr, b.client.device().clone()).await;
+
+    assert!(a.client.peers().lock().await.contains_key(&b.fingerprint()));
+    assert!(matches!(rx.try_recv(), Ok(Event::PeerDiscovered { device, .. }) if device.alias == "Receiver"));
+    assert!(rx.try_recv().is_err());
+}
+
+#[tokio::test]
+async fn accept_policy_turns_senders_away() {
+    let a = Node::new("Sender").await.serve().await;
+    let (events, mut rx) = tokio::sync::mpsc::unbounded_channel();
+    let b = Node::with("Receiver", |builder| {
+        builder.accept_policy(AcceptPolicy::Senders(vec!["friend".into()])).event_sink(events)
+    })
+    .await
+    .serve()
+    .await;
+    let peer = connect(&a, &b).await;
+
+    let result = a.client.send_file(peer, write_file(a.dir.path(), "a.bin", b"x")).await;
+    assert!(matches!(result, Err(LocalSendError::Rejected)));
+    assert!(b.received().is_empty());
+    assert!(matches!(rx.try_recv(), Ok(Event::TransferRejected { sender }) if sender.alias == "Sender"));
+}
+
+#[tokio::test]
+async fn pin_policy_needs_pin() {
+    let a = Node::new("Sender").await.serve().await;
+    let b = Node::with("Receiver", |builder| builder.accept_policy(AcceptPolicy::Pin("1234".into())))
+        .await
+        .serve()
+        .await;
+    let peer = connect(&a, &b).await;
+
+    let result = a.client.send_file(peer, write_file(a.dir.path(), "a.bin", b"x")).await;
+    assert!(matches!(result, Err(LocalSendError::InvalidPin)));
+    assert!(b.received().is_empty());
+}
+
+#[tokio::test]
+async fn events_follow_incoming_session() {
+    let (events, mut rx) = tokio::sync::mpsc::unbounded_channel();
+    let a = Node::new("Sender").await.serve().await;
+    let b = Node::with("Receiver", |builder| builder.event_sink(events)).await.serve().await;
+    let peer = connect(&a, &b).await;
+
+    a.client.send_file(peer, write_file(a.dir.path(), "a.bin", b"x")).await.unwrap();
+
+    let session_id = match rx.try_recv().unwrap() {
+        Event::TransferAccepted { session_id, sender, files } => {
+            assert_eq!((sender.alias.as_str(), files), ("Sender", 1));
+            session_id
+        }
+        other => panic!("unexpected {:?}", other),
+    };
+    assert!(matches!(rx.try_recv(), Ok(Event::FileReceived { path: Some(path), .. }) if path.ends_with("a.bin")));
+    assert!(matches!(
+        rx.try_recv(),
+        Ok(Event::SessionFinished { session_id: id, status: SessionStatus::Completed }) if id == session_id
+    ));
+    assert!(rx.try_recv().is_err());
+}
+
+#[tokio::test]
+async fn unknown_peer_and_session_are_errors() {
+    let a = Node::new("Sender").await;
+    let file = FileMetadata::from_path(&write_file(a.dir.path(), "a.bin", b"x")).unwrap();
+
+    let result = a.client.prepare_upload("nobody".into(), HashMap::from([(file.id.clone(), file.clone())])).await;
+    assert!(matches!(result, Err(LocalSendError::PeerNotFound)));
+    let result = a.client.upload("gone".into(), file.id, "t".into(), Bytes::from_static(b"x")).await;
+    assert!(matches!(result, Err(LocalSendError::SessionNotFound(id)) if id == "gone"));
+    assert!(matches!(a.client.cancel_upload("gone".into()).await, Err(LocalSendError::SessionNotFound(_))));
+}
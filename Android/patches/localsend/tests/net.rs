@@ -0,0 +1,80 @@
+//! Peer URLs: scoped IPv6 link-local addresses survive the trip through a
+//! URL host and back, and dual-stack addresses fold back to IPv4.
+
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV6};
+
+use localsend::net::{base_url, canonical, is_ipv6, parse_link_local_host};
+use proptest::prelude::*;
+
+/// Parses a URL from [`base_url`] the way reqwest does and resolves its host.
+fn round_trip(addr: SocketAddr) -> (Option<SocketAddr>, Option<u16>) {
+    let url = reqwest::Url::parse(&base_url("https", &addr)).unwrap();
+    let host = url.host_str().unwrap();
+    (parse_link_local_host(host), url.port())
+}
+
+fn scoped(ip: &str, port: u16, scope: u32) -> SocketAddr {
+    SocketAddr::V6(SocketAddrV6::new(ip.parse().unwrap(), port, 0, scope))
+}
+
+#[test]
+fn ipv4_urls_are_plain() {
+    let addr = SocketAddr::from((Ipv4Addr::new(192, 168, 1, 20), 53317));
+    assert_eq!(base_url("http", &addr), "http://192.168.1.20:53317");
+}
+
+#[test]
+fn ipv4_mapped_addresses_fold_to_ipv4() {
+    let mapped: SocketAddr = "[::ffff:192.168.1.20]:53317".parse().unwrap();
+    assert_eq!(canonical(mapped), "192.168.1.20:53317".parse().unwrap());
+    assert_eq!(base_url("https", &mapped), "https://192.168.1.20:53317");
+    assert!(!is_ipv6(&mapped));
+    assert_eq!(round_trip(mapped), (None, Some(53317)));
+}
+
+#[test]
+fn global_ipv6_is_bracketed() {
+    let addr: SocketAddr = "[2001:db8::7]:53317".parse().unwrap();
+    assert_eq!(base_url("https", &addr), "https://[2001:db8::7]:53317");
+    assert!(is_ipv6(&addr));
+    assert_eq!(round_trip(addr).0, None);
+}
+
+#[test]
+fn unscoped_link_local_is_bracketed() {
+    let addr = scoped("fe80::1", 53317, 0);
+    assert_eq!(base_url("https", &addr), "https://[fe80::1]:53317");
+}
+
+#[test]
+fn scoped_link_local_round_trips() {
+    let addr = scoped("fe80::1c2:3ff:fe04:506", 53317, 3);
+    assert_eq!(base_url("https", &addr), "https://fe80--1c2-3ff-fe04-506.s3.link-local.localsend:53317");
+    assert_eq!(round_trip(addr), (Some(scoped("fe80::1c2:3ff:fe04:506", 0, 3)), Some(53317)));
+}
+
+#[test]
+fn foreign_hosts_are_not_link_local() {
+    for host in [
+        "example.com",
+        "192.168.1.20",
+        "fe80--1.link-local.localsend",
+        "fe80--1.sx.link-local.localsend",
+        "nothex.s3.link-local.localsend",
+        "fe80--1.s3.link-local.localsend.evil.com",
+    ] {
+        assert_eq!(parse_link_local_host(host), None, "{}", host);
+    }
+}
+
+proptest! {
+    #[test]
+    fn any_scoped_link_local_round_trips(tail in any::<[u16; 7]>(), scope in 1u32.., port in 1u16..) {
+        let mut segments = [0xfe80; 8];
+        segments[1..].copy_from_slice(&tail);
+        let ip = Ipv6Addr::from(segments);
+        let addr = SocketAddr::V6(SocketAddrV6::new(ip, port, 0, scope));
+        let expected_port = (port != 443).then_some(port);
+        prop_assert_eq!(round_trip(addr), (Some(SocketAddr::V6(SocketAddrV6::new(ip, 0, 0, scope))), expected_port));
+    }
+}
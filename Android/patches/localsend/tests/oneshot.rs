@@ -0,0 +1,100 @@
+mod common;
+
+use std::time::{Duration, Instant};
+
+use common::Node;
+use localsend::error::LocalSendError;
+use localsend::oneshot::{Payload, Target};
+
+/// Starts `receiver.receive_once` in the background and waits until its
+/// server accepts connections.
+async fn receive_in_background(
+    receiver: &Node,
+    timeout: Duration,
+) -> tokio::task::JoinHandle<localsend::error::Result<localsend::oneshot::ReceiveReport>> {
+    let handle = tokio::spawn(receiver.client.clone().receive_once(Some(timeout)));
+    for _ in 0..100 {
+        if tokio::net::TcpStream::connect(receiver.addr()).await.is_ok() {
+            return handle;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    panic!("receive_once on {} did not come up", receiver.addr());
+}
+
+#[tokio::test]
+async fn send_once_to_address_reaches_receive_once() {
+    let receiver = Node::new("Receiver").await;
+    let sender = Node::new("Sender").await;
+    let receiving = receive_in_background(&receiver, Duration::from_secs(10)).await;
+
+    let path = sender.dir.path().join("report.pdf");
+    std::fs::write(&path, b"%PDF").unwrap();
+    let target = Target::Address(receiver.addr().to_string());
+    let report = sender.client.send_once(&target, Payload::Files(vec![path.clone()]), Duration::from_secs(5)).await.unwrap();
+    assert!(report.is_success(), "{:?}", report.failed);
+    assert_eq!(report.peer.alias, "Receiver");
+    assert_eq!(report.sent, [path.display().to_string()]);
+
+    let received = receiving.await.unwrap().unwrap();
+    assert_eq!(received.sender.alias, "Sender");
+    assert_eq!(received.files, [receiver.dir.path().join("report.pdf")]);
+    assert!(received.texts.is_empty());
+    assert_eq!(std::fs::read(&received.files[0]).unwrap(), b"%PDF");
+}
+
+#[tokio::test]
+async fn receive_once_captures_text_instead_of_the_clipboard() {
+    let receiver = Node::new("Receiver").await;
+    let sender = Node::new("Sender").await;
+    let receiving = receive_in_background(&receiver, Duration::from_secs(10)).await;
+
+    let target = Target::Address(receiver.addr().to_string());
+    let report = sender.client.send_once(&target, Payload::Text("hello".to_string()), Duration::from_secs(5)).await.unwrap();
+    assert_eq!(report.sent, ["text"]);
+
+    let received = receiving.await.unwrap().unwrap();
+    assert_eq!(received.texts, ["hello"]);
+    assert!(received.files.is_empty());
+    assert!(receiver.platform.calls().is_empty());
+}
+
+#[tokio::test]
+async fn peers_found_by_address_are_remembered_by_alias() {
+    let receiver = Node::new("Receiver").await.serve().await;
+    let sender = Node::new("Sender").await;
+
+    let target = Target::Address(receiver.addr().to_string());
+    let (fingerprint, device) = sender.client.find_peer(&target, Duration::from_secs(5)).await.unwrap();
+    assert_eq!(fingerprint, receiver.fingerprint());
+    assert_eq!(device.alias, "Receiver");
+
+    let again = sender.client.find_peer(&Target::Peer("RECEIVER".to_string()), Duration::from_secs(1)).await.unwrap();
+    assert_eq!(again.0, fingerprint);
+    let any = sender.client.find_peer(&Target::Any, Duration::from_secs(1)).await.unwrap();
+    assert_eq!(any.0, fingerprint);
+}
+
+/// `airsend send --direct --to <missing>` exits with code 3.
+#[tokio::test]
+async fn send_once_reports_a_missing_peer() {
+    let sender = Node::new("Sender").await;
+    let started = Instant::now();
+    let err = sender
+        .client
+        .send_once(&Target::Peer("Nobody".to_string()), Payload::Text("hi".to_string()), Duration::from_millis(500))
+        .await
+        .unwrap_err();
+    assert!(matches!(err, LocalSendError::PeerNotFound), "{:?}", err);
+    assert!(started.elapsed() < Duration::from_secs(3));
+}
+
+/// `airsend receive --once --timeout N` exits with code 3 when nobody sends.
+#[tokio::test]
+async fn receive_once_times_out() {
+    let receiver = Node::new("Receiver").await;
+    let started = Instant::now();
+    let err = receiver.client.clone().receive_once(Some(Duration::from_millis(300))).await.unwrap_err();
+    assert!(matches!(err, LocalSendError::Timeout), "{:?}", err);
+    assert!(started.elapsed() < Duration::from_secs(3));
+}
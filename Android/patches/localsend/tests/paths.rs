@@ -0,0 +1,167 @@
+use std::path::{Path, PathBuf};
+
+use localsend::error::LocalSendError;
+use localsend::models::file::FileMetadata;
+use localsend::transfer::paths::{
+    create_dir_within, ensure_within, resolve_within, sanitize_component, sanitize_relative_path, MAX_COMPONENT_BYTES,
+};
+
+fn rejected(name: &str) -> bool {
+    matches!(sanitize_relative_path(name), Err(LocalSendError::InvalidPath { .. }))
+}
+
+#[test]
+fn keeps_plain_and_nested_names() {
+    assert_eq!(sanitize_relative_path("photo.jpg").unwrap(), PathBuf::from("photo.jpg"));
+    assert_eq!(
+        sanitize_relative_path("album/day1/img.jpg").unwrap(),
+        PathBuf::from("album/day1/img.jpg")
+    );
+    assert_eq!(
+        sanitize_relative_path("album\\day1\\img.jpg").unwrap(),
+        PathBuf::from("album/day1/img.jpg")
+    );
+    assert_eq!(sanitize_relative_path("./a//b/./c.txt").unwrap(), PathBuf::from("a/b/c.txt"));
+}
+
+#[test]
+fn rejects_traversal() {
+    assert!(rejected("../../data/adb/service.d/evil.sh"));
+    assert!(rejected("album/../../etc/passwd"));
+    assert!(rejected("..\\..\\windows\\system32"));
+    assert!(rejected(".."));
+    assert!(rejected("a/ .. /b"));
+}
+
+#[test]
+fn rejects_absolute_and_drive_paths() {
+    assert!(rejected("/data/adb/modules/x"));
+    assert!(rejected("\\\\server\\share\\x"));
+    assert!(rejected("C:\\Windows\\x.dll"));
+    assert!(rejected("c:evil"));
+}
+
+#[test]
+fn rejects_nul_and_empty() {
+    assert!(rejected("evil.txt\0.jpg"));
+    assert!(rejected(""));
+    assert!(rejected("/"));
+    assert!(rejected("./."));
+    assert!(rejected("   "));
+}
+
+#[test]
+fn strips_control_and_reserved_characters() {
+    assert_eq!(sanitize_component("a\u{7}b\r\nc.txt").unwrap(), "abc.txt");
+    assert_eq!(sanitize_component("what?<is>:this|\"*.txt").unwrap(), "what__is__this___.txt");
+    assert_eq!(sanitize_component("  trailing dots... ").unwrap(), "trailing dots");
+    assert!(sanitize_component("\u{1b}\u{7f}").is_err());
+}
+
+#[test]
+fn prefixes_windows_device_names() {
+    assert_eq!(sanitize_component("CON").unwrap(), "_CON");
+    assert_eq!(sanitize_component("com1.txt").unwrap(), "_com1.txt");
+    assert_eq!(sanitize_component("lpt9.tar.gz").unwrap(), "_lpt9.tar.gz");
+    assert_eq!(sanitize_component("console.log").unwrap(), "console.log");
+}
+
+#[test]
+fn truncates_overlong_names_keeping_extension() {
+    let long = format!("{}.jpg", "a".repeat(400));
+    let name = sanitize_component(&long).unwrap();
+    assert_eq!(name.len(), MAX_COMPONENT_BYTES);
+    assert!(name.ends_with(".jpg"));
+
+    // Multi-byte characters must not be split
+    let wide = format!("{}.png", "照".repeat(200));
+    let name = sanitize_component(&wide).unwrap();
+    assert!(name.len() <= MAX_COMPONENT_BYTES);
+    assert!(name.ends_with(".png"));
+}
+
+#[test]
+fn rejects_excessive_depth() {
+    let deep = vec!["d"; 64].join("/") + "/f.txt";
+    assert!(rejected(&deep));
+}
+
+#[test]
+fn normalizes_unicode_to_nfc() {
+    // "é" as e + combining acute (macOS NFD) becomes the precomposed form
+    let nfd = "cafe\u{301}.txt";
+    assert_eq!(sanitize_relative_path(nfd).unwrap(), PathBuf::from("caf\u{e9}.txt"));
+}
+
+#[test]
+fn resolve_within_stays_under_root() {
+    let root = Path::new("/sdcard/Download/AirSend");
+    assert_eq!(
+        resolve_within(root, Path::new("album/day1")).unwrap(),
+        root.join("album/day1")
+    );
+    assert!(resolve_within(root, Path::new("../x")).is_err());
+    assert!(resolve_within(root, Path::new("/etc")).is_err());
+}
+
+#[tokio::test]
+async fn ensure_within_catches_symlink_escape() {
+    let root = tempfile::tempdir().unwrap();
+    let outside = tempfile::tempdir().unwrap();
+    let inside = root.path().join("album");
+    std::fs::create_dir(&inside).unwrap();
+    assert!(ensure_within(root.path(), &inside).await.is_ok());
+
+    let link = root.path().join("link");
+    std::os::unix::fs::symlink(outside.path(), &link).unwrap();
+    assert!(matches!(
+        ensure_within(root.path(), &link).await,
+        Err(LocalSendError::InvalidPath { .. })
+    ));
+}
+
+#[tokio::test]
+async fn create_dir_within_builds_nested_dirs() {
+    let root = tempfile::tempdir().unwrap();
+    let routed = root.path().join("Pictures");
+    let dir = routed.join("album/day1");
+    create_dir_within(&routed, &dir).await.unwrap();
+    assert!(dir.is_dir());
+    // Existing directories are fine
+    create_dir_within(&routed, &dir).await.unwrap();
+    create_dir_within(&routed, &routed).await.unwrap();
+}
+
+#[tokio::test]
+async fn create_dir_within_never_creates_through_a_symlink() {
+    let root = tempfile::tempdir().unwrap();
+    let outside = tempfile::tempdir().unwrap();
+    std::os::unix::fs::symlink(outside.path(), root.path().join("album")).unwrap();
+
+    let result = create_dir_within(root.path(), &root.path().join("album/day1/deeper")).await;
+    assert!(matches!(result, Err(LocalSendError::InvalidPath { .. })), "{:?}", result);
+    assert!(std::fs::read_dir(outside.path()).unwrap().next().is_none(), "created outside the root");
+}
+
+#[tokio::test]
+async fn create_dir_within_refuses_files_and_foreign_dirs() {
+    let root = tempfile::tempdir().unwrap();
+    std::fs::write(root.path().join("album"), b"not a dir").unwrap();
+    assert!(create_dir_within(root.path(), &root.path().join("album/day1")).await.is_err());
+
+    let elsewhere = tempfile::tempdir().unwrap();
+    assert!(matches!(
+        create_dir_within(root.path(), elsewhere.path()).await,
+        Err(LocalSendError::InvalidPath { .. })
+    ));
+}
+
+#[test]
+fn metadata_refuses_non_utf8_names() {
+    use std::os::unix::ffi::OsStrExt;
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join(std::ffi::OsStr::from_bytes(b"photo-\xff.jpg"));
+    std::fs::write(&path, b"x").unwrap();
+    assert!(matches!(FileMetadata::from_path(&path), Err(LocalSendError::InvalidPath { .. })));
+}
@@ -0,0 +1,31 @@
+use std::io::ErrorKind;
+
+use localsend::platform::linux::ClipboardBackend;
+use localsend::platform::{Android, Linux, Platform};
+
+#[test]
+fn linux_file_clipboard_round_trips() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join("state/clipboard.txt");
+    let linux = Linux::with_clipboard(ClipboardBackend::File(file.clone()));
+
+    assert_eq!(linux.get_clipboard().unwrap(), None);
+    linux.set_clipboard("hello from the Mac").unwrap();
+    assert_eq!(std::fs::read_to_string(&file).unwrap(), "hello from the Mac");
+    assert_eq!(linux.get_clipboard().unwrap().as_deref(), Some("hello from the Mac"));
+}
+
+#[test]
+fn linux_media_scan_is_a_no_op() {
+    let linux = Linux::with_clipboard(ClipboardBackend::File("/nonexistent".into()));
+    assert!(linux.media_scan("/nonexistent/a.jpg".as_ref()).is_ok());
+    assert!(linux.download_dir().ends_with("AirSend"));
+    assert!(linux.state_dir().ends_with("airsend"));
+}
+
+#[test]
+fn android_keeps_its_fixed_paths() {
+    assert_eq!(Android.download_dir().to_str(), Some("/sdcard/Download/AirSend"));
+    assert_eq!(Android.pictures_dir().to_str(), Some("/sdcard/Pictures/AirSend"));
+    assert_eq!(Android.get_clipboard().unwrap_err().kind(), ErrorKind::Unsupported);
+}
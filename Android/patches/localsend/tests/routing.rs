@@ -0,0 +1,166 @@
+use std::path::PathBuf;
+
+use chrono::{DateTime, Local, TimeZone};
+use localsend::models::device::DeviceInfo;
+use localsend::models::file::FileMetadata;
+use localsend::transfer::routing::{render_template, RouteRule, RoutingTable};
+
+fn file(name: &str, mime: &str, size: u64) -> FileMetadata {
+    FileMetadata {
+        id: "f1".to_string(),
+        file_name: name.to_string(),
+        size,
+        file_type: mime.to_string(),
+        sha256: None,
+        preview: None,
+        metadata: None,
+    }
+}
+
+fn sender(alias: &str) -> DeviceInfo {
+    DeviceInfo { alias: alias.to_string(), fingerprint: "fp-123".to_string(), ..Default::default() }
+}
+
+fn now() -> DateTime<Local> {
+    Local.with_ymd_and_hms(2024, 3, 7, 12, 0, 0).unwrap()
+}
+
+fn render(template: &str) -> String {
+    render_template(template, &file("Photo.JPG", "image/jpeg", 10), &sender("Pixel 8"), now())
+}
+
+fn rule(destination: &str, configure: impl FnOnce(&mut RouteRule)) -> RouteRule {
+    let mut rule = RouteRule { destination: destination.to_string(), ..Default::default() };
+    configure(&mut rule);
+    rule
+}
+
+#[test]
+fn placeholders_are_filled() {
+    assert_eq!(render("{sender}/{fingerprint}"), "Pixel 8/fp-123");
+    assert_eq!(render("by-type/{mime}/{ext}"), "by-type/image/jpg");
+    assert_eq!(render("plain/path"), "plain/path");
+}
+
+#[test]
+fn date_patterns() {
+    assert_eq!(render("{yyyy}"), "2024");
+    assert_eq!(render("{yyyy-mm}"), "2024-03");
+    assert_eq!(render("{yyyy}/{mm}/{dd}"), "2024/03/07");
+    assert_eq!(render("{yyyy_mm_dd}"), "2024_03_07");
+    assert_eq!(render("{dd.mm.yyyy}"), "07.03.2024");
+}
+
+#[test]
+fn unknown_keys_are_kept_verbatim() {
+    assert_eq!(render("{senderr}/x"), "{senderr}/x");
+    assert_eq!(render("{yyyy-qq}"), "{yyyy-qq}");
+    assert_eq!(render("{}"), "{}");
+}
+
+#[test]
+fn unclosed_brace_is_literal() {
+    assert_eq!(render("abc{def"), "abc{def");
+    assert_eq!(render("{sender}/abc{def"), "Pixel 8/abc{def");
+    assert_eq!(render("{"), "{");
+    assert_eq!(render("a}b"), "a}b");
+}
+
+#[test]
+fn sender_values_stay_one_component() {
+    let template = "inbox/{sender}";
+    let rendered = |alias: &str| render_template(template, &file("a.txt", "text/plain", 1), &sender(alias), now());
+    assert_eq!(rendered("../../data/adb"), "inbox/.._.._data_adb");
+    assert_eq!(rendered(".."), "inbox/_");
+    assert_eq!(rendered("."), "inbox/_");
+    assert_eq!(rendered("a\\b"), "inbox/a_b");
+    assert_eq!(rendered("  "), "inbox/_");
+    assert_eq!(rendered("line\nbreak"), "inbox/line_break");
+}
+
+#[test]
+fn unmatched_files_go_to_the_download_dir() {
+    let table = RoutingTable { rules: vec![rule("Pictures", |r| r.mime = Some("image/*".into()))] };
+    let destination = table.destination(&file("a.pdf", "application/pdf", 1), &sender("Mac"), "/dl", now());
+    assert_eq!(destination, PathBuf::from("/dl"));
+}
+
+#[test]
+fn relative_destinations_sit_under_the_download_dir() {
+    let table = RoutingTable { rules: vec![rule("by-sender/{sender}", |_| {})] };
+    let destination = table.destination(&file("a.pdf", "application/pdf", 1), &sender("Mac"), "/dl", now());
+    assert_eq!(destination, PathBuf::from("/dl/by-sender/Mac"));
+
+    let table = RoutingTable { rules: vec![rule("/sdcard/Pictures/{yyyy}", |_| {})] };
+    let destination = table.destination(&file("a.pdf", "application/pdf", 1), &sender("Mac"), "/dl", now());
+    assert_eq!(destination, PathBuf::from("/sdcard/Pictures/2024"));
+}
+
+#[test]
+fn mime_wildcard_and_exact_match_ignore_case() {
+    let table = RoutingTable {
+        rules: vec![
+            rule("/exact", |r| r.mime = Some("application/PDF".into())),
+            rule("/images", |r| r.mime = Some("image/*".into())),
+        ],
+    };
+    let to = |mime: &str| table.destination(&file("x", mime, 1), &sender("Mac"), "/dl", now());
+    assert_eq!(to("application/pdf"), PathBuf::from("/exact"));
+    assert_eq!(to("image/png"), PathBuf::from("/images"));
+    assert_eq!(to("IMAGE/PNG"), PathBuf::from("/images"));
+    assert_eq!(to("imagery/png"), PathBuf::from("/dl"));
+    assert_eq!(to("video/mp4"), PathBuf::from("/dl"));
+}
+
+#[test]
+fn first_matching_rule_wins() {
+    let table = RoutingTable {
+        rules: vec![
+            rule("/big", |r| r.min_size = Some(1000)),
+            rule("/from-mac", |r| r.sender = Some("mac".into())),
+            rule("/docs", |r| r.extensions = vec![".PDF".into(), "txt".into()]),
+        ],
+    };
+    let to = |name: &str, size: u64, from: &str| {
+        table.destination(&file(name, "application/octet-stream", size), &sender(from), "/dl", now())
+    };
+    assert_eq!(to("a.pdf", 5000, "Mac"), PathBuf::from("/big"));
+    assert_eq!(to("a.pdf", 10, "Mac"), PathBuf::from("/from-mac"));
+    assert_eq!(to("a.Pdf", 10, "Phone"), PathBuf::from("/docs"));
+    assert_eq!(to("a.zip", 10, "Phone"), PathBuf::from("/dl"));
+}
+
+#[test]
+fn sender_matches_fingerprint_or_alias() {
+    let table = RoutingTable { rules: vec![rule("/known", |r| r.sender = Some("fp-123".into()))] };
+    let to = |alias: &str| table.destination(&file("a", "text/plain", 1), &sender(alias), "/dl", now());
+    assert_eq!(to("Anything"), PathBuf::from("/known"));
+
+    let table = RoutingTable { rules: vec![rule("/known", |r| r.sender = Some("FP-123".into()))] };
+    let to = |alias: &str| table.destination(&file("a", "text/plain", 1), &sender(alias), "/dl", now());
+    assert_eq!(to("Anything"), PathBuf::from("/dl"));
+}
+
+#[test]
+fn size_bounds_are_inclusive() {
+    let table = RoutingTable {
+        rules: vec![rule("/mid", |r| {
+            r.min_size = Some(10);
+            r.max_size = Some(20);
+        })],
+    };
+    let to = |size: u64| table.destination(&file("a", "text/plain", size), &sender("Mac"), "/dl", now());
+    assert_eq!(to(9), PathBuf::from("/dl"));
+    assert_eq!(to(10), PathBuf::from("/mid"));
+    assert_eq!(to(20), PathBuf::from("/mid"));
+    assert_eq!(to(21), PathBuf::from("/dl"));
+}
+
+#[test]
+fn default_table_sends_media_to_the_gallery() {
+    let table = RoutingTable::default();
+    let to = |mime: &str| table.destination(&file("x", mime, 1), &sender("Mac"), "/dl", now());
+    assert_eq!(to("image/heic"), PathBuf::from("/sdcard/Pictures/AirSend"));
+    assert_eq!(to("video/mp4"), PathBuf::from("/sdcard/Pictures/AirSend"));
+    assert_eq!(to("audio/mpeg"), PathBuf::from("/dl"));
+}
@@ -3,7 +3,9 @@
 
 MODDIR=${0%/*}
 DAEMON_BIN="/system/bin/airsend_daemon"
-LOG_PATH="/data/local/tmp/airsend_daemon.log"
+# 日志文件 airsend_daemon.log 由守护进程独占写入并自行轮转；
+# 本脚本的输出和守护进程的 stdout/stderr (早期启动失败、panic) 写到单独的文件，每次启动时覆盖
+OUT_PATH="/data/local/tmp/airsend_daemon.out"
 
 # 等待系统数据分区挂载完成（最多 60 秒）
 for i in $(seq 1 30); do
@@ -13,13 +15,13 @@ done
 
 # 防重复启动判断
 if pgrep -f "$DAEMON_BIN" > /dev/null; then
-    echo "$(date): AirSend daemon is already running, skipping..." >> "$LOG_PATH"
+    echo "$(date): AirSend daemon is already running, skipping..." >> "$OUT_PATH"
     exit 0
 fi
 
 # 启动守护进程
-echo "$(date): Starting AirSend daemon..." >> "$LOG_PATH"
-# 重定向标准输出和错误流到日志文件
-nohup "$DAEMON_BIN" >> "$LOG_PATH" 2>&1 &
+echo "$(date): Starting AirSend daemon..." > "$OUT_PATH"
+# 重定向标准输出和错误流到 .out 文件，不与守护进程的日志交错
+nohup "$DAEMON_BIN" >> "$OUT_PATH" 2>&1 &
 
-echo "$(date): AirSend daemon started in background." >> "$LOG_PATH"
+echo "$(date): AirSend daemon started in background." >> "$OUT_PATH"